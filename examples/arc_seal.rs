@@ -8,6 +8,7 @@
  * except according to those terms.
  */
 
+use mail_auth::dkim::verify::DkimVerifier;
 use mail_auth::{
     arc::ArcSealer,
     common::{
@@ -16,7 +17,6 @@ use mail_auth::{
     },
     AuthenticatedMessage, AuthenticationResults, Resolver,
 };
-use mail_auth::dkim::verify::DkimVerifier;
 
 const TEST_MESSAGE: &str = include_str!("../resources/arc/001.txt");
 
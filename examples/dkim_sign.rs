@@ -35,7 +35,6 @@ GMot/L2x0IYyMLAz6oLWh2hm7zwtb0CgOrPo1ke44hFYnfc=
 -----END RSA PRIVATE KEY-----"#;
 
 const ED25519_PRIVATE_KEY: &str = "nWGxne/9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A=";
-const ED25519_PUBLIC_KEY: &str = "11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=";
 
 const TEST_MESSAGE: &str = r#"From: bill@example.com
 To: jdoe@example.com
@@ -46,14 +45,7 @@ I'm going to need those TPS reports ASAP. So, if you could do that, that'd be gr
 
 fn main() {
     // Sign an e-mail message using RSA-SHA256
-    #[cfg(feature = "rust-crypto")]
-    let pk_rsa = RsaKey::<Sha256>::from_pkcs1_pem(RSA_PRIVATE_KEY).unwrap();
-    #[cfg(all(
-        feature = "ring",
-        feature = "rustls-pemfile",
-        not(feature = "rust-crypto")
-    ))]
-    let pk_rsa = RsaKey::<Sha256>::from_rsa_pem(RSA_PRIVATE_KEY).unwrap();
+    let pk_rsa = RsaKey::<Sha256>::from_private_key(RSA_PRIVATE_KEY.as_bytes()).unwrap();
 
     let signature_rsa = DkimSigner::from_key(pk_rsa)
         .domain("example.com")
@@ -63,15 +55,8 @@ fn main() {
         .unwrap();
 
     // Sign an e-mail message using ED25519-SHA256
-    #[cfg(feature = "rust-crypto")]
     let pk_ed =
-        Ed25519Key::from_bytes(&base64_decode(ED25519_PRIVATE_KEY.as_bytes()).unwrap()).unwrap();
-    #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
-    let pk_ed = Ed25519Key::from_seed_and_public_key(
-        &base64_decode(ED25519_PRIVATE_KEY.as_bytes()).unwrap(),
-        &base64_decode(ED25519_PUBLIC_KEY.rsplit_once("p=").unwrap().1.as_bytes()).unwrap(),
-    )
-    .unwrap();
+        Ed25519Key::from_seed(&base64_decode(ED25519_PRIVATE_KEY.as_bytes()).unwrap()).unwrap();
 
     let signature_ed = DkimSigner::from_key(pk_ed)
         .domain("example.com")
@@ -8,8 +8,8 @@
  * except according to those terms.
  */
 
-use mail_auth::{AuthenticatedMessage, DmarcResult, Resolver};
 use mail_auth::dkim::verify::DkimVerifier;
+use mail_auth::{AuthenticatedMessage, DmarcResult, Resolver};
 
 const TEST_MESSAGE: &str = r#"DKIM-Signature: v=1; a=ed25519-sha256; c=relaxed/relaxed;
 d=football.example.com; i=@football.example.com;
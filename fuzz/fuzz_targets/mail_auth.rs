@@ -13,6 +13,7 @@ use libfuzzer_sys::fuzz_target;
 
 use mail_auth::{
     arc,
+    common::crypto::{Ed25519Key, RsaKey, Sha256},
     common::parse::TxtRecordParser,
     common::verify::DomainKey,
     dkim::{self, Atps, DomainKeyReport},
@@ -68,6 +69,9 @@ fuzz_target!(|data: &[u8]| {
 
     Feedback::parse_arf(data);
     Feedback::parse_arf(&data_rfc822);
+
+    RsaKey::<Sha256>::from_private_key(data).ok();
+    Ed25519Key::from_private_key(data).ok();
 });
 
 fn into_alphabet(data: &[u8], alphabet: &[u8]) -> Vec<u8> {
@@ -8,8 +8,13 @@
  * except according to those terms.
  */
 
+use alloc::string::String;
+
 use crate::{
-    common::crypto::{Sha256, SigningKey},
+    common::{
+        clock::Clock,
+        crypto::{AsyncSigningKey, Sha256, SigningKey},
+    },
     dkim::{Canonicalization, Done, NeedDomain, NeedHeaders, NeedSelector},
 };
 
@@ -28,11 +33,32 @@ impl<T: SigningKey<Hasher = Sha256>> ArcSealer<T> {
                 ..Default::default()
             },
             key,
+            clock: Clock::signing(),
         }
     }
 }
 
-impl<T: SigningKey<Hasher = Sha256>> ArcSealer<T, NeedDomain> {
+impl<T: AsyncSigningKey<Hasher = Sha256>> ArcSealer<T> {
+    /// Creates a sealer for a key that signs asynchronously, such as a key
+    /// held by an HSM. Messages are sealed with [`ArcSealer::seal_async`].
+    pub fn from_async_key(key: T) -> ArcSealer<T, NeedDomain> {
+        ArcSealer {
+            _state: Default::default(),
+            signature: Signature {
+                a: key.algorithm(),
+                ..Default::default()
+            },
+            seal: Seal {
+                a: key.algorithm(),
+                ..Default::default()
+            },
+            key,
+            clock: Clock::signing(),
+        }
+    }
+}
+
+impl<T> ArcSealer<T, NeedDomain> {
     /// Sets the domain to use for signing.
     pub fn domain(mut self, domain: impl Into<String> + Clone) -> ArcSealer<T, NeedSelector> {
         self.signature.d = domain.clone().into();
@@ -42,11 +68,12 @@ impl<T: SigningKey<Hasher = Sha256>> ArcSealer<T, NeedDomain> {
             key: self.key,
             signature: self.signature,
             seal: self.seal,
+            clock: self.clock,
         }
     }
 }
 
-impl<T: SigningKey<Hasher = Sha256>> ArcSealer<T, NeedSelector> {
+impl<T> ArcSealer<T, NeedSelector> {
     /// Sets the selector to use for signing.
     pub fn selector(mut self, selector: impl Into<String> + Clone) -> ArcSealer<T, NeedHeaders> {
         self.signature.s = selector.clone().into();
@@ -56,11 +83,12 @@ impl<T: SigningKey<Hasher = Sha256>> ArcSealer<T, NeedSelector> {
             key: self.key,
             signature: self.signature,
             seal: self.seal,
+            clock: self.clock,
         }
     }
 }
 
-impl<T: SigningKey<Hasher = Sha256>> ArcSealer<T, NeedHeaders> {
+impl<T> ArcSealer<T, NeedHeaders> {
     /// Sets the headers to sign.
     pub fn headers(
         mut self,
@@ -72,11 +100,19 @@ impl<T: SigningKey<Hasher = Sha256>> ArcSealer<T, NeedHeaders> {
             key: self.key,
             signature: self.signature,
             seal: self.seal,
+            clock: self.clock,
         }
     }
 }
 
-impl<T: SigningKey<Hasher = Sha256>> ArcSealer<T, Done> {
+impl<T> ArcSealer<T, Done> {
+    /// Sets the clock used to obtain the signature timestamp. Without the
+    /// `std` feature it has to be set, otherwise seals carry `t=0`.
+    pub fn clock(mut self, clock: impl Into<Clock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
     /// Sets the number of seconds from now to use for the signature expiration.
     pub fn expiration(mut self, expiration: u64) -> Self {
         self.signature.x = expiration;
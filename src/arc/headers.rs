@@ -168,7 +168,7 @@ impl<'x> AuthenticationResults<'x> {
             for &ch in self.auth_results.as_bytes() {
                 if !ch.is_ascii_whitespace() {
                     if last_is_space {
-                        writer.write(&[b' ']);
+                        writer.write(b" ");
                         last_is_space = false;
                     }
                     writer.write(&[ch]);
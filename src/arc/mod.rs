@@ -9,36 +9,49 @@
  */
 
 pub mod builder;
+#[cfg(feature = "std")]
 pub mod headers;
 pub mod parse;
+#[cfg(feature = "std")]
 pub mod seal;
+#[cfg(feature = "std")]
 pub mod verify;
 
+use alloc::{string::String, vec::Vec};
+
+#[cfg(feature = "std")]
+use crate::AuthenticationResults;
 use crate::{
     common::{
-        crypto::{Algorithm, Sha256, SigningKey},
-        headers::Header,
+        clock::Clock,
+        crypto::Algorithm,
+        headers::{Header, OwnedHeader},
         verify::VerifySignature,
     },
     dkim::{Canonicalization, NeedDomain},
-    ArcOutput, AuthenticationResults, DkimResult,
+    ArcOutput, DkimResult, OwnedArcOutput,
 };
 
-#[derive(Debug, PartialEq, Eq, Clone, Default)]
-pub struct ArcSealer<T: SigningKey<Hasher = Sha256>, State = NeedDomain> {
-    _state: std::marker::PhantomData<State>,
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "std", derive(Default))]
+pub struct ArcSealer<T, State = NeedDomain> {
+    _state: core::marker::PhantomData<State>,
     pub(crate) key: T,
     pub(crate) signature: Signature,
     pub(crate) seal: Seal,
+    pub(crate) clock: Clock,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Signature {
     pub(crate) i: u32,
     pub(crate) a: Algorithm,
     pub(crate) d: String,
     pub(crate) s: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::common::serialize::base64"))]
     pub(crate) b: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::common::serialize::base64"))]
     pub(crate) bh: Vec<u8>,
     pub(crate) h: Vec<String>,
     pub(crate) z: Vec<String>,
@@ -50,9 +63,11 @@ pub struct Signature {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Seal {
     pub(crate) i: u32,
     pub(crate) a: Algorithm,
+    #[cfg_attr(feature = "serde", serde(with = "crate::common::serialize::base64"))]
     pub(crate) b: Vec<u8>,
     pub(crate) d: String,
     pub(crate) s: String,
@@ -61,10 +76,12 @@ pub struct Seal {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Results {
     pub(crate) i: u32,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ArcSet<'x> {
     pub(crate) signature: Signature,
@@ -73,13 +90,24 @@ pub struct ArcSet<'x> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Set<'x> {
     pub(crate) signature: Header<'x, &'x Signature>,
     pub(crate) seal: Header<'x, &'x Seal>,
     pub(crate) results: Header<'x, &'x Results>,
 }
 
+/// Owned counterpart of [`Set`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedSet {
+    pub(crate) signature: OwnedHeader<Signature>,
+    pub(crate) seal: OwnedHeader<Seal>,
+    pub(crate) results: OwnedHeader<Results>,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub(crate) enum ChainValidation {
     #[default]
     None,
@@ -124,6 +152,7 @@ impl VerifySignature for Seal {
 }
 
 impl<'x> ArcOutput<'x> {
+    #[cfg(feature = "std")]
     pub(crate) fn with_result(mut self, result: DkimResult) -> Self {
         self.result = result;
         self
@@ -132,6 +161,58 @@ impl<'x> ArcOutput<'x> {
     pub fn can_be_sealed(&self) -> bool {
         self.set.is_empty() || self.set.last().unwrap().seal.header.cv != ChainValidation::Fail
     }
+
+    /// Copies the ARC sets into an [`OwnedArcOutput`] that no longer borrows
+    /// from the message.
+    pub fn into_owned(self) -> OwnedArcOutput {
+        OwnedArcOutput {
+            result: self.result,
+            set: self.set.iter().map(Set::to_owned_set).collect(),
+        }
+    }
+}
+
+impl<'x> Set<'x> {
+    pub fn to_owned_set(&self) -> OwnedSet {
+        OwnedSet {
+            signature: self.signature.to_owned_header(),
+            seal: self.seal.to_owned_header(),
+            results: self.results.to_owned_header(),
+        }
+    }
+}
+
+impl OwnedSet {
+    pub fn as_set(&self) -> Set<'_> {
+        Set {
+            signature: self.signature.as_header(),
+            seal: self.seal.as_header(),
+            results: self.results.as_header(),
+        }
+    }
+}
+
+impl OwnedArcOutput {
+    pub fn result(&self) -> &DkimResult {
+        &self.result
+    }
+
+    pub fn sets(&self) -> &[OwnedSet] {
+        &self.set
+    }
+
+    pub fn can_be_sealed(&self) -> bool {
+        self.set.is_empty() || self.set.last().unwrap().seal.header.cv != ChainValidation::Fail
+    }
+
+    /// Returns a borrowed [`ArcOutput`], as accepted by the sealing and
+    /// reporting functions.
+    pub fn as_output(&self) -> ArcOutput<'_> {
+        ArcOutput {
+            result: self.result.clone(),
+            set: self.set.iter().map(OwnedSet::as_set).collect(),
+        }
+    }
 }
 
 impl<'x> Default for ArcOutput<'x> {
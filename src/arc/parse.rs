@@ -8,10 +8,10 @@
  * except according to those terms.
  */
 
-use mail_parser::decoders::base64::base64_decode_stream;
+use alloc::vec::Vec;
 
 use crate::{
-    common::{crypto::Algorithm, parse::TagParser},
+    common::{base64::base64_decode_stream, crypto::Algorithm, parse::TagParser},
     dkim::{parse::SignatureParser, Canonicalization},
     Error,
 };
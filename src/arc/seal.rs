@@ -8,117 +8,179 @@
  * except according to those terms.
  */
 
-use std::time::SystemTime;
-
-use mail_builder::encoders::base64::base64_encode;
+use crate::common::base64::base64_encode;
 
 use crate::{
     common::{
-        crypto::{HashAlgorithm, Sha256, SigningKey},
+        crypto::{AsyncSigningKey, HashAlgorithm, HashContext, HashImpl, Sha256, SigningKey},
         headers::{Writable, Writer},
     },
     dkim::{canonicalize::CanonicalHeaders, Canonicalization, Done},
     ArcOutput, AuthenticatedMessage, AuthenticationResults, DkimResult, Error,
 };
 
-use super::{ArcSealer, ArcSet, ChainValidation, Signature};
+use super::{ArcSealer, ArcSet, ChainValidation, Seal, Signature};
 
-impl<T: SigningKey<Hasher=Sha256>> ArcSealer<T, Done> {
+impl<T: SigningKey<Hasher = Sha256>> ArcSealer<T, Done> {
     pub fn seal<'x>(
         &self,
         message: &'x AuthenticatedMessage<'x>,
         results: &'x AuthenticationResults,
         arc_output: &ArcOutput,
     ) -> crate::Result<ArcSet<'x>> {
-        if !arc_output.can_be_sealed() {
-            return Err(Error::ArcInvalidCV);
-        }
-
-        // Create set
-        let mut set = ArcSet {
-            signature: self.signature.clone(),
-            seal: self.seal.clone(),
+        let (mut set, canonical_headers) = prepare_set(
+            &self.signature,
+            &self.seal,
+            message,
             results,
-        };
-
-        // Set i= and cv=
-        if arc_output.set.is_empty() {
-            set.signature.i = 1;
-            set.seal.i = 1;
-            set.seal.cv = ChainValidation::None;
-        } else {
-            let i = arc_output.set.last().unwrap().seal.header.i + 1;
-            set.signature.i = i;
-            set.seal.i = i;
-            set.seal.cv = match &arc_output.result {
-                DkimResult::Pass => ChainValidation::Pass,
-                _ => ChainValidation::Fail,
-            };
-        }
-
-        // Canonicalize headers
-        let (canonical_headers, signed_headers) = set.signature.canonicalize_headers(message)?;
-        if signed_headers.is_empty() {
-            return Err(Error::NoHeadersFound);
-        }
-
-        // Canonicalize body
-        if set.signature.l > 0 {
-            set.signature.l = (message.raw_message.len() - message.body_offset) as u64;
-        }
-        let ha = HashAlgorithm::from(set.signature.a);
-        if let Some((_, _, _, bh)) = message
-            .body_hashes
-            .iter()
-            .find(|(c, h, l, _)| c == &set.signature.cb && h == &ha && l == &set.signature.l)
-        {
-            // Use cached hash
-            set.signature.bh = base64_encode(bh)?;
-        } else {
-            let hash = self.key.hash(
-                set.signature.cb.canonical_body(
-                    message
-                        .raw_message
-                        .get(message.body_offset..)
-                        .unwrap_or_default(),
-                    u64::MAX,
-                ),
-            );
-            set.signature.bh = base64_encode(hash.as_ref())?;
-        }
-
-        // Create Signature
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
-
-        set.signature.t = now;
-        set.signature.x = if set.signature.x > 0 {
-            now + set.signature.x
-        } else {
-            0
-        };
-        set.signature.h = signed_headers;
+            arc_output,
+            self.clock.now(),
+        )?;
 
         // Sign
         let b = self.key.sign(SignableSet {
             set: &set,
             headers: canonical_headers,
         })?;
-        set.signature.b = base64_encode(&b)?;
+        set.signature.b = base64_encode(&b);
 
         // Seal
         let b = self.key.sign(SignableChain {
             arc_output,
             set: &set,
         })?;
-        set.seal.b = base64_encode(&b)?;
+        set.seal.b = base64_encode(&b);
 
         Ok(set)
     }
 }
 
+impl<T: AsyncSigningKey<Hasher = Sha256>> ArcSealer<T, Done> {
+    /// Seals a message using a key that signs asynchronously.
+    pub async fn seal_async<'x>(
+        &self,
+        message: &'x AuthenticatedMessage<'x>,
+        results: &'x AuthenticationResults<'x>,
+        arc_output: &ArcOutput<'_>,
+    ) -> crate::Result<ArcSet<'x>> {
+        let (mut set, canonical_headers) = prepare_set(
+            &self.signature,
+            &self.seal,
+            message,
+            results,
+            arc_output,
+            self.clock.now(),
+        )?;
+
+        // Sign
+        let mut data = Vec::with_capacity(256);
+        SignableSet {
+            set: &set,
+            headers: canonical_headers,
+        }
+        .write(&mut data);
+        let b = self.key.sign(data).await?;
+        set.signature.b = base64_encode(&b);
+
+        // Seal
+        let mut data = Vec::with_capacity(256);
+        SignableChain {
+            arc_output,
+            set: &set,
+        }
+        .write(&mut data);
+        let b = self.key.sign(data).await?;
+        set.seal.b = base64_encode(&b);
+
+        Ok(set)
+    }
+}
+
+/// Builds the next ARC set of a message, with all tags filled in except the
+/// `b=` tags of the signature and the seal.
+fn prepare_set<'x>(
+    signature: &Signature,
+    seal: &Seal,
+    message: &'x AuthenticatedMessage<'x>,
+    results: &'x AuthenticationResults,
+    arc_output: &ArcOutput,
+    now: u64,
+) -> crate::Result<(ArcSet<'x>, CanonicalHeaders<'x>)> {
+    if !arc_output.can_be_sealed() {
+        return Err(Error::ArcInvalidCV);
+    }
+
+    // Create set
+    let mut set = ArcSet {
+        signature: signature.clone(),
+        seal: seal.clone(),
+        results,
+    };
+
+    // Set i= and cv=
+    if arc_output.set.is_empty() {
+        set.signature.i = 1;
+        set.seal.i = 1;
+        set.seal.cv = ChainValidation::None;
+    } else {
+        let i = arc_output.set.last().unwrap().seal.header.i + 1;
+        set.signature.i = i;
+        set.seal.i = i;
+        set.seal.cv = match &arc_output.result {
+            DkimResult::Pass => ChainValidation::Pass,
+            _ => ChainValidation::Fail,
+        };
+    }
+
+    // Canonicalize headers
+    let (canonical_headers, signed_headers) = set.signature.canonicalize_headers(message)?;
+    if signed_headers.is_empty() {
+        return Err(Error::NoHeadersFound);
+    }
+
+    // Canonicalize body
+    if set.signature.l > 0 {
+        set.signature.l = message.body_len as u64;
+    }
+    let ha = HashAlgorithm::from(set.signature.a);
+    if let Some((_, _, _, bh)) = message
+        .body_hashes
+        .iter()
+        .find(|(c, h, l, _)| c == &set.signature.cb && h == &ha && *l == 0)
+    {
+        // Use cached whole-body hash
+        set.signature.bh = base64_encode(bh);
+    } else if message.raw_body().len() != message.body_len {
+        // The body was streamed through `AuthenticatedMessageBuilder` without
+        // requesting this hash
+        return Err(Error::MissingParameters);
+    } else {
+        let mut hasher = Sha256::hasher();
+        set.signature
+            .cb
+            .canonical_body(
+                message
+                    .raw_message
+                    .get(message.body_offset..)
+                    .unwrap_or_default(),
+                u64::MAX,
+            )
+            .write(&mut hasher);
+        set.signature.bh = base64_encode(hasher.complete().as_ref());
+    }
+
+    // Create Signature
+    set.signature.t = now;
+    set.signature.x = if set.signature.x > 0 {
+        now + set.signature.x
+    } else {
+        0
+    };
+    set.signature.h = signed_headers;
+
+    Ok((set, canonical_headers))
+}
+
 struct SignableSet<'a> {
     set: &'a ArcSet<'a>,
     headers: CanonicalHeaders<'a>,
@@ -200,31 +262,31 @@ mod test {
 
     use mail_parser::decoders::base64::base64_decode;
 
+    use crate::dkim::verify::DkimVerifier;
     use crate::{
         arc::ArcSealer,
         common::{
-            crypto::{Ed25519Key, RsaKey, Sha256, SigningKey},
+            crypto::{Ed25519Key, HashAlgorithm, RsaKey, Sha256, SigningKey},
             headers::HeaderWriter,
             parse::TxtRecordParser,
             verify::DomainKey,
         },
-        dkim::DkimSigner,
-        AuthenticatedMessage, AuthenticationResults, DkimResult, Resolver,
+        dkim::{sign::test::RemoteKey, Canonicalization, DkimSigner},
+        AuthenticatedMessage, AuthenticationResults, DkimResult, Error, Resolver,
     };
-    use crate::dkim::verify::DkimVerifier;
 
     const RSA_PRIVATE_KEY: &str = include_str!("../../resources/rsa-private.pem");
 
     const RSA_PUBLIC_KEY: &str = concat!(
-    "v=DKIM1; t=s; p=MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ",
-    "8AMIIBCgKCAQEAv9XYXG3uK95115mB4nJ37nGeNe2CrARm",
-    "1agrbcnSk5oIaEfMZLUR/X8gPzoiNHZcfMZEVR6bAytxUh",
-    "c5EvZIZrjSuEEeny+fFd/cTvcm3cOUUbIaUmSACj0dL2/K",
-    "wW0LyUaza9z9zor7I5XdIl1M53qVd5GI62XBB76FH+Q0bW",
-    "PZNkT4NclzTLspD/MTpNCCPhySM4Kdg5CuDczTH4aNzyS0",
-    "TqgXdtw6A4Sdsp97VXT9fkPW9rso3lrkpsl/9EQ1mR/DWK",
-    "6PBmRfIuSFuqnLKY6v/z2hXHxF7IoojfZLa2kZr9Aed4l9",
-    "WheQOTA19k5r2BmlRw/W9CrgCBo0Sdj+KQIDAQAB",
+        "v=DKIM1; t=s; p=MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ",
+        "8AMIIBCgKCAQEAv9XYXG3uK95115mB4nJ37nGeNe2CrARm",
+        "1agrbcnSk5oIaEfMZLUR/X8gPzoiNHZcfMZEVR6bAytxUh",
+        "c5EvZIZrjSuEEeny+fFd/cTvcm3cOUUbIaUmSACj0dL2/K",
+        "wW0LyUaza9z9zor7I5XdIl1M53qVd5GI62XBB76FH+Q0bW",
+        "PZNkT4NclzTLspD/MTpNCCPhySM4Kdg5CuDczTH4aNzyS0",
+        "TqgXdtw6A4Sdsp97VXT9fkPW9rso3lrkpsl/9EQ1mR/DWK",
+        "6PBmRfIuSFuqnLKY6v/z2hXHxF7IoojfZLa2kZr9Aed4l9",
+        "WheQOTA19k5r2BmlRw/W9CrgCBo0Sdj+KQIDAQAB",
     );
 
     const ED25519_PRIVATE_KEY: &str = "nWGxne/9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A=";
@@ -238,16 +300,16 @@ mod test {
     #[tokio::test]
     async fn arc_seal() {
         let message = concat!(
-        "From: queso@manchego.org\r\n",
-        "To: affumicata@scamorza.org\r\n",
-        "Subject: Say cheese\r\n",
-        "\r\n",
-        "We need to settle which one of us ",
-        "is tastier.\r\n"
+            "From: queso@manchego.org\r\n",
+            "To: affumicata@scamorza.org\r\n",
+            "Subject: Say cheese\r\n",
+            "\r\n",
+            "We need to settle which one of us ",
+            "is tastier.\r\n"
         );
 
         // Crate resolver
-        let resolver = Resolver::new_system_conf().unwrap();
+        let resolver = Resolver::new_mock();
         #[cfg(any(test, feature = "test"))]
         {
             resolver.txt_add(
@@ -294,11 +356,11 @@ mod test {
                 "scamorza.org",
                 "ed",
                 #[cfg(feature = "rust-crypto")]
-                Ed25519Key::from_bytes(&pk_ed_private).unwrap(),
+                Ed25519Key::from_seed(&pk_ed_private).unwrap(),
                 #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
                 Ed25519Key::from_seed_and_public_key(&pk_ed_private, &pk_ed_public).unwrap(),
             )
-                .await;
+            .await;
             raw_message =
                 arc_verify_and_seal(&resolver, &raw_message, "manchego.org", "rsa", pk_rsa).await;
         }
@@ -306,15 +368,149 @@ mod test {
         //println!("{}", raw_message);
     }
 
+    #[cfg(any(
+        feature = "rust-crypto",
+        all(feature = "ring", feature = "rustls-pemfile")
+    ))]
+    #[tokio::test]
+    async fn arc_seal_async() {
+        let raw_message = concat!(
+            "From: queso@manchego.org\r\n",
+            "To: affumicata@scamorza.org\r\n",
+            "Subject: Say cheese\r\n",
+            "\r\n",
+            "We need to settle which one of us ",
+            "is tastier.\r\n"
+        );
+        let resolver = Resolver::new_mock();
+        resolver.txt_add(
+            "ed._domainkey.scamorza.org.".to_string(),
+            DomainKey::parse(ED25519_PUBLIC_KEY.as_bytes()).unwrap(),
+            Instant::now() + Duration::new(3600, 0),
+        );
+        let pk_ed_public =
+            base64_decode(ED25519_PUBLIC_KEY.rsplit_once("p=").unwrap().1.as_bytes()).unwrap();
+        let pk_ed_private = base64_decode(ED25519_PRIVATE_KEY.as_bytes()).unwrap();
+        let pk_ed = || {
+            #[cfg(feature = "rust-crypto")]
+            let pk = Ed25519Key::from_seed(&pk_ed_private).unwrap();
+            #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
+            let pk = Ed25519Key::from_seed_and_public_key(&pk_ed_private, &pk_ed_public).unwrap();
+            pk
+        };
+
+        let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
+        let arc_result = resolver.verify_arc(&message).await;
+        let auth_results = AuthenticationResults::new("scamorza.org");
+        let expected = ArcSealer::from_key(pk_ed())
+            .domain("scamorza.org")
+            .selector("ed")
+            .headers(["From", "To", "Subject"])
+            .clock(311923920)
+            .seal(&message, &auth_results, &arc_result)
+            .unwrap()
+            .to_header();
+        let sealed = ArcSealer::from_async_key(RemoteKey(pk_ed()))
+            .domain("scamorza.org")
+            .selector("ed")
+            .headers(["From", "To", "Subject"])
+            .clock(311923920)
+            .seal_async(&message, &auth_results, &arc_result)
+            .await
+            .unwrap()
+            .to_header();
+        assert_eq!(sealed, expected);
+
+        let raw_message = format!("{sealed}{raw_message}");
+        let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
+        assert_eq!(
+            resolver.verify_arc(&message).await.result(),
+            &DkimResult::Pass
+        );
+    }
+
+    #[tokio::test]
+    async fn arc_seal_builder() {
+        let headers = concat!(
+            "From: queso@manchego.org\r\n",
+            "To: affumicata@scamorza.org\r\n",
+            "Subject: Say cheese\r\n",
+            "\r\n",
+        );
+        let body = concat!(
+            "We need to settle  which one\t\r\n",
+            "of us is tastier.\r\n\r\n"
+        );
+        let resolver = Resolver::new_mock();
+        resolver.txt_add(
+            "ed._domainkey.scamorza.org.".to_string(),
+            DomainKey::parse(ED25519_PUBLIC_KEY.as_bytes()).unwrap(),
+            Instant::now() + Duration::new(3600, 0),
+        );
+
+        let auth_results = AuthenticationResults::new("scamorza.org");
+        let sealer = |cb, body_length| {
+            ArcSealer::from_key(
+                Ed25519Key::from_seed(&base64_decode(ED25519_PRIVATE_KEY.as_bytes()).unwrap())
+                    .unwrap(),
+            )
+            .domain("scamorza.org")
+            .selector("ed")
+            .headers(["From", "To", "Subject"])
+            .body_canonicalization(cb)
+            .body_length(body_length)
+        };
+
+        for cb in [Canonicalization::Relaxed, Canonicalization::Simple] {
+            // Whole-body hashes are only computed when requested
+            let mut builder = AuthenticatedMessage::builder(headers.as_bytes()).unwrap();
+            builder.write_body(body.as_bytes());
+            let message = builder.build();
+            let arc_result = resolver.verify_arc(&message).await;
+            assert_eq!(
+                sealer(cb, false).seal(&message, &auth_results, &arc_result),
+                Err(Error::MissingParameters)
+            );
+
+            let mut builder = AuthenticatedMessage::builder(headers.as_bytes())
+                .unwrap()
+                .with_body_hash(cb, HashAlgorithm::Sha256);
+            for chunk in body.as_bytes().chunks(5) {
+                builder.write_body(chunk);
+            }
+            let message = builder.build();
+            let arc_result = resolver.verify_arc(&message).await;
+
+            for body_length in [false, true] {
+                let sealed = sealer(cb, body_length)
+                    .seal(&message, &auth_results, &arc_result)
+                    .unwrap();
+                assert_eq!(
+                    sealed.signature.l,
+                    if body_length { body.len() as u64 } else { 0 }
+                );
+
+                let raw_message = format!("{}{headers}{body}", sealed.to_header());
+                let message =
+                    AuthenticatedMessage::parse_with_opts(raw_message.as_bytes(), false).unwrap();
+                assert_eq!(
+                    resolver.verify_arc(&message).await.result(),
+                    &DkimResult::Pass,
+                    "{cb:?} {body_length}"
+                );
+            }
+        }
+    }
+
     async fn arc_verify_and_seal(
         resolver: &Resolver,
         raw_message: &str,
         d: &str,
         s: &str,
-        pk: impl SigningKey<Hasher=Sha256>,
+        pk: impl SigningKey<Hasher = Sha256>,
     ) -> String {
         let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
-        let dkim_result = DkimVerifier::verify_dkim(&resolver, &message).await;
+        let dkim_result = DkimVerifier::verify_dkim(resolver, &message).await;
         let arc_result = resolver.verify_arc(&message).await;
         assert!(
             matches!(arc_result.result(), DkimResult::Pass | DkimResult::None),
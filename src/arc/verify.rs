@@ -8,8 +8,6 @@
  * except according to those terms.
  */
 
-use std::time::SystemTime;
-
 use crate::{
     common::{
         crypto::HashAlgorithm,
@@ -36,10 +34,7 @@ impl Resolver {
             return ArcOutput::default().with_result(DkimResult::Fail(Error::ArcBrokenChain));
         }
 
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0);
+        let now = self.clock.now();
 
         let mut output = ArcOutput {
             result: DkimResult::None,
@@ -127,6 +122,11 @@ impl Resolver {
             }
         };
 
+        // Reject algorithms and keys not allowed by the policy
+        if let Err(err) = self.dkim_policy.check(signature.a, &record) {
+            return output.with_result(DkimResult::Policy(err));
+        }
+
         // Verify signature
         if let Err(err) = record.verify(&mut headers, *signature, signature.ch) {
             return output.with_result(DkimResult::Fail(err));
@@ -144,6 +144,10 @@ impl Resolver {
                 }
             };
 
+            if let Err(err) = self.dkim_policy.check(seal.a, &record) {
+                return output.with_result(DkimResult::Policy(err));
+            }
+
             // Build Seal headers
             let seal_signature = header.value.strip_signature();
             let mut headers = output
@@ -183,11 +187,11 @@ mod test {
         time::{Duration, Instant},
     };
 
+    use crate::dkim::{policy::DkimPolicy, verify::DkimVerifier};
     use crate::{
         common::{parse::TxtRecordParser, verify::DomainKey},
-        AuthenticatedMessage, DkimResult, Resolver,
+        AuthenticatedMessage, DkimResult, Error, PolicyViolation, Resolver,
     };
-    use crate::dkim::verify::DkimVerifier;
 
     #[tokio::test]
     async fn arc_verify() {
@@ -210,14 +214,36 @@ mod test {
 
             let arc = resolver.verify_arc(&message).await;
             assert_eq!(arc.result(), &DkimResult::Pass);
+            let owned = arc.clone().into_owned();
+            assert_eq!(owned.as_output(), arc);
+            assert_eq!(owned.can_be_sealed(), arc.can_be_sealed());
+            #[cfg(feature = "serde")]
+            {
+                let json = serde_json::to_string(&arc).unwrap();
+                let deserialized: crate::OwnedArcOutput = serde_json::from_str(&json).unwrap();
+                assert_eq!(deserialized, owned);
+            }
 
             let dkim = DkimVerifier::verify_dkim(&resolver, &message).await;
             assert!(dkim.iter().any(|o| o.result() == &DkimResult::Pass));
+
+            let arc = new_resolver(dns_records)
+                .with_dkim_policy(DkimPolicy::rfc8301().min_rsa_bits(4096))
+                .verify_arc(&message)
+                .await;
+            assert!(
+                matches!(
+                    arc.result(),
+                    DkimResult::Policy(Error::PolicyViolation(PolicyViolation::RsaKeyLength(_)))
+                ),
+                "{:?}",
+                arc.result()
+            );
         }
     }
 
     fn new_resolver(dns_records: &str) -> Resolver {
-        let resolver = Resolver::new_system_conf().unwrap();
+        let resolver = Resolver::new_mock();
         for (key, value) in dns_records
             .split('\n')
             .filter_map(|r| r.split_once(' ').map(|(a, b)| (a, b.as_bytes())))
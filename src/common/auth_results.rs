@@ -14,11 +14,13 @@ use std::{
     net::IpAddr,
 };
 
-use mail_builder::encoders::base64::base64_encode;
+use crate::common::base64::base64_encode;
 
 use crate::{
-    ArcOutput, AuthenticationResults, DkimOutput, DkimResult, DmarcOutput, DmarcResult, Error,
-    IprevOutput, IprevResult, ReceivedSpf, SpfOutput, SpfResult,
+    ArcOutput, AuthMethod, AuthProperty, AuthResultCode, AuthenticatedMessage,
+    AuthenticationResults, AuthenticationResultsHeader, DkimOutput, DkimResult, DmarcOutput,
+    DmarcResult, DnssecStatus, Error, IprevOutput, IprevResult, MethodResult, PolicyViolation,
+    ReceivedSpf, SpfOutput, SpfResult,
 };
 
 use super::headers::{HeaderWriter, Writer};
@@ -63,8 +65,7 @@ impl<'x> AuthenticationResults<'x> {
             if signature.b.len() >= 6 {
                 self.auth_results.push_str(" header.b=");
                 self.auth_results.push_str(
-                    &String::from_utf8(base64_encode(&signature.b[..6]).unwrap_or_default())
-                        .unwrap_or_default(),
+                    &String::from_utf8(base64_encode(&signature.b[..6])).unwrap_or_default(),
                 );
             }
         }
@@ -72,6 +73,7 @@ impl<'x> AuthenticationResults<'x> {
         if dkim.is_atps {
             write!(self.auth_results, " header.from={header_from}").ok();
         }
+        self.set_dnssec(dkim.dnssec);
     }
 
     pub fn with_spf_ehlo_result(
@@ -88,6 +90,7 @@ impl<'x> AuthenticationResults<'x> {
             ip_addr,
         );
         write!(self.auth_results, " smtp.helo={ehlo_domain}").ok();
+        self.set_dnssec(spf.dnssec);
         self
     }
 
@@ -111,6 +114,7 @@ impl<'x> AuthenticationResults<'x> {
             ip_addr,
         );
         write!(self.auth_results, " smtp.mailfrom={addr}").ok();
+        self.set_dnssec(spf.dnssec);
         self
     }
 
@@ -138,6 +142,7 @@ impl<'x> AuthenticationResults<'x> {
             dmarc.domain, dmarc.policy
         )
         .ok();
+        self.set_dnssec(dmarc.dnssec);
         self
     }
 
@@ -147,6 +152,14 @@ impl<'x> AuthenticationResults<'x> {
         write!(self.auth_results, " policy.iprev={remote_ip}").ok();
         self
     }
+
+    // There is no registered ptype.property for DNSSEC (RFC 8601), so the
+    // status is reported as a comment.
+    fn set_dnssec(&mut self, dnssec: Option<DnssecStatus>) {
+        if let Some(dnssec) = dnssec {
+            write!(self.auth_results, " (dnssec={dnssec})").ok();
+        }
+    }
 }
 
 impl<'x> Display for AuthenticationResults<'x> {
@@ -299,6 +312,10 @@ impl AsAuthResult for DkimResult {
                 header.push_str("fail");
                 err.as_auth_result(header);
             }
+            DkimResult::Policy(err) => {
+                header.push_str("policy");
+                err.as_auth_result(header);
+            }
             DkimResult::PermError(err) => {
                 header.push_str("permerror");
                 err.as_auth_result(header);
@@ -345,17 +362,426 @@ impl AsAuthResult for Error {
             Error::NotAligned => "policy not aligned",
             Error::InvalidRecordType => "invalid dns record type",
             Error::SignatureLength => "signature length ignored due to security risk",
+            Error::PolicyViolation(violation) => match violation {
+                PolicyViolation::Algorithm(_) => "algorithm not allowed",
+                PolicyViolation::RsaKeyLength(_) => "key too short",
+                PolicyViolation::RsaKeyInvalid => "invalid key",
+                PolicyViolation::Ed25519Required => "ed25519 required",
+            },
         });
         header.push(')');
     }
 }
 
+impl AuthenticationResultsHeader {
+    /// Parses the value of an Authentication-Results header field (RFC 8601),
+    /// skipping CFWS, unknown properties and malformed result entries.
+    ///
+    /// The reason of a result is taken from its `reason=` value or, when
+    /// missing, from the first comment following the result keyword.
+    pub fn parse(header: &[u8]) -> crate::Result<Self> {
+        let mut cursor = Cursor {
+            bytes: header,
+            pos: 0,
+        };
+        cursor.skip_cfws(None);
+        let authserv_id = cursor.value().ok_or(Error::ParseError)?;
+        cursor.skip_cfws(None);
+        let version = cursor.number();
+        let mut results = Vec::new();
+
+        loop {
+            cursor.skip_cfws(None);
+            match cursor.next() {
+                Some(b';') => {
+                    if let Some(result) = cursor.method_result() {
+                        results.push(result);
+                    }
+                }
+                Some(_) => cursor.seek_semicolon(),
+                None => break,
+            }
+        }
+
+        Ok(AuthenticationResultsHeader {
+            authserv_id,
+            version,
+            results,
+        })
+    }
+
+    /// Returns the results reported for `method`.
+    pub fn results_for<'x>(
+        &'x self,
+        method: &'x AuthMethod,
+    ) -> impl Iterator<Item = &'x MethodResult> + 'x {
+        self.results.iter().filter(move |r| &r.method == method)
+    }
+}
+
+impl MethodResult {
+    /// Returns the value of a property, e.g. `property("header", "d")`.
+    pub fn property(&self, ptype: &str, property: &str) -> Option<&str> {
+        self.properties
+            .iter()
+            .find(|p| {
+                p.ptype.eq_ignore_ascii_case(ptype) && p.property.eq_ignore_ascii_case(property)
+            })
+            .map(|p| p.value.as_str())
+    }
+}
+
+impl AuthMethod {
+    pub fn as_str(&self) -> &str {
+        match self {
+            AuthMethod::Dkim => "dkim",
+            AuthMethod::DkimAtps => "dkim-atps",
+            AuthMethod::Spf => "spf",
+            AuthMethod::Dmarc => "dmarc",
+            AuthMethod::Arc => "arc",
+            AuthMethod::Iprev => "iprev",
+            AuthMethod::Other(method) => method,
+        }
+    }
+}
+
+impl From<String> for AuthMethod {
+    fn from(method: String) -> Self {
+        match method.as_str() {
+            "dkim" => AuthMethod::Dkim,
+            "dkim-atps" => AuthMethod::DkimAtps,
+            "spf" => AuthMethod::Spf,
+            "dmarc" => AuthMethod::Dmarc,
+            "arc" => AuthMethod::Arc,
+            "iprev" => AuthMethod::Iprev,
+            _ => AuthMethod::Other(method),
+        }
+    }
+}
+
+impl AuthResultCode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            AuthResultCode::None => "none",
+            AuthResultCode::Pass => "pass",
+            AuthResultCode::Fail => "fail",
+            AuthResultCode::SoftFail => "softfail",
+            AuthResultCode::Neutral => "neutral",
+            AuthResultCode::Policy => "policy",
+            AuthResultCode::TempError => "temperror",
+            AuthResultCode::PermError => "permerror",
+            AuthResultCode::Other(result) => result,
+        }
+    }
+}
+
+impl From<String> for AuthResultCode {
+    fn from(result: String) -> Self {
+        match result.as_str() {
+            "none" => AuthResultCode::None,
+            "pass" => AuthResultCode::Pass,
+            "fail" | "hardfail" => AuthResultCode::Fail,
+            "softfail" => AuthResultCode::SoftFail,
+            "neutral" => AuthResultCode::Neutral,
+            "policy" => AuthResultCode::Policy,
+            "temperror" => AuthResultCode::TempError,
+            "permerror" => AuthResultCode::PermError,
+            _ => AuthResultCode::Other(result),
+        }
+    }
+}
+
+impl Display for AuthMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Display for AuthResultCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl AuthenticatedMessage<'_> {
+    /// Returns the parsed Authentication-Results header fields whose
+    /// authserv-id is one of `authserv_ids`, in message order.
+    pub fn authentication_results(
+        &self,
+        authserv_ids: &[&str],
+    ) -> Vec<AuthenticationResultsHeader> {
+        self.headers
+            .iter()
+            .filter_map(|(name, value)| parse_claimed(name, value, authserv_ids))
+            .collect()
+    }
+
+    /// Returns the header fields of the message, without the empty line
+    /// that ends them, removing the Authentication-Results fields that claim
+    /// one of `authserv_ids`.
+    ///
+    /// Border MTAs must remove such fields from incoming messages before
+    /// adding their own, as they cannot have been added by a trusted host
+    /// (RFC 8601, section 5).
+    pub fn remove_authentication_results(&self, authserv_ids: &[&str]) -> Vec<u8> {
+        let mut headers = Vec::with_capacity(self.body_offset);
+        for (pos, (name, value)) in self.headers.iter().enumerate() {
+            if parse_claimed(name, value, authserv_ids).is_some() {
+                continue;
+            }
+
+            match self.header_offsets(pos) {
+                Some((name_range, value_range)) => {
+                    headers.extend_from_slice(&self.raw_message[name_range.start..value_range.end])
+                }
+                None => {
+                    headers.extend_from_slice(name);
+                    if !value.is_empty() {
+                        headers.push(b':');
+                        headers.extend_from_slice(value);
+                    }
+                }
+            }
+        }
+        headers
+    }
+}
+
+fn parse_claimed(
+    name: &[u8],
+    value: &[u8],
+    authserv_ids: &[&str],
+) -> Option<AuthenticationResultsHeader> {
+    if name.eq_ignore_ascii_case(b"Authentication-Results") {
+        AuthenticationResultsHeader::parse(value)
+            .ok()
+            .filter(|header| {
+                authserv_ids
+                    .iter()
+                    .any(|id| id.eq_ignore_ascii_case(&header.authserv_id))
+            })
+    } else {
+        None
+    }
+}
+
+struct Cursor<'x> {
+    bytes: &'x [u8],
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let ch = self.peek()?;
+        self.pos += 1;
+        Some(ch)
+    }
+
+    /// Skips folding whitespace and comments, storing the text of the first
+    /// comment found in `comment`.
+    fn skip_cfws(&mut self, mut comment: Option<&mut Option<String>>) {
+        while let Some(ch) = self.peek() {
+            match ch {
+                b' ' | b'\t' | b'\r' | b'\n' => self.pos += 1,
+                b'(' => {
+                    let text = self.comment();
+                    if let Some(comment) = comment.as_mut().filter(|c| c.is_none()) {
+                        **comment = Some(text);
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn comment(&mut self) -> String {
+        let mut text = Vec::new();
+        let mut depth = 0;
+        while let Some(ch) = self.next() {
+            match ch {
+                b'(' => {
+                    if depth > 0 {
+                        text.push(ch);
+                    }
+                    depth += 1;
+                }
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                    text.push(ch);
+                }
+                b'\\' => {
+                    if let Some(ch) = self.next() {
+                        text.push(ch);
+                    }
+                }
+                b'\r' | b'\n' => (),
+                _ => text.push(ch),
+            }
+        }
+        String::from_utf8_lossy(&text).trim().to_string()
+    }
+
+    /// Reads a token, quoted-string or `local-part@domain` value.
+    fn value(&mut self) -> Option<String> {
+        let mut text = Vec::new();
+        if self.peek() == Some(b'"') {
+            self.pos += 1;
+            while let Some(ch) = self.next() {
+                match ch {
+                    b'"' => break,
+                    b'\\' => {
+                        if let Some(ch) = self.next() {
+                            text.push(ch);
+                        }
+                    }
+                    b'\r' | b'\n' => (),
+                    _ => text.push(ch),
+                }
+            }
+        } else {
+            while let Some(ch) = self.peek() {
+                match ch {
+                    b' ' | b'\t' | b'\r' | b'\n' | b';' | b'(' | b')' | b'"' => break,
+                    _ => {
+                        text.push(ch);
+                        self.pos += 1;
+                    }
+                }
+            }
+            if text.is_empty() {
+                return None;
+            }
+        }
+        String::from_utf8(text).ok()
+    }
+
+    fn keyword(&mut self) -> String {
+        let mut keyword = String::new();
+        while let Some(ch) = self.peek() {
+            if ch.is_ascii_alphanumeric() || matches!(ch, b'-' | b'_' | b'.') {
+                keyword.push(char::from(ch.to_ascii_lowercase()));
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        keyword
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn seek_semicolon(&mut self) {
+        while let Some(ch) = self.peek() {
+            match ch {
+                b';' => break,
+                b'(' => {
+                    self.comment();
+                }
+                b'"' => {
+                    self.value();
+                }
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    fn method_result(&mut self) -> Option<MethodResult> {
+        // methodspec: method [ "/" version ] "=" result
+        self.skip_cfws(None);
+        let method = self.keyword();
+        self.skip_cfws(None);
+        let mut version = None;
+        if self.peek() == Some(b'/') {
+            self.pos += 1;
+            self.skip_cfws(None);
+            version = self.number();
+            self.skip_cfws(None);
+        }
+        if method.is_empty() || self.peek() != Some(b'=') {
+            // Either "none" or a malformed entry
+            self.seek_semicolon();
+            return None;
+        }
+        self.pos += 1;
+        self.skip_cfws(None);
+        let result = self.keyword();
+        if result.is_empty() {
+            self.seek_semicolon();
+            return None;
+        }
+
+        // Only a comment right after the result is taken as its reason
+        let mut comment = None;
+        self.skip_cfws(Some(&mut comment));
+
+        // reasonspec and propspecs
+        let mut reason = None;
+        let mut properties = Vec::new();
+        loop {
+            self.skip_cfws(None);
+            if matches!(self.peek(), None | Some(b';')) {
+                break;
+            }
+            let mut key = self.keyword();
+            self.skip_cfws(None);
+            if self.peek() == Some(b'.') {
+                self.pos += 1;
+                self.skip_cfws(None);
+                key.push('.');
+                key.push_str(&self.keyword());
+                self.skip_cfws(None);
+            }
+            if key.is_empty() || self.peek() != Some(b'=') {
+                self.seek_semicolon();
+                break;
+            }
+            self.pos += 1;
+            self.skip_cfws(None);
+            let value = self.value().unwrap_or_default();
+            if key == "reason" {
+                reason = Some(value);
+            } else if let Some((ptype, property)) = key.split_once('.') {
+                properties.push(AuthProperty {
+                    ptype: ptype.to_string(),
+                    property: property.to_string(),
+                    value,
+                });
+            }
+        }
+
+        Some(MethodResult {
+            method: method.into(),
+            version,
+            result: result.into(),
+            reason: reason.or(comment),
+            properties,
+        })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
-        dkim::Signature, dmarc::Policy, ArcOutput, AuthenticationResults, DkimOutput, DkimResult,
-        DmarcOutput, DmarcResult, Error, IprevOutput, IprevResult, ReceivedSpf, SpfOutput,
-        SpfResult,
+        dkim::Signature, dmarc::Policy, ArcOutput, AuthMethod, AuthProperty, AuthResultCode,
+        AuthenticatedMessage, AuthenticationResults, AuthenticationResultsHeader, DkimOutput,
+        DkimResult, DmarcOutput, DmarcResult, DnssecStatus, Error, IprevOutput, IprevResult,
+        MethodResult, ReceivedSpf, SpfOutput, SpfResult,
     };
 
     #[test]
@@ -364,7 +790,7 @@ mod test {
 
         for (expected_auth_results, dkim) in [
             (
-                "dkim=pass header.d=example.org header.s=myselector",
+                "dkim=pass header.d=example.org header.s=myselector (dnssec=secure)",
                 DkimOutput {
                     result: DkimResult::Pass,
                     signature: (&Signature {
@@ -375,6 +801,7 @@ mod test {
                         .into(),
                     report: None,
                     is_atps: false,
+                    dnssec: Some(DnssecStatus::Secure),
                 },
             ),
             (
@@ -393,6 +820,7 @@ mod test {
                         .into(),
                     report: None,
                     is_atps: false,
+                    dnssec: None,
                 },
             ),
             (
@@ -411,6 +839,7 @@ mod test {
                         .into(),
                     report: None,
                     is_atps: true,
+                    dnssec: None,
                 },
             ),
         ] {
@@ -487,6 +916,8 @@ mod test {
                     domain: "".to_string(),
                     report: None,
                     explanation: None,
+                    dnssec: None,
+                    limit: None,
                 },
                 ip_addr,
                 mail_from,
@@ -498,6 +929,8 @@ mod test {
                     domain: "".to_string(),
                     report: None,
                     explanation: None,
+                    dnssec: None,
+                    limit: None,
                 },
                 ip_addr,
                 helo,
@@ -518,18 +951,25 @@ mod test {
                     spf_result: DmarcResult::Pass,
                     dkim_result: DmarcResult::None,
                     domain: "example.org".to_string(),
+                    policy_domain: "example.org".to_string(),
                     policy: Policy::None,
                     record: None,
+                    dnssec: None,
                 },
             ),
             (
-                "dmarc=fail (policy not aligned) header.from=example.com policy.dmarc=quarantine",
+                concat!(
+                    "dmarc=fail (policy not aligned) header.from=example.com ",
+                    "policy.dmarc=quarantine (dnssec=insecure)"
+                ),
                 DmarcOutput {
                     dkim_result: DmarcResult::Fail(Error::NotAligned),
                     spf_result: DmarcResult::None,
                     domain: "example.com".to_string(),
+                    policy_domain: "example.com".to_string(),
                     policy: Policy::Quarantine,
                     record: None,
+                    dnssec: Some(DnssecStatus::Insecure),
                 },
             ),
         ] {
@@ -590,4 +1030,212 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn parse_authentication_results() {
+        let property = |ptype: &str, property: &str, value: &str| AuthProperty {
+            ptype: ptype.to_string(),
+            property: property.to_string(),
+            value: value.to_string(),
+        };
+
+        for (header, expected) in [
+            (
+                "example.org 1; none",
+                AuthenticationResultsHeader {
+                    authserv_id: "example.org".to_string(),
+                    version: Some(1),
+                    results: vec![],
+                },
+            ),
+            (
+                concat!(
+                    "example.com;\r\n  spf=pass smtp.mailfrom=example.net;\r\n",
+                    "  x-unknown=whatever (not a standard method) x.y=z"
+                ),
+                AuthenticationResultsHeader {
+                    authserv_id: "example.com".to_string(),
+                    version: None,
+                    results: vec![
+                        MethodResult {
+                            method: AuthMethod::Spf,
+                            version: None,
+                            result: AuthResultCode::Pass,
+                            reason: None,
+                            properties: vec![property("smtp", "mailfrom", "example.net")],
+                        },
+                        MethodResult {
+                            method: AuthMethod::Other("x-unknown".to_string()),
+                            version: None,
+                            result: AuthResultCode::Other("whatever".to_string()),
+                            reason: Some("not a standard method".to_string()),
+                            properties: vec![property("x", "y", "z")],
+                        },
+                    ],
+                },
+            ),
+            (
+                concat!(
+                    "\"mx.example.com\"; dkim=fail reason=\"bad signature\" ",
+                    "header.d=newyork.example.com header.b=oINEO8hg;\r\n\t",
+                    "dmarc=pass (p=NONE sp=NONE dis=NONE) header.from=example.com;\r\n\t",
+                    "iprev=pass policy.iprev=192.0.2.200; garbage;",
+                ),
+                AuthenticationResultsHeader {
+                    authserv_id: "mx.example.com".to_string(),
+                    version: None,
+                    results: vec![
+                        MethodResult {
+                            method: AuthMethod::Dkim,
+                            version: None,
+                            result: AuthResultCode::Fail,
+                            reason: Some("bad signature".to_string()),
+                            properties: vec![
+                                property("header", "d", "newyork.example.com"),
+                                property("header", "b", "oINEO8hg"),
+                            ],
+                        },
+                        MethodResult {
+                            method: AuthMethod::Dmarc,
+                            version: None,
+                            result: AuthResultCode::Pass,
+                            reason: Some("p=NONE sp=NONE dis=NONE".to_string()),
+                            properties: vec![property("header", "from", "example.com")],
+                        },
+                        MethodResult {
+                            method: AuthMethod::Iprev,
+                            version: None,
+                            result: AuthResultCode::Pass,
+                            reason: None,
+                            properties: vec![property("policy", "iprev", "192.0.2.200")],
+                        },
+                    ],
+                },
+            ),
+            (
+                // CFWS example from RFC 8601, appendix B.7 (errata)
+                concat!(
+                    "foo.example.net (foobar) 1 (baz);\r\n",
+                    "    dkim (Because I like it) / 1 (One yay) = (wait for it) fail\r\n",
+                    "      policy (A dot can go here) . (like that) expired\r\n",
+                    "      (this surprised me) = (as I wasn't expecting it) 1362471462"
+                ),
+                AuthenticationResultsHeader {
+                    authserv_id: "foo.example.net".to_string(),
+                    version: Some(1),
+                    results: vec![MethodResult {
+                        method: AuthMethod::Dkim,
+                        version: Some(1),
+                        result: AuthResultCode::Fail,
+                        reason: None,
+                        properties: vec![property("policy", "expired", "1362471462")],
+                    }],
+                },
+            ),
+        ] {
+            assert_eq!(
+                AuthenticationResultsHeader::parse(header.as_bytes()).unwrap(),
+                expected,
+                "{header}"
+            );
+        }
+        assert!(AuthenticationResultsHeader::parse(b" (comment only) ").is_err());
+
+        // Headers written by AuthenticationResults are read back
+        let signature = Signature {
+            d: "example.org".into(),
+            s: "myselector".into(),
+            b: b"123456".to_vec(),
+            ..Default::default()
+        };
+        let auth_results = AuthenticationResults::new("mx.mydomain.org")
+            .with_dkim_result(
+                &DkimOutput {
+                    result: DkimResult::Fail(Error::FailedVerification),
+                    signature: (&signature).into(),
+                    report: None,
+                    is_atps: false,
+                    dnssec: None,
+                },
+                "jdoe@example.org",
+            )
+            .with_spf_mailfrom_result(
+                &SpfOutput {
+                    result: SpfResult::SoftFail,
+                    domain: "example.org".to_string(),
+                    report: None,
+                    explanation: None,
+                    dnssec: None,
+                    limit: None,
+                },
+                "192.168.1.1".parse().unwrap(),
+                "jdoe@example.org",
+                "example.org",
+            )
+            .to_string();
+        let parsed = AuthenticationResultsHeader::parse(auth_results.as_bytes()).unwrap();
+        assert_eq!(parsed.authserv_id, "mx.mydomain.org");
+        let dkim = parsed.results_for(&AuthMethod::Dkim).next().unwrap();
+        assert_eq!(dkim.result, AuthResultCode::Fail);
+        assert_eq!(dkim.reason.as_deref(), Some("verification failed"));
+        assert_eq!(dkim.property("header", "d"), Some("example.org"));
+        assert_eq!(dkim.property("header", "b"), Some("MTIzNDU2"));
+        let spf = parsed.results_for(&AuthMethod::Spf).next().unwrap();
+        assert_eq!(spf.result, AuthResultCode::SoftFail);
+        assert_eq!(spf.property("smtp", "mailfrom"), Some("jdoe@example.org"));
+    }
+
+    #[test]
+    fn trusted_authentication_results() {
+        let message = concat!(
+            "Authentication-Results: relay.example.org;\r\n",
+            "\tspf=pass smtp.mailfrom=example.com\r\n",
+            "Authentication-Results: MX.Example.org; dkim=pass header.d=example.com\r\n",
+            "From: jdoe@example.com\r\n",
+            "Authentication-Results: other.example.net; spf=fail\r\n",
+            "authentication-results: (forged) mx.example.org 1;\r\n",
+            " dmarc=pass header.from=example.com\r\n",
+            "Subject: test\r\n",
+            "\r\n",
+            "body\r\n"
+        );
+        let message = AuthenticatedMessage::parse(message.as_bytes()).unwrap();
+
+        // Results claiming a trusted authserv-id
+        let trusted = message.authentication_results(&["relay.example.org"]);
+        assert_eq!(trusted.len(), 1);
+        assert_eq!(trusted[0].results[0].method, AuthMethod::Spf);
+        assert_eq!(trusted[0].results[0].result, AuthResultCode::Pass);
+        assert_eq!(
+            message
+                .authentication_results(&["mx.example.org", "unknown.example.org"])
+                .iter()
+                .map(|h| h.results[0].method.clone())
+                .collect::<Vec<_>>(),
+            vec![AuthMethod::Dkim, AuthMethod::Dmarc]
+        );
+
+        // Forged results are removed, everything else is preserved verbatim
+        assert_eq!(
+            String::from_utf8(message.remove_authentication_results(&["mx.example.org"])).unwrap(),
+            concat!(
+                "Authentication-Results: relay.example.org;\r\n",
+                "\tspf=pass smtp.mailfrom=example.com\r\n",
+                "From: jdoe@example.com\r\n",
+                "Authentication-Results: other.example.net; spf=fail\r\n",
+                "Subject: test\r\n",
+            )
+        );
+        assert_eq!(
+            message.remove_authentication_results(&[]),
+            message.raw_headers().strip_suffix(b"\r\n").unwrap()
+        );
+
+        // Headers added after parsing are written from their name and value
+        let mut modified = message.clone();
+        modified.headers.push((b"X-Added", b" yes\r\n"));
+        assert!(modified
+            .remove_authentication_results(&["mx.example.org"])
+            .ends_with(b"Subject: test\r\nX-Added: yes\r\n"));
+    }
 }
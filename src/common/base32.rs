@@ -8,7 +8,8 @@
  * except according to those terms.
  */
 
-use std::slice::Iter;
+use alloc::string::String;
+use core::slice::Iter;
 
 use super::headers::Writer;
 
@@ -44,7 +45,7 @@ impl Base32Writer {
 
     pub fn with_capacity(capacity: usize) -> Self {
         Base32Writer {
-            result: String::with_capacity((capacity + 3) / 4 * 5),
+            result: String::with_capacity(capacity.div_ceil(4) * 5),
             last_byte: 0,
             pos: 0,
         }
@@ -0,0 +1,120 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use alloc::vec::Vec;
+
+pub(crate) static BASE64_ALPHABET: &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn base64_encode(bytes: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        result.push(BASE64_ALPHABET[(b[0] >> 2) as usize]);
+        result.push(BASE64_ALPHABET[(((b[0] & 0x03) << 4) | (b[1] >> 4)) as usize]);
+        result.push(if chunk.len() > 1 {
+            BASE64_ALPHABET[(((b[1] & 0x0f) << 2) | (b[2] >> 6)) as usize]
+        } else {
+            b'='
+        });
+        result.push(if chunk.len() > 2 {
+            BASE64_ALPHABET[(b[2] & 0x3f) as usize]
+        } else {
+            b'='
+        });
+    }
+    result
+}
+
+pub fn base64_decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    base64_decode_stream(bytes.iter(), bytes.len(), u8::MAX)
+}
+
+/// Decodes base64 from `stream` until `stop_char` or the end of the stream is
+/// reached, skipping folding whitespace.
+pub fn base64_decode_stream<'x>(
+    stream: impl Iterator<Item = &'x u8>,
+    stream_len: usize,
+    stop_char: u8,
+) -> Option<Vec<u8>> {
+    let mut chunk: u32 = 0;
+    let mut byte_count: u8 = 0;
+    let mut buf = Vec::with_capacity(stream_len / 4 * 3);
+
+    for &ch in stream {
+        let val = match ch {
+            b'A'..=b'Z' => ch - b'A',
+            b'a'..=b'z' => ch - b'a' + 26,
+            b'0'..=b'9' => ch - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => {
+                match byte_count {
+                    1 | 2 => buf.push((chunk >> 16) as u8),
+                    3 => buf.extend_from_slice(&[(chunk >> 16) as u8, (chunk >> 8) as u8]),
+                    0 => (),
+                    _ => return None,
+                }
+                byte_count = 0;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            _ => return if ch == stop_char { Some(buf) } else { None },
+        };
+
+        if byte_count == 0 {
+            chunk = 0;
+        }
+        chunk |= (val as u32) << (18 - 6 * byte_count as u32);
+        byte_count += 1;
+        if byte_count == 4 {
+            buf.extend_from_slice(&[(chunk >> 16) as u8, (chunk >> 8) as u8, chunk as u8]);
+            byte_count = 0;
+        }
+    }
+
+    Some(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{base64_decode, base64_decode_stream, base64_encode};
+
+    #[test]
+    fn base64_roundtrip() {
+        for (decoded, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64_encode(decoded.as_bytes()), encoded.as_bytes());
+            assert_eq!(
+                base64_decode(encoded.as_bytes()).unwrap(),
+                decoded.as_bytes()
+            );
+        }
+
+        let mut stream = b"Zm9v\r\n YmFy; next".iter();
+        assert_eq!(
+            base64_decode_stream(&mut stream, 14, b';').unwrap(),
+            b"foobar"
+        );
+        assert_eq!(stream.as_slice(), b" next");
+        assert_eq!(base64_decode(b"Zm9v!"), None);
+    }
+}
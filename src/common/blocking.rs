@@ -0,0 +1,548 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+//! Blocking verification API.
+//!
+//! [`BlockingResolver`] exposes the verification methods of [`Resolver`] as
+//! regular functions. Resolvers backed by a static source, such as
+//! [`DnsZone`](super::dns::DnsZone) or a custom synchronous
+//! [`DnsBackend`](super::dns::DnsBackend), are driven on the calling thread
+//! without any runtime. Resolvers performing live queries through
+//! `hickory-resolver` need a Tokio runtime, which is created by
+//! [`BlockingResolver::with_runtime`] and the `new_*` constructors. As that
+//! runtime cannot be driven from within another one, these resolvers return
+//! an error when created or used inside a Tokio runtime.
+//!
+//! DKIM verification against keys known in advance does not require a
+//! resolver at all, see [`DkimVerifier::verify_dkim_with_keys`].
+
+use std::{
+    future::Future,
+    io,
+    net::IpAddr,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+};
+
+use hickory_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    system_conf::read_system_conf,
+};
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use crate::{
+    dkim::{trace::DkimVerificationTrace, verify::DkimVerifier},
+    dmarc::{
+        disposition::{DmarcDisposition, PctSampler},
+        organizational::OrganizationalDomain,
+        URI,
+    },
+    spf::{trace::SpfTrace, SpfLimits},
+    ArcOutput, AuthenticatedMessage, BlockingResolver, DkimOutput, DmarcOutput, IprevOutput,
+    Resolver, SpfOutput,
+};
+
+use super::dns::HickoryBackend;
+
+impl BlockingResolver {
+    /// Wraps a resolver whose backend completes lookups without a runtime.
+    ///
+    /// Verification fails with an error if the backend requires a Tokio
+    /// runtime, as [`HickoryBackend`] does, use
+    /// [`BlockingResolver::with_runtime`] for those.
+    pub fn new(resolver: Resolver) -> Self {
+        BlockingResolver {
+            resolver,
+            runtime: None,
+        }
+    }
+
+    /// Wraps a resolver and creates a single-threaded Tokio runtime used to
+    /// drive its lookups.
+    pub fn with_runtime(resolver: Resolver) -> crate::Result<Self> {
+        Ok(BlockingResolver {
+            resolver,
+            runtime: Some(new_runtime()?),
+        })
+    }
+
+    pub fn new_cloudflare_tls() -> crate::Result<Self> {
+        Self::with_capacity(
+            ResolverConfig::cloudflare_tls(),
+            ResolverOpts::default(),
+            128,
+        )
+    }
+
+    pub fn new_cloudflare() -> crate::Result<Self> {
+        Self::with_capacity(ResolverConfig::cloudflare(), ResolverOpts::default(), 128)
+    }
+
+    pub fn new_google() -> crate::Result<Self> {
+        Self::with_capacity(ResolverConfig::google(), ResolverOpts::default(), 128)
+    }
+
+    pub fn new_quad9() -> crate::Result<Self> {
+        Self::with_capacity(ResolverConfig::quad9(), ResolverOpts::default(), 128)
+    }
+
+    pub fn new_quad9_tls() -> crate::Result<Self> {
+        Self::with_capacity(ResolverConfig::quad9_tls(), ResolverOpts::default(), 128)
+    }
+
+    pub fn new_system_conf() -> crate::Result<Self> {
+        let (config, options) = read_system_conf()?;
+        Self::with_capacity(config, options, 128)
+    }
+
+    pub fn with_capacity(
+        config: ResolverConfig,
+        options: ResolverOpts,
+        capacity: usize,
+    ) -> crate::Result<Self> {
+        Self::with_hickory(capacity, || HickoryBackend::new(config, options))
+    }
+
+    /// Creates a resolver that validates TXT answers with DNSSEC, see
+    /// [`Resolver::with_dnssec`].
+    pub fn with_dnssec(
+        config: ResolverConfig,
+        options: ResolverOpts,
+        capacity: usize,
+    ) -> crate::Result<Self> {
+        Self::with_hickory(capacity, || HickoryBackend::with_dnssec(config, options))
+    }
+
+    fn with_hickory(
+        capacity: usize,
+        backend: impl FnOnce() -> HickoryBackend,
+    ) -> crate::Result<Self> {
+        // Build the runtime first, hickory spawns its connections on it
+        let runtime = new_runtime()?;
+        let resolver = {
+            let _guard = runtime.enter();
+            Resolver::with_backend(backend(), capacity)
+        };
+        Ok(BlockingResolver {
+            resolver,
+            runtime: Some(runtime),
+        })
+    }
+
+    pub fn resolver(&self) -> &Resolver {
+        &self.resolver
+    }
+
+    pub fn into_inner(self) -> Resolver {
+        self.resolver
+    }
+
+    /// Runs a future to completion on the runtime of this resolver, or on
+    /// the calling thread if it has none. Fails if this resolver has a
+    /// runtime and is called from within another Tokio runtime, or if it has
+    /// none and its backend requires one.
+    pub(crate) fn block_on<F: Future>(&self, future: F) -> crate::Result<F::Output> {
+        match &self.runtime {
+            Some(runtime) => {
+                outside_runtime()?;
+                Ok(runtime.block_on(future))
+            }
+            None if self.resolver.backend.requires_runtime() => Err(io::Error::new(
+                io::ErrorKind::Other,
+                "resolver backend requires a Tokio runtime, see BlockingResolver::with_runtime",
+            )
+            .into()),
+            None => Ok(block_on(future)),
+        }
+    }
+
+    /// Blocking version of [`DkimVerifier::verify_dkim`].
+    pub fn verify_dkim<'x>(
+        &self,
+        message: &'x AuthenticatedMessage<'x>,
+    ) -> crate::Result<Vec<DkimOutput<'x>>> {
+        self.block_on(DkimVerifier::verify_dkim(&self.resolver, message))
+    }
+
+    /// Blocking version of [`DkimVerifier::trace_dkim`].
+    pub fn trace_dkim<'x>(
+        &self,
+        message: &'x AuthenticatedMessage<'x>,
+    ) -> crate::Result<Vec<DkimVerificationTrace<'x>>> {
+        self.block_on(DkimVerifier::trace_dkim(&self.resolver, message))
+    }
+
+    /// Blocking version of [`Resolver::verify_arc`].
+    pub fn verify_arc<'x>(
+        &self,
+        message: &'x AuthenticatedMessage<'x>,
+    ) -> crate::Result<ArcOutput<'x>> {
+        self.block_on(self.resolver.verify_arc(message))
+    }
+
+    /// Blocking version of [`Resolver::verify_spf_helo`].
+    pub fn verify_spf_helo(
+        &self,
+        ip: IpAddr,
+        helo_domain: &str,
+        host_domain: &str,
+    ) -> crate::Result<SpfOutput> {
+        self.block_on(self.resolver.verify_spf_helo(ip, helo_domain, host_domain))
+    }
+
+    /// Blocking version of [`Resolver::verify_spf_sender`].
+    pub fn verify_spf_sender(
+        &self,
+        ip: IpAddr,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+    ) -> crate::Result<SpfOutput> {
+        self.block_on(
+            self.resolver
+                .verify_spf_sender(ip, helo_domain, host_domain, sender),
+        )
+    }
+
+    /// Blocking version of [`Resolver::verify_spf_sender_with_limits`].
+    pub fn verify_spf_sender_with_limits(
+        &self,
+        ip: IpAddr,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+        limits: SpfLimits,
+    ) -> crate::Result<SpfOutput> {
+        self.block_on(self.resolver.verify_spf_sender_with_limits(
+            ip,
+            helo_domain,
+            host_domain,
+            sender,
+            limits,
+        ))
+    }
+
+    /// Blocking version of [`Resolver::verify_spf`].
+    pub fn verify_spf(
+        &self,
+        ip: IpAddr,
+        helo_domain: &str,
+        host_domain: &str,
+        mail_from: &str,
+    ) -> crate::Result<SpfOutput> {
+        self.block_on(
+            self.resolver
+                .verify_spf(ip, helo_domain, host_domain, mail_from),
+        )
+    }
+
+    /// Blocking version of [`Resolver::verify_spf_with_limits`].
+    pub fn verify_spf_with_limits(
+        &self,
+        ip: IpAddr,
+        helo_domain: &str,
+        host_domain: &str,
+        mail_from: &str,
+        limits: SpfLimits,
+    ) -> crate::Result<SpfOutput> {
+        self.block_on(self.resolver.verify_spf_with_limits(
+            ip,
+            helo_domain,
+            host_domain,
+            mail_from,
+            limits,
+        ))
+    }
+
+    /// Blocking version of [`Resolver::check_host`].
+    pub fn check_host(
+        &self,
+        ip: IpAddr,
+        domain: &str,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+    ) -> crate::Result<SpfOutput> {
+        self.block_on(
+            self.resolver
+                .check_host(ip, domain, helo_domain, host_domain, sender),
+        )
+    }
+
+    /// Blocking version of [`Resolver::check_host_with_limits`].
+    pub fn check_host_with_limits(
+        &self,
+        ip: IpAddr,
+        domain: &str,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+        limits: SpfLimits,
+    ) -> crate::Result<SpfOutput> {
+        self.block_on(self.resolver.check_host_with_limits(
+            ip,
+            domain,
+            helo_domain,
+            host_domain,
+            sender,
+            limits,
+        ))
+    }
+
+    /// Blocking version of [`Resolver::trace_spf`].
+    pub fn trace_spf(
+        &self,
+        ip: IpAddr,
+        domain: &str,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+    ) -> crate::Result<SpfTrace> {
+        self.block_on(
+            self.resolver
+                .trace_spf(ip, domain, helo_domain, host_domain, sender),
+        )
+    }
+
+    /// Blocking version of [`Resolver::verify_dmarc`].
+    pub fn verify_dmarc(
+        &self,
+        message: &AuthenticatedMessage<'_>,
+        dkim_output: &[DkimOutput<'_>],
+        rfc5321_mail_from_domain: &str,
+        spf_output: &SpfOutput,
+        domain_suffix_fn: impl Fn(&str) -> &str,
+    ) -> crate::Result<DmarcOutput> {
+        self.block_on(self.resolver.verify_dmarc(
+            message,
+            dkim_output,
+            rfc5321_mail_from_domain,
+            spf_output,
+            domain_suffix_fn,
+        ))
+    }
+
+    /// Blocking version of [`Resolver::verify_dmarc_with_org_domain`].
+    pub fn verify_dmarc_with_org_domain(
+        &self,
+        message: &AuthenticatedMessage<'_>,
+        dkim_output: &[DkimOutput<'_>],
+        rfc5321_mail_from_domain: &str,
+        spf_output: &SpfOutput,
+        org_domain: &(impl OrganizationalDomain + ?Sized),
+    ) -> crate::Result<DmarcOutput> {
+        self.block_on(self.resolver.verify_dmarc_with_org_domain(
+            message,
+            dkim_output,
+            rfc5321_mail_from_domain,
+            spf_output,
+            org_domain,
+        ))
+    }
+
+    /// Blocking version of [`Resolver::verify_dmarc_report_address`].
+    pub fn verify_dmarc_report_address<'x>(
+        &self,
+        domain: &str,
+        addresses: &'x [URI],
+    ) -> crate::Result<Option<Vec<&'x URI>>> {
+        self.block_on(self.resolver.verify_dmarc_report_address(domain, addresses))
+    }
+
+    /// Blocking version of [`Resolver::dmarc_disposition`].
+    pub fn dmarc_disposition(
+        &self,
+        output: &DmarcOutput,
+        sampler: &mut impl PctSampler,
+    ) -> crate::Result<DmarcDisposition> {
+        self.block_on(self.resolver.dmarc_disposition(output, sampler))
+    }
+
+    /// Blocking version of [`Resolver::verify_iprev`].
+    pub fn verify_iprev(&self, addr: IpAddr) -> crate::Result<IprevOutput> {
+        self.block_on(self.resolver.verify_iprev(addr))
+    }
+}
+
+impl From<Resolver> for BlockingResolver {
+    fn from(resolver: Resolver) -> Self {
+        BlockingResolver::new(resolver)
+    }
+}
+
+fn new_runtime() -> crate::Result<Runtime> {
+    outside_runtime()?;
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(Into::into)
+}
+
+/// Blocking on a runtime, or dropping one, panics inside another runtime.
+fn outside_runtime() -> crate::Result<()> {
+    if Handle::try_current().is_err() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "cannot block on a Tokio runtime from within another one",
+        )
+        .into())
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls a future on the calling thread, parking it while the future is
+/// pending.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hickory_resolver::config::{ResolverConfig, ResolverOpts};
+
+    use crate::{
+        common::dns::{DnsZone, HickoryBackend},
+        AuthenticatedMessage, BlockingResolver, DkimResult, DmarcResult, IprevResult, Resolver,
+        SpfResult,
+    };
+
+    #[test]
+    fn blocking_verify() {
+        let test = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/resources/dkim/001.txt"
+        ))
+        .unwrap();
+        let (dns_records, raw_message) = test.split_once("\n\n").unwrap();
+        let raw_message = raw_message.replace('\n', "\r\n");
+        let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
+        let signed_at = message.dkim_headers[0].header.as_ref().unwrap().t;
+
+        let new_resolver = || {
+            let zone = DnsZone::new();
+            for (name, value) in dns_records.split('\n').filter_map(|r| r.split_once(' ')) {
+                zone.add_txt(name, value);
+            }
+            zone.add_txt("football.example.com", "v=spf1 ip4:192.0.2.1 -all")
+                .add_txt("_dmarc.football.example.com", "v=DMARC1; p=reject")
+                .add_ipv4("mx.football.example.com", "192.0.2.1".parse().unwrap())
+                .add_ptr("192.0.2.1".parse().unwrap(), "mx.football.example.com");
+            Resolver::with_backend(zone, 128).with_clock(signed_at)
+        };
+
+        // Static records are verified without a runtime, or on a runtime
+        // owned by the resolver
+        for resolver in [
+            BlockingResolver::new(new_resolver()),
+            BlockingResolver::with_runtime(new_resolver()).unwrap(),
+        ] {
+            let dkim = resolver.verify_dkim(&message).unwrap();
+            assert!(dkim.iter().all(|o| o.result() == &DkimResult::Pass));
+            assert_eq!(resolver.trace_dkim(&message).unwrap().len(), dkim.len());
+            assert_eq!(
+                resolver.verify_arc(&message).unwrap().result(),
+                &DkimResult::None
+            );
+
+            let spf = resolver
+                .verify_spf(
+                    "192.0.2.1".parse().unwrap(),
+                    "football.example.com",
+                    "mx.example.org",
+                    "joe@football.example.com",
+                )
+                .unwrap();
+            assert_eq!(spf.result(), SpfResult::Pass);
+            assert_eq!(
+                resolver
+                    .trace_spf(
+                        "192.0.2.1".parse().unwrap(),
+                        "football.example.com",
+                        "football.example.com",
+                        "mx.example.org",
+                        "joe@football.example.com",
+                    )
+                    .unwrap()
+                    .output(),
+                &spf
+            );
+            assert_eq!(
+                resolver
+                    .verify_spf_sender(
+                        "192.0.2.2".parse().unwrap(),
+                        "football.example.com",
+                        "mx.example.org",
+                        "joe@football.example.com",
+                    )
+                    .unwrap()
+                    .result(),
+                SpfResult::Fail
+            );
+
+            let dmarc = resolver
+                .verify_dmarc(&message, &dkim, "football.example.com", &spf, |d| d)
+                .unwrap();
+            assert_eq!(dmarc.dkim_result(), &DmarcResult::Pass);
+            assert_eq!(dmarc.spf_result(), &DmarcResult::Pass);
+
+            assert_eq!(
+                resolver
+                    .verify_iprev("192.0.2.1".parse().unwrap())
+                    .unwrap()
+                    .result(),
+                &IprevResult::Pass
+            );
+        }
+
+        // Resolvers with their own runtime cannot be driven from within another
+        let with_runtime = BlockingResolver::with_runtime(new_resolver()).unwrap();
+        let without_runtime = BlockingResolver::new(new_resolver());
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                assert!(BlockingResolver::with_runtime(new_resolver()).is_err());
+                assert!(BlockingResolver::with_dnssec(
+                    ResolverConfig::cloudflare(),
+                    ResolverOpts::default(),
+                    128
+                )
+                .is_err());
+                assert!(with_runtime.verify_dkim(&message).is_err());
+                assert!(without_runtime.verify_dkim(&message).is_ok());
+            });
+
+        // Live lookups are rejected without a runtime
+        let hickory = BlockingResolver::new(Resolver::with_backend(
+            HickoryBackend::new(ResolverConfig::cloudflare(), ResolverOpts::default()),
+            128,
+        ));
+        assert!(hickory.verify_iprev("192.0.2.1".parse().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,62 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+#[cfg(feature = "std")]
+use std::time::SystemTime;
+
+/// Source of the current time, in seconds since the UNIX epoch, used for
+/// signature expiration checks, signing timestamps and the SPF `%{t}` macro.
+///
+/// Without the `std` feature there is no system clock, so there is no
+/// default and verifiers must be given the time explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "std", derive(Default))]
+pub enum Clock {
+    /// Reads the system wall clock.
+    #[cfg(feature = "std")]
+    #[default]
+    System,
+    /// Always returns the given timestamp.
+    Fixed(u64),
+}
+
+impl Clock {
+    pub fn now(&self) -> u64 {
+        match self {
+            #[cfg(feature = "std")]
+            Clock::System => SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            Clock::Fixed(timestamp) => *timestamp,
+        }
+    }
+}
+
+impl Clock {
+    /// Clock used by signers until one is set: the system clock, or without
+    /// the `std` feature `Fixed(0)`, so the signing time has to be supplied.
+    pub(crate) fn signing() -> Self {
+        #[cfg(feature = "std")]
+        {
+            Clock::System
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            Clock::Fixed(0)
+        }
+    }
+}
+
+impl From<u64> for Clock {
+    fn from(timestamp: u64) -> Self {
+        Clock::Fixed(timestamp)
+    }
+}
@@ -1,10 +1,16 @@
 #[cfg(feature = "sha1")]
 use sha1::{digest::Output, Digest};
 
+use alloc::{boxed::Box, vec::Vec};
+use core::{future::Future, pin::Pin};
+
 use crate::{dkim::Canonicalization, Result};
 
 use super::headers::{Writable, Writer};
 
+#[cfg(any(feature = "ring", feature = "rust-crypto"))]
+pub(crate) mod keys;
+
 #[cfg(feature = "rust-crypto")]
 mod rust_crypto;
 #[cfg(feature = "rust-crypto")]
@@ -33,6 +39,36 @@ pub trait SigningKey {
     fn algorithm(&self) -> Algorithm;
 }
 
+/// Future returned by [`AsyncSigningKey::sign`].
+pub type SignFuture<'x> = Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send + 'x>>;
+
+/// Signing key that produces signatures asynchronously, such as a key held
+/// by an HSM or a remote signing service that is never exported to the
+/// process. The trait is object safe, so signers can hold a
+/// `Box<dyn AsyncSigningKey<Hasher = Sha256> + Send + Sync>`.
+pub trait AsyncSigningKey {
+    type Hasher: HashImpl;
+
+    /// Signs `input` with the key's algorithm, returning the signature in
+    /// the same format as [`SigningKey::sign`]: PKCS#1 v1.5 for RSA keys and
+    /// Ed25519 over the SHA-256 hash of `input` for Ed25519 keys.
+    fn sign<'x>(&'x self, input: Vec<u8>) -> SignFuture<'x>;
+
+    fn algorithm(&self) -> Algorithm;
+}
+
+impl<T: AsyncSigningKey + ?Sized> AsyncSigningKey for Box<T> {
+    type Hasher = T::Hasher;
+
+    fn sign<'x>(&'x self, input: Vec<u8>) -> SignFuture<'x> {
+        (**self).sign(input)
+    }
+
+    fn algorithm(&self) -> Algorithm {
+        (**self).algorithm()
+    }
+}
+
 pub trait VerifyingKey {
     fn verify<'a>(
         &self,
@@ -43,12 +79,21 @@ pub trait VerifyingKey {
     ) -> Result<()>;
 }
 
+/// Key type of a DKIM public key record, as published in the `k=` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum VerifyingKeyType {
     Rsa,
     Ed25519,
 }
 
 impl VerifyingKeyType {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            VerifyingKeyType::Rsa => "rsa",
+            VerifyingKeyType::Ed25519 => "ed25519",
+        }
+    }
+
     pub(crate) fn verifying_key(
         &self,
         bytes: &[u8],
@@ -84,6 +129,7 @@ pub struct Sha256;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u64)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HashAlgorithm {
     Sha1 = R_HASH_SHA1,
     Sha256 = R_HASH_SHA256,
@@ -121,6 +167,39 @@ impl HashAlgorithm {
     }
 }
 
+/// Hashing context for an algorithm selected at runtime.
+pub enum HashState {
+    Sha1(<Sha1 as HashImpl>::Context),
+    Sha256(<Sha256 as HashImpl>::Context),
+}
+
+impl HashAlgorithm {
+    pub fn hasher(&self) -> HashState {
+        match self {
+            Self::Sha1 => HashState::Sha1(Sha1::hasher()),
+            Self::Sha256 => HashState::Sha256(Sha256::hasher()),
+        }
+    }
+}
+
+impl Writer for HashState {
+    fn write(&mut self, buf: &[u8]) {
+        match self {
+            Self::Sha1(hasher) => hasher.write(buf),
+            Self::Sha256(hasher) => hasher.write(buf),
+        }
+    }
+}
+
+impl HashContext for HashState {
+    fn complete(self) -> HashOutput {
+        match self {
+            Self::Sha1(hasher) => hasher.complete(),
+            Self::Sha256(hasher) => hasher.complete(),
+        }
+    }
+}
+
 #[non_exhaustive]
 pub enum HashOutput {
     #[cfg(feature = "ring")]
@@ -145,6 +224,7 @@ impl AsRef<[u8]> for HashOutput {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Algorithm {
     RsaSha1,
     #[default]
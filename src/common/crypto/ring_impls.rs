@@ -1,4 +1,5 @@
-use std::marker::PhantomData;
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
+use core::marker::PhantomData;
 
 use ring::digest::{Context, SHA1_FOR_LEGACY_USE_ONLY, SHA256};
 use ring::rand::SystemRandom;
@@ -69,6 +70,20 @@ impl<T: HashImpl> RsaKey<T> {
         })
     }
 
+    /// Creates a new RSA private key from a PKCS1 PEM string.
+    pub fn from_pkcs1_pem(private_key_pem: &str) -> Result<Self> {
+        match pkcs1::der::SecretDocument::from_pem(private_key_pem) {
+            Ok(("RSA PRIVATE KEY", der)) => Self::from_der(der.as_bytes()),
+            Ok(_) => Err(Error::CryptoError("No RSA key found in PEM".to_string())),
+            Err(err) => Err(Error::CryptoError(err.to_string())),
+        }
+    }
+
+    /// Creates a new RSA private key from a PKCS1 binary slice.
+    pub fn from_pkcs1_der(private_key_bytes: &[u8]) -> Result<Self> {
+        Self::from_der(private_key_bytes)
+    }
+
     /// Returns the public key of the RSA key pair.
     pub fn public_key(&self) -> Vec<u8> {
         self.inner.public().as_ref().to_vec()
@@ -127,6 +142,14 @@ impl Ed25519Key {
         })
     }
 
+    /// Creates an Ed25519 private key from its 32-byte seed.
+    pub fn from_seed(seed: &[u8]) -> Result<Self> {
+        Ok(Self {
+            inner: Ed25519KeyPair::from_seed_unchecked(seed)
+                .map_err(|err| Error::CryptoError(err.to_string()))?,
+        })
+    }
+
     // Returns the public key of the Ed25519 key pair.
     pub fn public_key(&self) -> Vec<u8> {
         self.inner.public_key().as_ref().to_vec()
@@ -1,8 +1,13 @@
-use std::array::TryFromSliceError;
-use std::marker::PhantomData;
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+use core::array::TryFromSliceError;
+use core::marker::PhantomData;
 
 use ed25519_dalek::Signer;
-use rsa::{pkcs1::DecodeRsaPrivateKey, Pkcs1v15Sign, RsaPrivateKey};
+use rsa::{
+    pkcs1::{DecodeRsaPrivateKey, EncodeRsaPublicKey},
+    pkcs8::DecodePrivateKey,
+    Pkcs1v15Sign, RsaPrivateKey,
+};
 use sha2::digest::Digest;
 
 use crate::{
@@ -41,6 +46,47 @@ impl<T: HashImpl> RsaKey<T> {
             padding: PhantomData,
         })
     }
+
+    /// Creates a new RSA private key from a PKCS8 PEM string.
+    pub fn from_pkcs8_pem(pkcs8_pem: &str) -> Result<Self> {
+        let inner = RsaPrivateKey::from_pkcs8_pem(pkcs8_pem)
+            .map_err(|err| Error::CryptoError(err.to_string()))?;
+
+        Ok(RsaKey {
+            inner,
+            padding: PhantomData,
+        })
+    }
+
+    /// Creates a new RSA private key from PKCS8 DER-encoded bytes.
+    pub fn from_pkcs8_der(pkcs8_der: &[u8]) -> Result<Self> {
+        let inner = RsaPrivateKey::from_pkcs8_der(pkcs8_der)
+            .map_err(|err| Error::CryptoError(err.to_string()))?;
+
+        Ok(RsaKey {
+            inner,
+            padding: PhantomData,
+        })
+    }
+
+    /// Creates a new RSA private key from a PKCS1 PEM string.
+    pub fn from_rsa_pem(rsa_pem: &str) -> Result<Self> {
+        Self::from_pkcs1_pem(rsa_pem)
+    }
+
+    /// Creates a new RSA private key from a PKCS1 binary slice.
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        Self::from_pkcs1_der(der)
+    }
+
+    /// Returns the public key of the RSA key pair.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.inner
+            .to_public_key()
+            .to_pkcs1_der()
+            .map(|der| der.into_vec())
+            .unwrap_or_default()
+    }
 }
 
 impl SigningKey for RsaKey<Sha1> {
@@ -84,16 +130,46 @@ pub struct Ed25519Key {
 }
 
 impl Ed25519Key {
-    /// Creates an Ed25519 private key
-    pub fn from_bytes(private_key_bytes: &[u8]) -> crate::Result<Self> {
+    /// Creates an Ed25519 private key from its 32-byte seed.
+    pub fn from_seed(seed: &[u8]) -> crate::Result<Self> {
         Ok(Self {
             inner: ed25519_dalek::SigningKey::from_bytes(
-                private_key_bytes
-                    .try_into()
+                seed.try_into()
                     .map_err(|err: TryFromSliceError| Error::CryptoError(err.to_string()))?,
             ),
         })
     }
+
+    /// Creates an Ed25519 private key from its 32-byte seed.
+    #[deprecated(
+        note = "use `Ed25519Key::from_seed`, or `Ed25519Key::from_private_key` for encoded keys"
+    )]
+    pub fn from_bytes(private_key_bytes: &[u8]) -> crate::Result<Self> {
+        Self::from_seed(private_key_bytes)
+    }
+
+    pub fn from_pkcs8_der(pkcs8_der: &[u8]) -> Result<Self> {
+        Ok(Self {
+            inner: ed25519_dalek::SigningKey::from_pkcs8_der(pkcs8_der)
+                .map_err(|err| Error::CryptoError(err.to_string()))?,
+        })
+    }
+
+    pub fn from_seed_and_public_key(seed: &[u8], public_key: &[u8]) -> Result<Self> {
+        let key = Self::from_seed(seed)?;
+        if key.public_key() == public_key {
+            Ok(key)
+        } else {
+            Err(Error::CryptoError(
+                "Public key does not match the seed".to_string(),
+            ))
+        }
+    }
+
+    // Returns the public key of the Ed25519 key pair.
+    pub fn public_key(&self) -> Vec<u8> {
+        self.inner.verifying_key().to_bytes().to_vec()
+    }
 }
 
 impl SigningKey for Ed25519Key {
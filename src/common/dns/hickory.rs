@@ -0,0 +1,420 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use futures_util::{Stream, TryStreamExt};
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    name_server::{NameServerPool, TokioConnectionProvider},
+    proto::{
+        error::ProtoErrorKind,
+        op::Query,
+        rr::{Record, RecordType},
+        xfer::{
+            DnsHandle, DnsRequest, DnsRequestOptions, DnsResponse, DnssecDnsHandle, FirstAnswer,
+            RetryDnsHandle,
+        },
+    },
+    AsyncResolver, Name, TokioAsyncResolver,
+};
+use parking_lot::Mutex;
+
+use crate::{DnssecStatus, Error, MX};
+
+use super::{DnsAnswer, DnsBackend, DnsFuture};
+
+/// [`DnsBackend`] performing live queries through `hickory-resolver`.
+#[derive(Clone)]
+pub struct HickoryBackend {
+    resolver: TokioAsyncResolver,
+    validator: Option<Validator>,
+}
+
+type Client = RetryDnsHandle<NameServerPool<TokioConnectionProvider>>;
+
+/// Uncached client that sends TXT queries through a [`DnssecDnsHandle`].
+#[derive(Clone)]
+struct Validator {
+    client: Client,
+    options: DnsRequestOptions,
+}
+
+/// Handle that keeps a copy of the unvalidated response to `query`, so the
+/// answer can still be returned when DNSSEC validation fails.
+#[derive(Clone)]
+struct ResponseRecorder {
+    client: Client,
+    query: Query,
+    response: Arc<Mutex<Option<DnsResponse>>>,
+}
+
+impl HickoryBackend {
+    pub fn new(config: ResolverConfig, options: ResolverOpts) -> Self {
+        Self {
+            resolver: AsyncResolver::tokio(config, options),
+            validator: None,
+        }
+    }
+
+    /// Creates a backend that also validates TXT answers with DNSSEC and
+    /// reports their [`DnssecStatus`].
+    ///
+    /// TXT queries are sent once and validated as they are received: answers
+    /// without signatures are returned as `Insecure`, answers whose signatures
+    /// fail validation as `Bogus` and answers whose validation could not be
+    /// completed as `Indeterminate`. TXT answers are not cached by hickory.
+    pub fn with_dnssec(config: ResolverConfig, options: ResolverOpts) -> Self {
+        let name_servers = config
+            .name_servers()
+            .iter()
+            .map(|ns_config| {
+                let mut ns_config = ns_config.clone();
+                ns_config.tls_config = config.client_config().clone();
+                ns_config
+            })
+            .collect::<Vec<_>>();
+        let pool = NameServerPool::from_config(
+            NameServerConfigGroup::from(name_servers),
+            options.clone(),
+            TokioConnectionProvider::default(),
+        );
+        let mut request_options = DnsRequestOptions::default();
+        request_options.use_edns = options.edns0;
+        request_options.recursion_desired = options.recursion_desired;
+
+        Self {
+            validator: Some(Validator {
+                client: RetryDnsHandle::new(pool, options.attempts),
+                options: request_options,
+            }),
+            resolver: AsyncResolver::tokio(config, options),
+        }
+    }
+
+    pub fn resolver(&self) -> &TokioAsyncResolver {
+        &self.resolver
+    }
+}
+
+impl From<TokioAsyncResolver> for HickoryBackend {
+    fn from(resolver: TokioAsyncResolver) -> Self {
+        Self {
+            resolver,
+            validator: None,
+        }
+    }
+}
+
+impl DnsBackend for HickoryBackend {
+    fn txt_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Vec<u8>>>> {
+        Box::pin(async move {
+            let name = Name::from_str_relaxed(name)?;
+            if let Some(validator) = &self.validator {
+                return validator.txt_lookup(name).await;
+            }
+
+            let txt_lookup = self.resolver.txt_lookup(name).await?;
+            Ok(DnsAnswer {
+                records: txt_records(txt_lookup.as_lookup().record_iter()),
+                valid_until: txt_lookup.valid_until(),
+                dnssec: None,
+            })
+        })
+    }
+
+    fn mx_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<MX>>> {
+        Box::pin(async move {
+            let mx_lookup = self
+                .resolver
+                .mx_lookup(Name::from_str_relaxed(name)?)
+                .await?;
+            let mx_records = mx_lookup.as_lookup().records();
+            let mut records: Vec<MX> = Vec::with_capacity(mx_records.len());
+            for mx_record in mx_records {
+                if let Some(mx) = mx_record.data().and_then(|r| r.as_mx()) {
+                    let preference = mx.preference();
+                    let exchange = mx.exchange().to_lowercase().to_string();
+
+                    if let Some(record) = records.iter_mut().find(|r| r.preference == preference) {
+                        record.exchanges.push(exchange);
+                    } else {
+                        records.push(MX {
+                            exchanges: vec![exchange],
+                            preference,
+                        });
+                    }
+                }
+            }
+
+            records.sort_unstable_by_key(|a| a.preference);
+
+            Ok(DnsAnswer {
+                records,
+                valid_until: mx_lookup.valid_until(),
+                dnssec: None,
+            })
+        })
+    }
+
+    fn ipv4_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv4Addr>>> {
+        Box::pin(async move {
+            let ipv4_lookup = self
+                .resolver
+                .ipv4_lookup(Name::from_str_relaxed(name)?)
+                .await?;
+            let records = ipv4_lookup
+                .as_lookup()
+                .record_iter()
+                .filter_map(|r| r.data()?.as_a()?.0.into())
+                .collect();
+
+            Ok(DnsAnswer {
+                records,
+                valid_until: ipv4_lookup.valid_until(),
+                dnssec: None,
+            })
+        })
+    }
+
+    fn ipv6_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv6Addr>>> {
+        Box::pin(async move {
+            let ipv6_lookup = self
+                .resolver
+                .ipv6_lookup(Name::from_str_relaxed(name)?)
+                .await?;
+            let records = ipv6_lookup
+                .as_lookup()
+                .record_iter()
+                .filter_map(|r| r.data()?.as_aaaa()?.0.into())
+                .collect();
+
+            Ok(DnsAnswer {
+                records,
+                valid_until: ipv6_lookup.valid_until(),
+                dnssec: None,
+            })
+        })
+    }
+
+    fn ptr_lookup(&self, addr: IpAddr) -> DnsFuture<'_, DnsAnswer<Vec<String>>> {
+        Box::pin(async move {
+            let ptr_lookup = self.resolver.reverse_lookup(addr).await?;
+            let records = ptr_lookup
+                .as_lookup()
+                .record_iter()
+                .filter_map(|r| {
+                    let r = r.data()?.as_ptr()?;
+                    if !r.is_empty() {
+                        r.to_lowercase().to_string().into()
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+
+            Ok(DnsAnswer {
+                records,
+                valid_until: ptr_lookup.valid_until(),
+                dnssec: None,
+            })
+        })
+    }
+
+    fn exists<'x>(&'x self, name: &'x str) -> DnsFuture<'x, bool> {
+        Box::pin(async move {
+            match self.resolver.lookup_ip(Name::from_str_relaxed(name)?).await {
+                Ok(result) => Ok(result.as_lookup().record_iter().any(|r| {
+                    r.data().is_some_and(|d| {
+                        matches!(d.record_type(), RecordType::A | RecordType::AAAA)
+                    })
+                })),
+                Err(err) => {
+                    if matches!(err.kind(), ResolveErrorKind::NoRecordsFound { .. }) {
+                        Ok(false)
+                    } else {
+                        Err(err.into())
+                    }
+                }
+            }
+        })
+    }
+
+    fn requires_runtime(&self) -> bool {
+        true
+    }
+}
+
+impl Validator {
+    async fn txt_lookup(&self, mut name: Name) -> crate::Result<DnsAnswer<Vec<Vec<u8>>>> {
+        name.set_fqdn(true);
+        let query = Query::query(name, RecordType::TXT);
+        let recorder = ResponseRecorder {
+            client: self.client.clone(),
+            query: query.clone(),
+            response: Arc::new(Mutex::new(None)),
+        };
+        let recorded = recorder.response.clone();
+
+        let (response, dnssec) = match DnssecDnsHandle::new(recorder)
+            .lookup(query, self.options)
+            .first_answer()
+            .await
+        {
+            Ok(response) => (response, DnssecStatus::Secure),
+            Err(err) => match (validation_status(&err), recorded.lock().take()) {
+                (Some(status), Some(response)) => (response, status),
+                _ => return Err(err.into()),
+            },
+        };
+        let ttl = response
+            .answers()
+            .iter()
+            .map(Record::ttl)
+            .min()
+            .unwrap_or(0);
+
+        Ok(DnsAnswer {
+            records: txt_records(response.answers().iter()),
+            valid_until: Instant::now() + Duration::from_secs(ttl.into()),
+            dnssec: Some(dnssec),
+        })
+    }
+}
+
+impl DnsHandle for ResponseRecorder {
+    type Response = Pin<Box<dyn Stream<Item = Result<DnsResponse, ResolveError>> + Send>>;
+    type Error = ResolveError;
+
+    fn send<R: Into<DnsRequest> + Unpin + Send + 'static>(&self, request: R) -> Self::Response {
+        let request = request.into();
+        let recorded = request
+            .queries()
+            .first()
+            .is_some_and(|query| {
+                query.query_type() == self.query.query_type() && query.name() == self.query.name()
+            })
+            .then(|| self.response.clone());
+
+        Box::pin(self.client.send(request).inspect_ok(move |response| {
+            if let Some(recorded) = &recorded {
+                *recorded.lock() = Some(response.clone());
+            }
+        }))
+    }
+}
+
+/// Maps a failed validation to the status of the unvalidated answer, or
+/// `None` when the lookup itself failed.
+fn validation_status(err: &ResolveError) -> Option<DnssecStatus> {
+    match err.kind() {
+        ResolveErrorKind::Proto(err) => match err.kind() {
+            ProtoErrorKind::RrsigsNotPresent { .. } => DnssecStatus::Insecure,
+            ProtoErrorKind::Busy | ProtoErrorKind::Io(_) | ProtoErrorKind::Timeout => {
+                DnssecStatus::Indeterminate
+            }
+            _ => DnssecStatus::Bogus,
+        },
+        ResolveErrorKind::Message(_) | ResolveErrorKind::Msg(_) => DnssecStatus::Bogus,
+        ResolveErrorKind::Timeout | ResolveErrorKind::NoConnections | ResolveErrorKind::Io(_) => {
+            DnssecStatus::Indeterminate
+        }
+        _ => return None,
+    }
+    .into()
+}
+
+fn txt_records<'x>(records: impl Iterator<Item = &'x Record>) -> Vec<Vec<u8>> {
+    records
+        .filter_map(|r| {
+            let txt_data = r.data()?.as_txt()?.txt_data();
+            match txt_data.len() {
+                0 => None,
+                1 => txt_data[0].to_vec().into(),
+                _ => {
+                    let mut entry = Vec::with_capacity(255 * txt_data.len());
+                    for data in txt_data {
+                        entry.extend_from_slice(data);
+                    }
+                    entry.into()
+                }
+            }
+        })
+        .collect()
+}
+
+impl From<ResolveError> for Error {
+    fn from(err: ResolveError) -> Self {
+        match err.kind() {
+            ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+                Error::DnsRecordNotFound(*response_code)
+            }
+            _ => Error::DnsError(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hickory_resolver::{
+        error::{ResolveError, ResolveErrorKind},
+        proto::{
+            error::{ProtoError, ProtoErrorKind},
+            op::{Query, ResponseCode},
+            rr::RecordType,
+        },
+        Name,
+    };
+
+    use crate::DnssecStatus;
+
+    use super::validation_status;
+
+    #[test]
+    fn dnssec_validation_status() {
+        let name = Name::from_ascii("example.org.").unwrap();
+        for (err, expected) in [
+            (
+                ResolveError::from(ProtoError::from(ProtoErrorKind::RrsigsNotPresent {
+                    name: name.clone(),
+                    record_type: RecordType::TXT,
+                })),
+                Some(DnssecStatus::Insecure),
+            ),
+            (
+                ResolveError::from(ProtoError::from("validation failed")),
+                Some(DnssecStatus::Bogus),
+            ),
+            (
+                ResolveError::from(ProtoError::from(ProtoErrorKind::Timeout)),
+                Some(DnssecStatus::Indeterminate),
+            ),
+            (
+                ResolveErrorKind::NoRecordsFound {
+                    query: Box::new(Query::query(name, RecordType::TXT)),
+                    soa: None,
+                    negative_ttl: None,
+                    response_code: ResponseCode::NXDomain,
+                    trusted: true,
+                }
+                .into(),
+                None,
+            ),
+        ] {
+            assert_eq!(validation_status(&err), expected, "{err}");
+        }
+    }
+}
@@ -0,0 +1,106 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{
+    future::Future,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    pin::Pin,
+    sync::Arc,
+    time::Instant,
+};
+
+use crate::{DnssecStatus, Error, MX};
+
+pub mod hickory;
+pub mod zone;
+
+pub use hickory::HickoryBackend;
+pub use zone::DnsZone;
+
+pub type DnsFuture<'x, T> = Pin<Box<dyn Future<Output = crate::Result<T>> + Send + 'x>>;
+
+/// Records returned by a [`DnsBackend`] along with the instant until which
+/// they may be cached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsAnswer<T> {
+    pub records: T,
+    pub valid_until: Instant,
+    /// DNSSEC status of the answer, `None` when the backend does not validate.
+    pub dnssec: Option<DnssecStatus>,
+}
+
+/// Source of DNS data used by [`crate::Resolver`].
+///
+/// Names are passed in fully qualified, lowercase form. Backends must return
+/// `Error::DnsRecordNotFound` when the name or record type does not exist and
+/// `Error::DnsError` for transient failures.
+pub trait DnsBackend: Send + Sync {
+    /// Returns one entry per TXT record, with its character-strings concatenated.
+    fn txt_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Vec<u8>>>>;
+
+    /// Returns the MX records grouped by preference, lowest preference first.
+    fn mx_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<MX>>>;
+
+    fn ipv4_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv4Addr>>>;
+
+    fn ipv6_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv6Addr>>>;
+
+    fn ptr_lookup(&self, addr: IpAddr) -> DnsFuture<'_, DnsAnswer<Vec<String>>>;
+
+    /// Returns whether an A or AAAA record exists for the name.
+    fn exists<'x>(&'x self, name: &'x str) -> DnsFuture<'x, bool> {
+        Box::pin(async move {
+            match self.ipv4_lookup(name).await {
+                Ok(answer) if !answer.records.is_empty() => Ok(true),
+                Ok(_) | Err(Error::DnsRecordNotFound(_)) => match self.ipv6_lookup(name).await {
+                    Ok(answer) => Ok(!answer.records.is_empty()),
+                    Err(Error::DnsRecordNotFound(_)) => Ok(false),
+                    Err(err) => Err(err),
+                },
+                Err(err) => Err(err),
+            }
+        })
+    }
+
+    /// Returns whether lookups must be driven by a Tokio runtime.
+    fn requires_runtime(&self) -> bool {
+        false
+    }
+}
+
+impl<T: DnsBackend + ?Sized> DnsBackend for Arc<T> {
+    fn txt_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Vec<u8>>>> {
+        self.as_ref().txt_lookup(name)
+    }
+
+    fn mx_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<MX>>> {
+        self.as_ref().mx_lookup(name)
+    }
+
+    fn ipv4_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv4Addr>>> {
+        self.as_ref().ipv4_lookup(name)
+    }
+
+    fn ipv6_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv6Addr>>> {
+        self.as_ref().ipv6_lookup(name)
+    }
+
+    fn ptr_lookup(&self, addr: IpAddr) -> DnsFuture<'_, DnsAnswer<Vec<String>>> {
+        self.as_ref().ptr_lookup(addr)
+    }
+
+    fn exists<'x>(&'x self, name: &'x str) -> DnsFuture<'x, bool> {
+        self.as_ref().exists(name)
+    }
+
+    fn requires_runtime(&self) -> bool {
+        self.as_ref().requires_runtime()
+    }
+}
@@ -0,0 +1,336 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::{Duration, Instant},
+};
+
+use ahash::AHashMap;
+use hickory_resolver::proto::op::ResponseCode;
+use parking_lot::RwLock;
+
+use crate::{common::resolver::IntoFqdn, DnssecStatus, Error, MX};
+
+use super::{DnsAnswer, DnsBackend, DnsFuture};
+
+/// In-memory [`DnsBackend`] serving records added by the caller, useful for
+/// verifying messages against recorded DNS data.
+///
+/// Lookups of names without any records fail with `NXDomain`, lookups of
+/// record types missing from an existing name fail with `NoError` (NODATA).
+#[derive(Debug)]
+pub struct DnsZone {
+    names: RwLock<AHashMap<String, ZoneRecords>>,
+    ptr: RwLock<AHashMap<IpAddr, Vec<String>>>,
+    ttl: Duration,
+    dnssec: Option<DnssecStatus>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct ZoneRecords {
+    txt: Vec<Vec<u8>>,
+    mx: Vec<MX>,
+    ipv4: Vec<Ipv4Addr>,
+    ipv6: Vec<Ipv6Addr>,
+}
+
+impl DnsZone {
+    pub fn new() -> Self {
+        Self::with_ttl(Duration::from_secs(300))
+    }
+
+    /// Creates an empty zone whose answers may be cached for `ttl`.
+    pub fn with_ttl(ttl: Duration) -> Self {
+        Self {
+            names: RwLock::new(AHashMap::new()),
+            ptr: RwLock::new(AHashMap::new()),
+            ttl,
+            dnssec: None,
+        }
+    }
+
+    /// Reports `status` as the DNSSEC status of every answer served by the zone.
+    pub fn with_dnssec(mut self, status: DnssecStatus) -> Self {
+        self.dnssec = Some(status);
+        self
+    }
+
+    /// Adds a TXT record to `name`.
+    pub fn add_txt<'x>(&self, name: impl IntoFqdn<'x>, value: impl Into<Vec<u8>>) -> &Self {
+        self.names
+            .write()
+            .entry(name.into_fqdn().into_owned())
+            .or_default()
+            .txt
+            .push(value.into());
+        self
+    }
+
+    /// Adds an MX record to `name`.
+    pub fn add_mx<'x>(
+        &self,
+        name: impl IntoFqdn<'x>,
+        preference: u16,
+        exchange: impl IntoFqdn<'x>,
+    ) -> &Self {
+        let exchange = exchange.into_fqdn().into_owned();
+        let mut names = self.names.write();
+        let records = &mut names.entry(name.into_fqdn().into_owned()).or_default().mx;
+        if let Some(record) = records.iter_mut().find(|r| r.preference == preference) {
+            record.exchanges.push(exchange);
+        } else {
+            records.push(MX {
+                exchanges: vec![exchange],
+                preference,
+            });
+            records.sort_unstable_by_key(|a| a.preference);
+        }
+        self
+    }
+
+    /// Adds an A record to `name`.
+    pub fn add_ipv4<'x>(&self, name: impl IntoFqdn<'x>, addr: Ipv4Addr) -> &Self {
+        self.names
+            .write()
+            .entry(name.into_fqdn().into_owned())
+            .or_default()
+            .ipv4
+            .push(addr);
+        self
+    }
+
+    /// Adds an AAAA record to `name`.
+    pub fn add_ipv6<'x>(&self, name: impl IntoFqdn<'x>, addr: Ipv6Addr) -> &Self {
+        self.names
+            .write()
+            .entry(name.into_fqdn().into_owned())
+            .or_default()
+            .ipv6
+            .push(addr);
+        self
+    }
+
+    /// Adds a PTR record for `addr`.
+    pub fn add_ptr<'x>(&self, addr: IpAddr, name: impl IntoFqdn<'x>) -> &Self {
+        self.ptr
+            .write()
+            .entry(addr)
+            .or_default()
+            .push(name.into_fqdn().into_owned());
+        self
+    }
+
+    fn lookup<T: Clone>(
+        &self,
+        name: &str,
+        records: impl FnOnce(&ZoneRecords) -> &Vec<T>,
+    ) -> crate::Result<DnsAnswer<Vec<T>>> {
+        let names = self.names.read();
+        let entry = names
+            .get(name.into_fqdn().as_ref())
+            .ok_or(Error::DnsRecordNotFound(ResponseCode::NXDomain))?;
+        let records = records(entry);
+        if !records.is_empty() {
+            Ok(DnsAnswer {
+                records: records.clone(),
+                valid_until: Instant::now() + self.ttl,
+                dnssec: self.dnssec,
+            })
+        } else {
+            Err(Error::DnsRecordNotFound(ResponseCode::NoError))
+        }
+    }
+}
+
+impl Default for DnsZone {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DnsBackend for DnsZone {
+    fn txt_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Vec<u8>>>> {
+        Box::pin(async move { self.lookup(name, |r| &r.txt) })
+    }
+
+    fn mx_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<MX>>> {
+        Box::pin(async move { self.lookup(name, |r| &r.mx) })
+    }
+
+    fn ipv4_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv4Addr>>> {
+        Box::pin(async move { self.lookup(name, |r| &r.ipv4) })
+    }
+
+    fn ipv6_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv6Addr>>> {
+        Box::pin(async move { self.lookup(name, |r| &r.ipv6) })
+    }
+
+    fn ptr_lookup(&self, addr: IpAddr) -> DnsFuture<'_, DnsAnswer<Vec<String>>> {
+        Box::pin(async move {
+            self.ptr
+                .read()
+                .get(&addr)
+                .map(|records| DnsAnswer {
+                    records: records.clone(),
+                    valid_until: Instant::now() + self.ttl,
+                    dnssec: self.dnssec,
+                })
+                .ok_or(Error::DnsRecordNotFound(ResponseCode::NXDomain))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{net::IpAddr, sync::Arc};
+
+    use hickory_resolver::proto::op::ResponseCode;
+
+    use crate::{
+        common::dns::DnsZone, dkim::verify::DkimVerifier, spf::Spf, AuthenticatedMessage,
+        AuthenticationResults, DkimResult, DnssecStatus, Error, IprevResult, Resolver, SpfResult,
+        MX,
+    };
+
+    #[tokio::test]
+    async fn zone_backend() {
+        let zone = Arc::new(DnsZone::new());
+        zone.add_txt("example.org", "v=spf1 mx a:mail.example.org -all")
+            .add_txt("example.org", "google-site-verification=1234")
+            .add_mx("Example.org", 20, "mx2.example.org")
+            .add_mx("example.org", 10, "mx1.example.org")
+            .add_ipv4("mx1.example.org", "192.0.2.1".parse().unwrap())
+            .add_ipv4("mail.example.org", "192.0.2.2".parse().unwrap())
+            .add_ptr("192.0.2.1".parse::<IpAddr>().unwrap(), "mx1.example.org");
+        let resolver = Resolver::with_backend(zone.clone(), 128);
+
+        // Records are parsed and grouped
+        assert!(resolver.txt_lookup::<Spf>("example.org").await.is_ok());
+        assert_eq!(
+            resolver.mx_lookup("example.org").await.unwrap().as_ref(),
+            &vec![
+                MX {
+                    exchanges: vec!["mx1.example.org.".to_string()],
+                    preference: 10
+                },
+                MX {
+                    exchanges: vec!["mx2.example.org.".to_string()],
+                    preference: 20
+                }
+            ]
+        );
+        assert!(resolver.exists("mail.example.org").await.unwrap());
+        assert!(!resolver.exists("unknown.example.org").await.unwrap());
+        assert!(matches!(
+            resolver.ipv6_lookup("mx1.example.org").await,
+            Err(Error::DnsRecordNotFound(ResponseCode::NoError))
+        ));
+        assert!(matches!(
+            resolver.ipv4_lookup("mx2.example.org").await,
+            Err(Error::DnsRecordNotFound(ResponseCode::NXDomain))
+        ));
+
+        // Verification runs entirely against the zone
+        for (ip, expected) in [
+            ("192.0.2.1", SpfResult::Pass),
+            ("192.0.2.2", SpfResult::Pass),
+            ("192.0.2.3", SpfResult::Fail),
+        ] {
+            assert_eq!(
+                resolver
+                    .verify_spf_sender(
+                        ip.parse().unwrap(),
+                        "mx1.example.org",
+                        "host.example.org",
+                        "sender@example.org",
+                    )
+                    .await
+                    .result(),
+                expected
+            );
+        }
+        assert_eq!(
+            resolver
+                .verify_iprev("192.0.2.1".parse().unwrap())
+                .await
+                .result(),
+            &IprevResult::Pass
+        );
+
+        // Records added later are visible once the name is not cached
+        zone.add_ipv4("late.example.org", "192.0.2.4".parse().unwrap());
+        assert!(resolver.exists("late.example.org").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn zone_dnssec_status() {
+        let test = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/resources/dkim/001.txt"
+        ))
+        .unwrap();
+        let (dns_records, raw_message) = test.split_once("\n\n").unwrap();
+        let raw_message = raw_message.replace('\n', "\r\n");
+        let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
+        let signed_at = message.dkim_headers[0].header.as_ref().unwrap().t;
+
+        for status in [
+            None,
+            Some(DnssecStatus::Secure),
+            Some(DnssecStatus::Insecure),
+            Some(DnssecStatus::Bogus),
+            Some(DnssecStatus::Indeterminate),
+        ] {
+            let zone = match status {
+                Some(status) => DnsZone::new().with_dnssec(status),
+                None => DnsZone::new(),
+            };
+            for (name, value) in dns_records.split('\n').filter_map(|r| r.split_once(' ')) {
+                zone.add_txt(name, value);
+            }
+            zone.add_txt("football.example.com", "v=spf1 ip4:192.0.2.1 -all")
+                .add_txt("_dmarc.football.example.com", "v=DMARC1; p=reject");
+            let resolver = Resolver::with_backend(zone, 128).with_clock(signed_at);
+
+            // The status of each TXT record reaches the outputs
+            let dkim = DkimVerifier::verify_dkim(&resolver, &message).await;
+            assert!(dkim
+                .iter()
+                .all(|o| o.result() == &DkimResult::Pass && o.dnssec() == status));
+            let spf = resolver
+                .verify_spf_sender(
+                    "192.0.2.1".parse().unwrap(),
+                    "football.example.com",
+                    "mx.example.org",
+                    "joe@football.example.com",
+                )
+                .await;
+            assert_eq!((spf.result(), spf.dnssec()), (SpfResult::Pass, status));
+            let dmarc = resolver
+                .verify_dmarc(&message, &dkim, "football.example.com", &spf, |d| d)
+                .await;
+            assert_eq!(dmarc.dnssec(), status);
+
+            // And is only reported when validation took place
+            let auth_results = AuthenticationResults::new("mx.example.org")
+                .with_dkim_results(&dkim, "joe@football.example.com")
+                .with_dmarc_result(&dmarc)
+                .to_string();
+            match status {
+                Some(status) => assert!(
+                    auth_results.ends_with(&format!("policy.dmarc=reject (dnssec={status})")),
+                    "{auth_results}"
+                ),
+                None => assert!(!auth_results.contains("dnssec="), "{auth_results}"),
+            }
+        }
+    }
+}
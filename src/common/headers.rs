@@ -8,8 +8,10 @@
  * except according to those terms.
  */
 
-use std::{
+use alloc::{string::String, vec::Vec};
+use core::{
     iter::{Enumerate, Peekable},
+    ops::Range,
     slice::Iter,
 };
 
@@ -23,6 +25,22 @@ impl<'x, T> Header<'x, T> {
     }
 }
 
+impl<'x, T: Clone> Header<'x, &'x T> {
+    pub fn to_owned_header(&self) -> OwnedHeader<T> {
+        OwnedHeader {
+            name: self.name.to_vec(),
+            value: self.value.to_vec(),
+            header: self.header.clone(),
+        }
+    }
+}
+
+impl<T> OwnedHeader<T> {
+    pub fn as_header(&self) -> Header<'_, &T> {
+        Header::new(&self.name, &self.value, &self.header)
+    }
+}
+
 pub trait HeaderStream<'x> {
     fn next_header(&mut self) -> Option<(&'x [u8], &'x [u8])>;
     fn body(&mut self) -> &'x [u8];
@@ -46,6 +64,8 @@ pub(crate) struct HeaderParser<'x> {
     pub num_received: usize,
     pub has_message_id: bool,
     pub has_date: bool,
+    /// Offsets of the name and value of the last header returned.
+    pub offsets: (Range<usize>, Range<usize>),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -59,12 +79,32 @@ pub(crate) enum AuthenticatedHeader<'x> {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Header<'x, T> {
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::common::serialize::base64::serialize")
+    )]
     pub name: &'x [u8],
+    #[cfg_attr(
+        feature = "serde",
+        serde(serialize_with = "crate::common::serialize::base64::serialize")
+    )]
     pub value: &'x [u8],
     pub header: T,
 }
 
+/// Owned counterpart of [`Header`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedHeader<T> {
+    #[cfg_attr(feature = "serde", serde(with = "crate::common::serialize::base64"))]
+    pub name: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::common::serialize::base64"))]
+    pub value: Vec<u8>,
+    pub header: T,
+}
+
 impl<'x> HeaderParser<'x> {
     pub fn new(message: &'x [u8]) -> Self {
         HeaderParser {
@@ -74,6 +114,7 @@ impl<'x> HeaderParser<'x> {
             num_received: 0,
             has_message_id: false,
             has_date: false,
+            offsets: (0..0, 0..0),
         }
     }
 
@@ -91,6 +132,7 @@ impl<'x> HeaderIterator<'x> {
         }
     }
 
+    #[cfg(feature = "std")]
     pub fn seek_start(&mut self) {
         while let Some((_, ch)) = self.iter.peek() {
             if !ch.is_ascii_whitespace() {
@@ -232,6 +274,7 @@ impl<'x> Iterator for HeaderParser<'x> {
                                 .message
                                 .get(self.start_pos..pos + 1)
                                 .unwrap_or_default();
+                            self.offsets = (self.start_pos..pos + 1, pos + 1..pos + 1);
                             self.start_pos = pos + 1;
                             return Some((AuthenticatedHeader::Other(header_name), b""));
                         }
@@ -322,6 +365,7 @@ impl<'x> Iterator for HeaderParser<'x> {
                     _ => AuthenticatedHeader::Other(header_name),
                 };
 
+                self.offsets = (self.start_pos..colon_pos, colon_pos + 1..pos + 1);
                 self.start_pos = pos + 1;
 
                 return Some((header_name, header_value));
@@ -8,9 +8,20 @@
  * except according to those terms.
  */
 
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+#[cfg(feature = "std")]
 use mail_parser::{parsers::MessageStream, Address, HeaderValue};
 
-use crate::{arc, common::crypto::HashAlgorithm, dkim, AuthenticatedMessage};
+use alloc::sync::Arc;
+
+use crate::{
+    arc,
+    common::crypto::{HashAlgorithm, HashContext},
+    dkim::{self, canonicalize::BodyCanonicalizer, Canonicalization},
+    AuthenticatedMessage, AuthenticatedMessageBuilder, OwnedAuthenticatedMessage,
+};
 
 use super::headers::{AuthenticatedHeader, Header, HeaderParser};
 
@@ -20,6 +31,19 @@ impl<'x> AuthenticatedMessage<'x> {
     }
 
     pub fn parse_with_opts(raw_message: &'x [u8], strict: bool) -> Option<Self> {
+        let mut message = Self::parse_headers(raw_message, strict)?;
+        let body = raw_message.get(message.body_offset..).unwrap_or_default();
+
+        // Calculate body hashes
+        for (cb, ha, l, bh) in &mut message.body_hashes {
+            *bh = ha.hash(cb.canonical_body(body, *l)).as_ref().to_vec();
+        }
+
+        message.into()
+    }
+
+    /// Parses the message headers, leaving the body hashes empty.
+    fn parse_headers(raw_message: &'x [u8], strict: bool) -> Option<Self> {
         let mut message = AuthenticatedMessage {
             headers: Vec::new(),
             from: Vec::new(),
@@ -33,12 +57,14 @@ impl<'x> AuthenticatedMessage<'x> {
             received_headers_count: 0,
             date_header_present: false,
             message_id_header_present: false,
+            header_offsets: Vec::new(),
+            body_len: 0,
         };
 
         let mut headers = HeaderParser::new(raw_message);
         let mut has_arc_errors = false;
 
-        for (header, value) in &mut headers {
+        while let Some((header, value)) = headers.next() {
             let name =
                 match header {
                     AuthenticatedHeader::Ds(name) => {
@@ -114,6 +140,9 @@ impl<'x> AuthenticatedMessage<'x> {
                         name
                     }
                     AuthenticatedHeader::From(name) => {
+                        // Address parsing requires mail-parser, without `std`
+                        // the From addresses are not extracted.
+                        #[cfg(feature = "std")]
                         match MessageStream::new(value).parse_address() {
                             HeaderValue::Address(Address::List(list)) => {
                                 message.from.extend(
@@ -138,6 +167,7 @@ impl<'x> AuthenticatedMessage<'x> {
                 };
 
             message.headers.push((name, value));
+            message.header_offsets.push(headers.offsets.clone());
         }
 
         if message.headers.is_empty() {
@@ -155,12 +185,7 @@ impl<'x> AuthenticatedMessage<'x> {
         } else {
             message.body_offset = raw_message.len();
         }
-        let body = raw_message.get(message.body_offset..).unwrap_or_default();
-
-        // Calculate body hashes
-        for (cb, ha, l, bh) in &mut message.body_hashes {
-            *bh = ha.hash(cb.canonical_body(body, *l)).as_ref().to_vec();
-        }
+        message.body_len = raw_message.len().saturating_sub(message.body_offset);
 
         // Sort ARC headers
         if !message.as_headers.is_empty() && !has_arc_errors {
@@ -190,6 +215,12 @@ impl<'x> AuthenticatedMessage<'x> {
         message.into()
     }
 
+    /// Returns a builder that parses the message headers and computes the
+    /// body hashes from body chunks supplied later on.
+    pub fn builder(headers: &'x [u8]) -> Option<AuthenticatedMessageBuilder<'x>> {
+        AuthenticatedMessageBuilder::with_opts(headers, true)
+    }
+
     pub fn received_headers_count(&self) -> usize {
         self.received_headers_count
     }
@@ -229,4 +260,433 @@ impl<'x> AuthenticatedMessage<'x> {
     pub fn from(&self) -> &str {
         self.from.first().map_or("", |f| f.as_str())
     }
+
+    /// Returns the offsets in `raw_message` of the name and value of the
+    /// header at `pos`, unless the header was modified after parsing.
+    pub(crate) fn header_offsets(&self, pos: usize) -> Option<(Range<usize>, Range<usize>)> {
+        let (name, value) = self.headers.get(pos)?;
+        self.header_offsets
+            .get(pos)
+            .filter(|(name_range, value_range)| {
+                self.raw_message.get(name_range.clone()) == Some(*name)
+                    && self.raw_message.get(value_range.clone()) == Some(*value)
+            })
+            .cloned()
+    }
+
+    /// Copies the message into an [`OwnedAuthenticatedMessage`], keeping the
+    /// parsed signatures and body hashes so they are not computed again.
+    ///
+    /// The raw message is copied once, use [`OwnedAuthenticatedMessage::parse`]
+    /// to avoid the copy when the message is already held in an `Arc`.
+    pub fn into_owned(self) -> OwnedAuthenticatedMessage {
+        self.into_shared(None)
+    }
+
+    /// Builds an [`OwnedAuthenticatedMessage`] on top of `shared`, which must
+    /// hold `raw_message`, or on top of a copy of it.
+    fn into_shared(self, shared: Option<Arc<[u8]>>) -> OwnedAuthenticatedMessage {
+        let raw_message = self.raw_message;
+        let mut buffer = Vec::new();
+
+        // Headers are located through the offsets recorded by the parser,
+        // headers modified or added afterwards are copied.
+        let headers = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(pos, (name, value))| {
+                self.header_offsets(pos).unwrap_or_else(|| {
+                    (
+                        copy_range(&mut buffer, raw_message, name),
+                        copy_range(&mut buffer, raw_message, value),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut ranges = |name: &[u8], value: &[u8]| {
+            self.headers
+                .iter()
+                .zip(&headers)
+                .find(|((n, v), _)| *n == name && *v == value)
+                .map(|(_, ranges)| ranges.clone())
+                .unwrap_or_else(|| {
+                    (
+                        copy_range(&mut buffer, raw_message, name),
+                        copy_range(&mut buffer, raw_message, value),
+                    )
+                })
+        };
+
+        let dkim_headers = self
+            .dkim_headers
+            .into_iter()
+            .map(|h| {
+                let (name, value) = ranges(h.name, h.value);
+                (name, value, h.header)
+            })
+            .collect();
+        let ams_headers = self
+            .ams_headers
+            .into_iter()
+            .map(|h| {
+                let (name, value) = ranges(h.name, h.value);
+                (name, value, h.header)
+            })
+            .collect();
+        let as_headers = self
+            .as_headers
+            .into_iter()
+            .map(|h| {
+                let (name, value) = ranges(h.name, h.value);
+                (name, value, h.header)
+            })
+            .collect();
+        let aar_headers = self
+            .aar_headers
+            .into_iter()
+            .map(|h| {
+                let (name, value) = ranges(h.name, h.value);
+                (name, value, h.header)
+            })
+            .collect();
+
+        // Bytes not found in the raw message are appended to a copy of it
+        let buffer = match shared {
+            Some(shared) if buffer.is_empty() => shared,
+            _ if buffer.is_empty() => Arc::from(raw_message),
+            _ => [raw_message, &buffer].concat().into(),
+        };
+
+        OwnedAuthenticatedMessage {
+            buffer,
+            raw_message: 0..raw_message.len(),
+            headers,
+            from: self.from,
+            body_offset: self.body_offset,
+            body_len: self.body_len,
+            body_hashes: self.body_hashes,
+            dkim_headers,
+            ams_headers,
+            as_headers,
+            aar_headers,
+            received_headers_count: self.received_headers_count,
+            date_header_present: self.date_header_present,
+            message_id_header_present: self.message_id_header_present,
+        }
+    }
+}
+
+impl OwnedAuthenticatedMessage {
+    /// Parses a message held in a shared buffer, which is referenced rather
+    /// than copied.
+    pub fn parse(raw_message: Arc<[u8]>) -> Option<Self> {
+        Self::parse_with_opts(raw_message, true)
+    }
+
+    pub fn parse_with_opts(raw_message: Arc<[u8]>, strict: bool) -> Option<Self> {
+        AuthenticatedMessage::parse_with_opts(&raw_message, strict)
+            .map(|message| message.into_shared(Some(raw_message.clone())))
+    }
+
+    /// Returns a borrowed [`AuthenticatedMessage`], as accepted by the
+    /// verifiers. The message bytes are not copied, but `AuthenticatedMessage`
+    /// holds its headers in vectors, so each call allocates them and clones
+    /// the parsed signatures and body hashes, in time linear in the number of
+    /// headers. The view should be kept for as long as it is needed rather
+    /// than requested for each verification.
+    pub fn as_message(&self) -> AuthenticatedMessage<'_> {
+        let bytes = |range: &Range<usize>| &self.buffer[range.clone()];
+
+        AuthenticatedMessage {
+            headers: self
+                .headers
+                .iter()
+                .map(|(name, value)| (bytes(name), bytes(value)))
+                .collect(),
+            from: self.from.clone(),
+            raw_message: bytes(&self.raw_message),
+            body_offset: self.body_offset,
+            body_hashes: self.body_hashes.clone(),
+            dkim_headers: self
+                .dkim_headers
+                .iter()
+                .map(|(name, value, header)| Header::new(bytes(name), bytes(value), header.clone()))
+                .collect(),
+            ams_headers: self
+                .ams_headers
+                .iter()
+                .map(|(name, value, header)| Header::new(bytes(name), bytes(value), header.clone()))
+                .collect(),
+            as_headers: self
+                .as_headers
+                .iter()
+                .map(|(name, value, header)| Header::new(bytes(name), bytes(value), header.clone()))
+                .collect(),
+            aar_headers: self
+                .aar_headers
+                .iter()
+                .map(|(name, value, header)| Header::new(bytes(name), bytes(value), header.clone()))
+                .collect(),
+            received_headers_count: self.received_headers_count,
+            date_header_present: self.date_header_present,
+            message_id_header_present: self.message_id_header_present,
+            header_offsets: self.headers.clone(),
+            body_len: self.body_len,
+        }
+    }
+
+    pub fn raw_message(&self) -> &[u8] {
+        &self.buffer[self.raw_message.clone()]
+    }
+
+    pub fn body_offset(&self) -> usize {
+        self.body_offset
+    }
+
+    pub fn froms(&self) -> &[String] {
+        &self.from
+    }
+
+    pub fn from(&self) -> &str {
+        self.from.first().map_or("", |f| f.as_str())
+    }
+}
+
+/// Appends `bytes` to `buffer`, returning their location once `buffer` is
+/// placed after `raw_message`.
+fn copy_range(buffer: &mut Vec<u8>, raw_message: &[u8], bytes: &[u8]) -> Range<usize> {
+    let start = raw_message.len() + buffer.len();
+    buffer.extend_from_slice(bytes);
+    start..start + bytes.len()
+}
+
+impl<'x> AuthenticatedMessageBuilder<'x> {
+    /// Parses the header section of a message. Any bytes following the empty
+    /// line that ends the headers are treated as the first body chunk.
+    pub fn new(headers: &'x [u8]) -> Option<Self> {
+        Self::with_opts(headers, true)
+    }
+
+    pub fn with_opts(headers: &'x [u8], strict: bool) -> Option<Self> {
+        let mut message = AuthenticatedMessage::parse_headers(headers, strict)?;
+        let body = headers.get(message.body_offset..).unwrap_or_default();
+        message.raw_message = headers.get(..message.body_offset).unwrap_or_default();
+        message.body_len = 0;
+
+        Some(AuthenticatedMessageBuilder {
+            body_hashers: message
+                .body_hashes
+                .iter()
+                .map(|(cb, ha, l, _)| (BodyCanonicalizer::new(*cb, *l), ha.hasher()))
+                .collect(),
+            message,
+            pending_body: body,
+        })
+    }
+
+    /// Also hashes the whole body with the given canonicalization and hash
+    /// algorithm, which `ArcSealer` requires to seal the message since the
+    /// body is not retained. Must be called before `write_body`.
+    pub fn with_body_hash(mut self, cb: Canonicalization, ha: HashAlgorithm) -> Self {
+        debug_assert_eq!(self.message.body_len, 0, "body already written");
+        if !self
+            .message
+            .body_hashes
+            .iter()
+            .any(|(c, h, l, _)| *c == cb && *h == ha && *l == 0)
+        {
+            self.message.body_hashes.push((cb, ha, 0, Vec::new()));
+            self.body_hashers
+                .push((BodyCanonicalizer::new(cb, 0), ha.hasher()));
+        }
+        self
+    }
+
+    /// Feeds a chunk of the message body to every body hasher.
+    pub fn write_body(&mut self, chunk: &[u8]) -> &mut Self {
+        let pending_body = core::mem::take(&mut self.pending_body);
+        for chunk in [pending_body, chunk] {
+            for (canonicalizer, hasher) in &mut self.body_hashers {
+                canonicalizer.write(chunk, hasher);
+            }
+            self.message.body_len += chunk.len();
+        }
+        self
+    }
+
+    /// Completes the body hashes and returns the message. The body itself is
+    /// not retained, `AuthenticatedMessage::raw_body` returns an empty slice.
+    pub fn build(mut self) -> AuthenticatedMessage<'x> {
+        self.write_body(b"");
+        let mut message = self.message;
+        for ((_, _, _, bh), (canonicalizer, mut hasher)) in
+            message.body_hashes.iter_mut().zip(self.body_hashers)
+        {
+            canonicalizer.finish(&mut hasher);
+            *bh = hasher.complete().as_ref().to_vec();
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf, sync::Arc};
+
+    use crate::{
+        dkim::verify::{DkimKeys, DkimVerifier},
+        AuthenticatedMessage, DkimResult, OwnedAuthenticatedMessage, OwnedDkimOutput,
+    };
+
+    #[test]
+    fn streaming_body_hashes() {
+        for dir in ["dkim", "arc"] {
+            let mut test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            test_dir.push("resources");
+            test_dir.push(dir);
+
+            for file_name in fs::read_dir(&test_dir).unwrap() {
+                let file_name = file_name.unwrap().path();
+                let test = String::from_utf8(fs::read(&file_name).unwrap()).unwrap();
+                let raw_message = test
+                    .split_once("\n\n")
+                    .map_or(test.as_str(), |(_, message)| message)
+                    .replace('\n', "\r\n");
+                let raw_message = raw_message.as_bytes();
+                let expected = AuthenticatedMessage::parse(raw_message).unwrap();
+                assert!(!expected.body_hashes.is_empty());
+
+                for (header_len, chunk_size) in [
+                    (expected.body_offset, 1),
+                    (expected.body_offset, 7),
+                    (expected.body_offset + 10, 64),
+                    (raw_message.len(), 1),
+                ] {
+                    let (headers, body) = raw_message.split_at(header_len);
+                    let mut builder = AuthenticatedMessage::builder(headers).unwrap();
+                    for chunk in body.chunks(chunk_size) {
+                        builder.write_body(chunk);
+                    }
+                    let message = builder.build();
+
+                    assert_eq!(
+                        &message.body_hashes[..expected.body_hashes.len()],
+                        expected.body_hashes,
+                        "{}",
+                        file_name.display()
+                    );
+                    for (cb, ha, l, bh) in &message.body_hashes {
+                        assert_eq!(
+                            bh,
+                            ha.hash(cb.canonical_body(expected.raw_body(), *l)).as_ref(),
+                            "{}",
+                            file_name.display()
+                        );
+                    }
+                    assert_eq!(message.raw_headers(), expected.raw_headers());
+                    assert_eq!(message.raw_body(), b"");
+                    assert_eq!(message.headers, expected.headers);
+                    assert_eq!(message.dkim_headers, expected.dkim_headers);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn owned_message() {
+        let mut test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_dir.push("resources");
+        test_dir.push("dkim");
+
+        for file_name in fs::read_dir(&test_dir).unwrap() {
+            let file_name = file_name.unwrap().path();
+            let test = String::from_utf8(fs::read(&file_name).unwrap()).unwrap();
+            let (dns_records, raw_message) = test.split_once("\n\n").unwrap();
+            let raw_message = raw_message.replace('\n', "\r\n");
+
+            let (owned, outputs): (OwnedAuthenticatedMessage, Vec<OwnedDkimOutput>) = {
+                let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
+                let signed_at = message
+                    .dkim_headers
+                    .last()
+                    .and_then(|h| h.header.as_ref().ok())
+                    .map(|s| s.t)
+                    .unwrap();
+                let mut keys = DkimKeys::new().with_clock(signed_at);
+                for (key, value) in dns_records
+                    .split('\n')
+                    .filter_map(|r| r.split_once(' ').map(|(a, b)| (a, b.as_bytes())))
+                {
+                    let (selector, domain) = key.split_once("._domainkey.").unwrap();
+                    keys.add_txt(selector, domain, value);
+                }
+                let outputs = DkimVerifier::verify_dkim_with_keys(&keys, &message)
+                    .into_iter()
+                    .map(|output| output.into_owned())
+                    .collect();
+                let owned = message.clone().into_owned();
+                let borrowed = owned.as_message();
+
+                // Parsed headers are located without copying them
+                assert_eq!(owned.buffer.len(), message.raw_message.len());
+                assert_eq!(
+                    borrowed.clone().into_owned().buffer.len(),
+                    message.raw_message.len()
+                );
+
+                // Shared buffers are referenced rather than copied
+                let shared = Arc::<[u8]>::from(raw_message.as_bytes());
+                let shared_owned = OwnedAuthenticatedMessage::parse(shared.clone()).unwrap();
+                assert!(Arc::ptr_eq(&shared_owned.buffer, &shared));
+                assert!(Arc::ptr_eq(&shared_owned.clone().buffer, &shared));
+                assert_eq!(shared_owned.as_message().headers, message.headers);
+
+                // Headers modified after parsing are copied
+                let mut modified = message.clone();
+                modified.headers.insert(0, (b"X-Added", b" yes\r\n"));
+                modified.headers.swap(1, 2);
+                let modified_owned = modified.clone().into_owned();
+                assert_eq!(modified_owned.as_message().headers, modified.headers);
+                assert_eq!(
+                    modified_owned.as_message().dkim_headers,
+                    modified.dkim_headers
+                );
+
+                assert_eq!(borrowed.raw_message, message.raw_message);
+                assert_eq!(borrowed.headers, message.headers);
+                assert_eq!(borrowed.body_hashes, message.body_hashes);
+                assert_eq!(borrowed.dkim_headers, message.dkim_headers);
+                assert_eq!(borrowed.ams_headers, message.ams_headers);
+                assert_eq!(borrowed.from, message.from);
+                assert_eq!(
+                    DkimVerifier::verify_dkim_with_keys(&keys, &borrowed),
+                    DkimVerifier::verify_dkim_with_keys(&keys, &message)
+                );
+
+                (owned, outputs)
+            };
+            drop(raw_message);
+
+            // Owned values outlive the raw message and can be moved across threads
+            let (owned, outputs) = std::thread::spawn(move || (owned, outputs)).join().unwrap();
+            assert_eq!(
+                outputs.last().unwrap().result(),
+                &DkimResult::Pass,
+                "{}",
+                file_name.display()
+            );
+            assert_eq!(
+                outputs.last().unwrap().as_output().signature(),
+                owned
+                    .as_message()
+                    .dkim_headers
+                    .last()
+                    .unwrap()
+                    .header
+                    .as_ref()
+                    .ok()
+            );
+        }
+    }
 }
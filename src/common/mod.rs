@@ -8,18 +8,33 @@
  * except according to those terms.
  */
 
+#[cfg(feature = "std")]
 use crate::{Error, IprevResult};
 
+#[cfg(feature = "std")]
 pub mod auth_results;
 pub mod base32;
+pub mod base64;
+#[cfg(feature = "std")]
+pub mod blocking;
+pub mod clock;
 pub mod crypto;
+#[cfg(feature = "std")]
+pub mod dns;
 pub mod headers;
+#[cfg(feature = "std")]
 pub mod lru;
 pub mod message;
 pub mod parse;
+#[cfg(feature = "std")]
 pub mod resolver;
+#[cfg(not(feature = "std"))]
+pub mod response_code;
+#[cfg(feature = "serde")]
+pub(crate) mod serialize;
 pub mod verify;
 
+#[cfg(feature = "std")]
 impl From<Error> for IprevResult {
     fn from(err: Error) -> Self {
         if matches!(&err, Error::DnsError(_)) {
@@ -8,9 +8,12 @@
  * except according to those terms.
  */
 
-use std::{borrow::Cow, slice::Iter};
-
-use mail_parser::decoders::quoted_printable::quoted_printable_decode_char;
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::slice::Iter;
 
 pub(crate) const V: u64 = b'v' as u64;
 pub(crate) const A: u64 = b'a' as u64;
@@ -22,6 +25,7 @@ pub(crate) const H: u64 = b'h' as u64;
 pub(crate) const I: u64 = b'i' as u64;
 pub(crate) const K: u64 = b'k' as u64;
 pub(crate) const L: u64 = b'l' as u64;
+#[cfg(feature = "std")]
 pub(crate) const N: u64 = b'n' as u64;
 pub(crate) const O: u64 = b'o' as u64;
 pub(crate) const P: u64 = b'p' as u64;
@@ -400,10 +404,16 @@ impl ItemParser for String {
 impl ItemParser for Cow<'_, str> {
     fn parse(bytes: &[u8]) -> Option<Self> {
         Some(
-            std::str::from_utf8(bytes)
+            core::str::from_utf8(bytes)
                 .unwrap_or_default()
                 .to_string()
                 .into(),
         )
     }
 }
+
+fn quoted_printable_decode_char(hex1: u8, hex2: u8) -> Option<u8> {
+    let hex1 = (hex1 as char).to_digit(16)?;
+    let hex2 = (hex2 as char).to_digit(16)?;
+    Some(((hex1 << 4) | hex2) as u8)
+}
@@ -16,26 +16,29 @@ use std::{
 
 use hickory_resolver::{
     config::{ResolverConfig, ResolverOpts},
-    error::{ResolveError, ResolveErrorKind},
-    proto::rr::RecordType,
+    error::ResolveError,
     system_conf::read_system_conf,
-    AsyncResolver, Name,
 };
 
 use crate::{
-    dkim::{Atps, DomainKeyReport},
+    dkim::{policy::DkimPolicy, Atps, DomainKeyReport},
     dmarc::Dmarc,
     mta_sts::{MtaSts, TlsRpt},
     spf::{Macro, Spf},
-    Error, IpLookupStrategy, Resolver, Txt, MX,
+    DnssecStatus, Error, IpLookupStrategy, Resolver, Txt, MX,
 };
 
 use super::{
+    clock::Clock,
+    dns::{DnsBackend, HickoryBackend},
     lru::{DnsCache, LruCache},
     parse::TxtRecordParser,
     verify::DomainKey,
 };
 
+#[cfg(any(test, feature = "test"))]
+use super::dns::{DnsAnswer, DnsFuture};
+
 impl Resolver {
     pub fn new_cloudflare_tls() -> Result<Self, ResolveError> {
         Self::with_capacity(
@@ -71,14 +74,23 @@ impl Resolver {
         options: ResolverOpts,
         capacity: usize,
     ) -> Result<Self, ResolveError> {
-        Ok(Self {
-            resolver: AsyncResolver::tokio(config, options),
-            cache_txt: LruCache::with_capacity(capacity),
-            cache_mx: LruCache::with_capacity(capacity),
-            cache_ipv4: LruCache::with_capacity(capacity),
-            cache_ipv6: LruCache::with_capacity(capacity),
-            cache_ptr: LruCache::with_capacity(capacity),
-        })
+        Ok(Self::with_backend(
+            HickoryBackend::new(config, options),
+            capacity,
+        ))
+    }
+
+    /// Creates a resolver that validates TXT answers with DNSSEC, their status
+    /// is reported by the DKIM, SPF and DMARC outputs.
+    pub fn with_dnssec(
+        config: ResolverConfig,
+        options: ResolverOpts,
+        capacity: usize,
+    ) -> Result<Self, ResolveError> {
+        Ok(Self::with_backend(
+            HickoryBackend::with_dnssec(config, options),
+            capacity,
+        ))
     }
 
     pub fn with_capacities(
@@ -90,80 +102,110 @@ impl Resolver {
         ipv6_capacity: usize,
         ptr_capacity: usize,
     ) -> Result<Self, ResolveError> {
-        Ok(Self {
-            resolver: AsyncResolver::tokio(config, options),
+        Ok(Self::with_backend_and_capacities(
+            HickoryBackend::new(config, options),
+            txt_capacity,
+            mx_capacity,
+            ipv4_capacity,
+            ipv6_capacity,
+            ptr_capacity,
+        ))
+    }
+
+    /// Creates a resolver that obtains its records from a custom [`DnsBackend`].
+    pub fn with_backend(backend: impl DnsBackend + 'static, capacity: usize) -> Self {
+        Self::with_backend_and_capacities(backend, capacity, capacity, capacity, capacity, capacity)
+    }
+
+    pub fn with_backend_and_capacities(
+        backend: impl DnsBackend + 'static,
+        txt_capacity: usize,
+        mx_capacity: usize,
+        ipv4_capacity: usize,
+        ipv6_capacity: usize,
+        ptr_capacity: usize,
+    ) -> Self {
+        Self {
+            backend: Arc::new(backend),
             cache_txt: LruCache::with_capacity(txt_capacity),
             cache_mx: LruCache::with_capacity(mx_capacity),
             cache_ipv4: LruCache::with_capacity(ipv4_capacity),
             cache_ipv6: LruCache::with_capacity(ipv6_capacity),
             cache_ptr: LruCache::with_capacity(ptr_capacity),
-        })
+            clock: Clock::System,
+            dkim_policy: DkimPolicy::default(),
+        }
+    }
+
+    /// Sets the clock used to check signature expiration and to expand the
+    /// SPF `%{t}` macro.
+    pub fn with_clock(mut self, clock: impl Into<Clock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    /// Sets the policy DKIM signatures, ARC message signatures and ARC seals
+    /// must satisfy to be verified.
+    pub fn with_dkim_policy(mut self, policy: DkimPolicy) -> Self {
+        self.dkim_policy = policy;
+        self
+    }
+
+    pub fn dkim_policy(&self) -> &DkimPolicy {
+        &self.dkim_policy
+    }
+
+    pub fn backend(&self) -> &dyn DnsBackend {
+        self.backend.as_ref()
     }
 
     pub async fn txt_raw_lookup(&self, key: impl IntoFqdn<'_>) -> crate::Result<Vec<u8>> {
-        let mut result = vec![];
-        for record in self
-            .resolver
-            .txt_lookup(Name::from_str_relaxed(key.into_fqdn().as_ref())?)
+        Ok(self
+            .backend
+            .txt_lookup(key.into_fqdn().as_ref())
             .await?
-            .as_lookup()
-            .record_iter()
-        {
-            if let Some(txt_data) = record.data().and_then(|r| r.as_txt()) {
-                for item in txt_data.txt_data() {
-                    result.extend_from_slice(item);
-                }
-            }
-        }
-
-        Ok(result)
+            .records
+            .concat())
     }
 
     pub async fn txt_lookup<'x, T: TxtRecordParser + Into<Txt> + UnwrapTxtRecord>(
         &self,
         key: impl IntoFqdn<'x>,
     ) -> crate::Result<Arc<T>> {
-        let key = key.into_fqdn();
-        if let Some(value) = self.cache_txt.get(key.as_ref()) {
-            return T::unwrap_txt(value);
-        }
+        self.txt_lookup_with_dnssec(key)
+            .await
+            .map(|(record, _)| record)
+    }
 
-        #[cfg(any(test, feature = "test"))]
-        if true {
-            return mock_resolve(key.as_ref());
+    /// Same as [`Resolver::txt_lookup`] but also returns the DNSSEC status of
+    /// the answer, which is `None` unless the backend validates.
+    pub async fn txt_lookup_with_dnssec<'x, T: TxtRecordParser + Into<Txt> + UnwrapTxtRecord>(
+        &self,
+        key: impl IntoFqdn<'x>,
+    ) -> crate::Result<(Arc<T>, Option<DnssecStatus>)> {
+        let key = key.into_fqdn();
+        if let Some((value, dnssec)) = self.cache_txt.get(key.as_ref()) {
+            return T::unwrap_txt(value).map(|record| (record, dnssec));
         }
 
-        let txt_lookup = self
-            .resolver
-            .txt_lookup(Name::from_str_relaxed(key.as_ref())?)
-            .await?;
+        let txt_lookup = self.backend.txt_lookup(key.as_ref()).await?;
         let mut result = Err(Error::InvalidRecordType);
-        let records = txt_lookup.as_lookup().record_iter().filter_map(|r| {
-            let txt_data = r.data()?.as_txt()?.txt_data();
-            match txt_data.len() {
-                1 => Cow::from(txt_data[0].as_ref()).into(),
-                0 => None,
-                _ => {
-                    let mut entry = Vec::with_capacity(255 * txt_data.len());
-                    for data in txt_data {
-                        entry.extend_from_slice(data);
-                    }
-                    Cow::from(entry).into()
-                }
-            }
-        });
-
-        for record in records {
-            result = T::parse(record.as_ref());
+        for record in &txt_lookup.records {
+            result = T::parse(record);
             if result.is_ok() {
                 break;
             }
         }
-        T::unwrap_txt(self.cache_txt.insert(
+        let (value, dnssec) = self.cache_txt.insert(
             key.into_owned(),
-            result.into(),
-            txt_lookup.valid_until(),
-        ))
+            (result.into(), txt_lookup.dnssec),
+            txt_lookup.valid_until,
+        );
+        T::unwrap_txt(value).map(|record| (record, dnssec))
     }
 
     pub async fn mx_lookup<'x>(&self, key: impl IntoFqdn<'x>) -> crate::Result<Arc<Vec<MX>>> {
@@ -172,38 +214,12 @@ impl Resolver {
             return Ok(value);
         }
 
-        #[cfg(any(test, feature = "test"))]
-        if true {
-            return mock_resolve(key.as_ref());
-        }
-
-        let mx_lookup = self
-            .resolver
-            .mx_lookup(Name::from_str_relaxed(key.as_ref())?)
-            .await?;
-        let mx_records = mx_lookup.as_lookup().records();
-        let mut records: Vec<MX> = Vec::with_capacity(mx_records.len());
-        for mx_record in mx_records {
-            if let Some(mx) = mx_record.data().and_then(|r| r.as_mx()) {
-                let preference = mx.preference();
-                let exchange = mx.exchange().to_lowercase().to_string();
-
-                if let Some(record) = records.iter_mut().find(|r| r.preference == preference) {
-                    record.exchanges.push(exchange);
-                } else {
-                    records.push(MX {
-                        exchanges: vec![exchange],
-                        preference,
-                    });
-                }
-            }
-        }
-
-        records.sort_unstable_by(|a, b| a.preference.cmp(&b.preference));
-
-        Ok(self
-            .cache_mx
-            .insert(key.into_owned(), Arc::new(records), mx_lookup.valid_until()))
+        let mx_lookup = self.backend.mx_lookup(key.as_ref()).await?;
+        Ok(self.cache_mx.insert(
+            key.into_owned(),
+            Arc::new(mx_lookup.records),
+            mx_lookup.valid_until,
+        ))
     }
 
     pub async fn ipv4_lookup<'x>(
@@ -215,24 +231,12 @@ impl Resolver {
             return Ok(value);
         }
 
-        #[cfg(any(test, feature = "test"))]
-        if true {
-            return mock_resolve(key.as_ref());
-        }
-
-        let ipv4_lookup = self
-            .resolver
-            .ipv4_lookup(Name::from_str_relaxed(key.as_ref())?)
-            .await?;
-        let ips: Vec<Ipv4Addr> = ipv4_lookup
-            .as_lookup()
-            .record_iter()
-            .filter_map(|r| r.data()?.as_a()?.0.into())
-            .collect::<Vec<_>>();
-
-        Ok(self
-            .cache_ipv4
-            .insert(key.into_owned(), Arc::new(ips), ipv4_lookup.valid_until()))
+        let ipv4_lookup = self.backend.ipv4_lookup(key.as_ref()).await?;
+        Ok(self.cache_ipv4.insert(
+            key.into_owned(),
+            Arc::new(ipv4_lookup.records),
+            ipv4_lookup.valid_until,
+        ))
     }
 
     pub async fn ipv6_lookup<'x>(
@@ -244,24 +248,12 @@ impl Resolver {
             return Ok(value);
         }
 
-        #[cfg(any(test, feature = "test"))]
-        if true {
-            return mock_resolve(key.as_ref());
-        }
-
-        let ipv6_lookup = self
-            .resolver
-            .ipv6_lookup(Name::from_str_relaxed(key.as_ref())?)
-            .await?;
-        let ips = ipv6_lookup
-            .as_lookup()
-            .record_iter()
-            .filter_map(|r| r.data()?.as_aaaa()?.0.into())
-            .collect::<Vec<_>>();
-
-        Ok(self
-            .cache_ipv6
-            .insert(key.into_owned(), Arc::new(ips), ipv6_lookup.valid_until()))
+        let ipv6_lookup = self.backend.ipv6_lookup(key.as_ref()).await?;
+        Ok(self.cache_ipv6.insert(
+            key.into_owned(),
+            Arc::new(ipv6_lookup.records),
+            ipv6_lookup.valid_until,
+        ))
     }
 
     pub async fn ip_lookup(
@@ -308,69 +300,32 @@ impl Resolver {
         }
     }
 
-    pub async fn ptr_lookup<'x>(&self, addr: IpAddr) -> crate::Result<Arc<Vec<String>>> {
+    pub async fn ptr_lookup(&self, addr: IpAddr) -> crate::Result<Arc<Vec<String>>> {
         if let Some(value) = self.cache_ptr.get(&addr) {
             return Ok(value);
         }
 
-        #[cfg(any(test, feature = "test"))]
-        if true {
-            return mock_resolve(&addr.to_string());
-        }
-
-        let ptr_lookup = self.resolver.reverse_lookup(addr).await?;
-        let ptr = ptr_lookup
-            .as_lookup()
-            .record_iter()
-            .filter_map(|r| {
-                let r = r.data()?.as_ptr()?;
-                if !r.is_empty() {
-                    r.to_lowercase().to_string().into()
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-
+        let ptr_lookup = self.backend.ptr_lookup(addr).await?;
         Ok(self
             .cache_ptr
-            .insert(addr, Arc::new(ptr), ptr_lookup.valid_until()))
+            .insert(addr, Arc::new(ptr_lookup.records), ptr_lookup.valid_until))
     }
 
     pub async fn exists<'x>(&self, key: impl IntoFqdn<'x>) -> crate::Result<bool> {
-        #[cfg(any(test, feature = "test"))]
-        if true {
-            let key = key.into_fqdn().into_owned();
-            return match self.ipv4_lookup(key.as_str()).await {
-                Ok(_) => Ok(true),
-                Err(Error::DnsRecordNotFound(_)) => match self.ipv6_lookup(key.as_str()).await {
-                    Ok(_) => Ok(true),
-                    Err(Error::DnsRecordNotFound(_)) => Ok(false),
-                    Err(err) => Err(err),
-                },
-                Err(err) => Err(err),
-            };
-        }
-
         let key = key.into_fqdn();
-        match self
-            .resolver
-            .lookup_ip(Name::from_str_relaxed(key.as_ref())?)
-            .await
+        if self
+            .cache_ipv4
+            .get(key.as_ref())
+            .is_some_and(|ips| !ips.is_empty())
+            || self
+                .cache_ipv6
+                .get(key.as_ref())
+                .is_some_and(|ips| !ips.is_empty())
         {
-            Ok(result) => Ok(result.as_lookup().record_iter().any(|r| {
-                r.data().map_or(false, |d| {
-                    matches!(d.record_type(), RecordType::A | RecordType::AAAA)
-                })
-            })),
-            Err(err) => {
-                if matches!(err.kind(), ResolveErrorKind::NoRecordsFound { .. }) {
-                    Ok(false)
-                } else {
-                    Err(err.into())
-                }
-            }
+            return Ok(true);
         }
+
+        self.backend.exists(key.as_ref()).await
     }
 
     #[cfg(any(test, feature = "test"))]
@@ -380,8 +335,11 @@ impl Resolver {
         value: impl Into<Txt>,
         valid_until: std::time::Instant,
     ) {
-        self.cache_txt
-            .insert(name.into_fqdn().into_owned(), value.into(), valid_until);
+        self.cache_txt.insert(
+            name.into_fqdn().into_owned(),
+            (value.into(), None),
+            valid_until,
+        );
     }
 
     #[cfg(any(test, feature = "test"))]
@@ -423,17 +381,6 @@ impl Resolver {
     }
 }
 
-impl From<ResolveError> for Error {
-    fn from(err: ResolveError) -> Self {
-        match err.kind() {
-            ResolveErrorKind::NoRecordsFound { response_code, .. } => {
-                Error::DnsRecordNotFound(*response_code)
-            }
-            _ => Error::DnsError(err.to_string()),
-        }
-    }
-}
-
 impl From<DomainKey> for Txt {
     fn from(v: DomainKey) -> Self {
         Txt::DomainKey(v.into())
@@ -644,17 +591,59 @@ impl ToReverseName for IpAddr {
     }
 }
 
+/// [`DnsBackend`] used by the test suite that never returns records. Lookups
+/// fail with `ParseError`, `InvalidRecordType` or `DnsError` when the name
+/// contains `_parse_error.`, `_invalid_record.` or `_dns_error.` respectively,
+/// and with `DnsRecordNotFound` otherwise.
+#[cfg(any(test, feature = "test"))]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MockBackend;
+
+#[cfg(any(test, feature = "test"))]
+impl MockBackend {
+    fn resolve<T>(domain: &str) -> crate::Result<T> {
+        Err(if domain.contains("_parse_error.") {
+            Error::ParseError
+        } else if domain.contains("_invalid_record.") {
+            Error::InvalidRecordType
+        } else if domain.contains("_dns_error.") {
+            Error::DnsError("".to_string())
+        } else {
+            Error::DnsRecordNotFound(hickory_resolver::proto::op::ResponseCode::NXDomain)
+        })
+    }
+}
+
 #[cfg(any(test, feature = "test"))]
-pub fn mock_resolve<T>(domain: &str) -> crate::Result<T> {
-    Err(if domain.contains("_parse_error.") {
-        Error::ParseError
-    } else if domain.contains("_invalid_record.") {
-        Error::InvalidRecordType
-    } else if domain.contains("_dns_error.") {
-        Error::DnsError("".to_string())
-    } else {
-        Error::DnsRecordNotFound(hickory_resolver::proto::op::ResponseCode::NXDomain)
-    })
+impl DnsBackend for MockBackend {
+    fn txt_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Vec<u8>>>> {
+        Box::pin(async move { Self::resolve(name) })
+    }
+
+    fn mx_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<MX>>> {
+        Box::pin(async move { Self::resolve(name) })
+    }
+
+    fn ipv4_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv4Addr>>> {
+        Box::pin(async move { Self::resolve(name) })
+    }
+
+    fn ipv6_lookup<'x>(&'x self, name: &'x str) -> DnsFuture<'x, DnsAnswer<Vec<Ipv6Addr>>> {
+        Box::pin(async move { Self::resolve(name) })
+    }
+
+    fn ptr_lookup(&self, addr: IpAddr) -> DnsFuture<'_, DnsAnswer<Vec<String>>> {
+        Box::pin(async move { Self::resolve(&addr.to_string()) })
+    }
+}
+
+#[cfg(any(test, feature = "test"))]
+impl Resolver {
+    /// Creates a resolver backed by [`MockBackend`], records are added to its
+    /// caches with `txt_add`, `mx_add`, `ipv4_add`, `ipv6_add` and `ptr_add`.
+    pub fn new_mock() -> Self {
+        Self::with_backend(MockBackend, 128)
+    }
 }
 
 #[cfg(test)]
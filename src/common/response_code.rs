@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use core::fmt::Display;
+
+/// DNS response code, used in place of `hickory_resolver::proto::op::ResponseCode`
+/// when the `std` feature is disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum ResponseCode {
+    NoError,
+    FormErr,
+    ServFail,
+    NXDomain,
+    NotImp,
+    Refused,
+    YXDomain,
+    YXRRSet,
+    NXRRSet,
+    NotAuth,
+    NotZone,
+    Unknown(u16),
+}
+
+impl ResponseCode {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            ResponseCode::NoError => "No Error",
+            ResponseCode::FormErr => "Form Error",
+            ResponseCode::ServFail => "Server Failure",
+            ResponseCode::NXDomain => "Non-Existent Domain",
+            ResponseCode::NotImp => "Not Implemented",
+            ResponseCode::Refused => "Query Refused",
+            ResponseCode::YXDomain => "Name Exists when it should not",
+            ResponseCode::YXRRSet => "RR Set Exists when it should not",
+            ResponseCode::NXRRSet => "RR Set that should exist does not",
+            ResponseCode::NotAuth => "Server Not Authoritative for zone",
+            ResponseCode::NotZone => "Name not contained in zone",
+            ResponseCode::Unknown(_) => "Unknown response code",
+        }
+    }
+}
+
+impl From<u16> for ResponseCode {
+    fn from(value: u16) -> Self {
+        match value {
+            0 => ResponseCode::NoError,
+            1 => ResponseCode::FormErr,
+            2 => ResponseCode::ServFail,
+            3 => ResponseCode::NXDomain,
+            4 => ResponseCode::NotImp,
+            5 => ResponseCode::Refused,
+            6 => ResponseCode::YXDomain,
+            7 => ResponseCode::YXRRSet,
+            8 => ResponseCode::NXRRSet,
+            9 => ResponseCode::NotAuth,
+            10 => ResponseCode::NotZone,
+            code => ResponseCode::Unknown(code),
+        }
+    }
+}
+
+impl From<ResponseCode> for u16 {
+    fn from(code: ResponseCode) -> Self {
+        match code {
+            ResponseCode::NoError => 0,
+            ResponseCode::FormErr => 1,
+            ResponseCode::ServFail => 2,
+            ResponseCode::NXDomain => 3,
+            ResponseCode::NotImp => 4,
+            ResponseCode::Refused => 5,
+            ResponseCode::YXDomain => 6,
+            ResponseCode::YXRRSet => 7,
+            ResponseCode::NXRRSet => 8,
+            ResponseCode::NotAuth => 9,
+            ResponseCode::NotZone => 10,
+            ResponseCode::Unknown(code) => code,
+        }
+    }
+}
+
+impl Display for ResponseCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
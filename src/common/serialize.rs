@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+// Field encodings used by the `serde` feature.
+
+/// Encodes byte strings (signatures, body hashes and raw headers) as base64.
+pub(crate) mod base64 {
+    use alloc::{string::String, vec::Vec};
+
+    use serde::{de, ser, Deserialize, Deserializer, Serializer};
+
+    use crate::common::base64::{base64_decode, base64_encode};
+
+    pub(crate) fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        let encoded = base64_encode(bytes.as_ref());
+        serializer.serialize_str(core::str::from_utf8(&encoded).map_err(ser::Error::custom)?)
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        if encoded.is_empty() {
+            Ok(Vec::new())
+        } else {
+            base64_decode(encoded.as_bytes())
+                .ok_or_else(|| de::Error::custom("invalid base64 string"))
+        }
+    }
+}
+
+/// Encodes DNS response codes as their numeric value (e.g. `3` for NXDOMAIN).
+pub(crate) mod response_code {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::ResponseCode;
+
+    pub(crate) fn serialize<S>(code: &ResponseCode, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u16(u16::from(*code))
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<ResponseCode, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u16::deserialize(deserializer).map(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf, sync::Arc};
+
+    use hickory_resolver::proto::op::ResponseCode;
+
+    use crate::{
+        common::parse::TxtRecordParser,
+        dkim::verify::{DkimKeys, DkimVerifier},
+        dmarc::{Dmarc, Policy},
+        AuthenticatedMessage, DmarcOutput, DmarcResult, Error, IprevOutput, IprevResult,
+        OwnedDkimOutput, SpfOutput, SpfResult,
+    };
+
+    #[test]
+    fn serialize_outputs() {
+        // DKIM outputs serialize from the borrowed form and deserialize into the owned one
+        let mut test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_dir.push("resources");
+        test_dir.push("dkim");
+
+        for file_name in fs::read_dir(&test_dir).unwrap() {
+            let file_name = file_name.unwrap().path();
+            let test = String::from_utf8(fs::read(&file_name).unwrap()).unwrap();
+            let (dns_records, raw_message) = test.split_once("\n\n").unwrap();
+            let raw_message = raw_message.replace('\n', "\r\n");
+            let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
+            let mut keys = DkimKeys::new();
+            for (key, value) in dns_records
+                .split('\n')
+                .filter_map(|r| r.split_once(' ').map(|(a, b)| (a, b.as_bytes())))
+            {
+                let (selector, domain) = key.split_once("._domainkey.").unwrap();
+                keys.add_txt(selector, domain, value);
+            }
+
+            for output in DkimVerifier::verify_dkim_with_keys(&keys, &message) {
+                let json = serde_json::to_string(&output).unwrap();
+                let owned: OwnedDkimOutput = serde_json::from_str(&json).unwrap();
+                assert_eq!(owned, output.into_owned(), "{}", file_name.display());
+                assert_eq!(serde_json::to_string(&owned).unwrap(), json);
+            }
+        }
+
+        let spf = SpfOutput::new("example.org".to_string())
+            .with_result(SpfResult::SoftFail)
+            .with_explanation("Not authorized".to_string());
+        let json = serde_json::to_string(&spf).unwrap();
+        assert_eq!(serde_json::from_str::<SpfOutput>(&json).unwrap(), spf);
+
+        let mut dmarc = DmarcOutput::default()
+            .with_domain("example.org")
+            .with_spf_result(DmarcResult::Pass)
+            .with_dkim_result(DmarcResult::Fail(Error::NotAligned));
+        dmarc.policy = Policy::Reject;
+        dmarc.record = Some(Arc::new(
+            Dmarc::parse(b"v=DMARC1; p=reject; rua=mailto:dmarc@example.org").unwrap(),
+        ));
+        let json = serde_json::to_string(&dmarc).unwrap();
+        assert_eq!(serde_json::from_str::<DmarcOutput>(&json).unwrap(), dmarc);
+
+        let iprev = IprevOutput {
+            result: IprevResult::PermError(Error::DnsRecordNotFound(ResponseCode::NXDomain)),
+            ptr: Some(Arc::new(vec!["mx.example.org.".to_string()])),
+        };
+        let json = serde_json::to_string(&iprev).unwrap();
+        assert_eq!(
+            json,
+            r#"{"result":{"PermError":{"DnsRecordNotFound":3}},"ptr":["mx.example.org."]}"#
+        );
+        assert_eq!(serde_json::from_str::<IprevOutput>(&json).unwrap(), iprev);
+    }
+}
@@ -8,17 +8,25 @@
  * except according to those terms.
  */
 
+use alloc::{boxed::Box, string::String, vec::Vec};
+#[cfg(feature = "std")]
 use std::net::IpAddr;
 
-use crate::{dkim::Canonicalization, Error, IprevOutput, IprevResult, Resolver};
+use crate::dkim::Canonicalization;
+#[cfg(feature = "std")]
+use crate::{Error, IprevOutput, IprevResult, Resolver};
 
-use super::crypto::{Algorithm, VerifyingKey};
+use super::crypto::{Algorithm, VerifyingKey, VerifyingKeyType};
 
 pub struct DomainKey {
     pub p: Box<dyn VerifyingKey + Send + Sync>,
     pub f: u64,
+    /// Public key bytes as published in the `p=` tag.
+    pub public_key: Vec<u8>,
+    pub(crate) k: VerifyingKeyType,
 }
 
+#[cfg(feature = "std")]
 impl Resolver {
     pub async fn verify_iprev(&self, addr: IpAddr) -> IprevOutput {
         match self.ptr_lookup(addr).await {
@@ -72,6 +80,7 @@ impl Resolver {
     }
 }
 
+#[cfg(feature = "std")]
 impl IprevOutput {
     pub fn result(&self) -> &IprevResult {
         &self.result
@@ -79,6 +88,11 @@ impl IprevOutput {
 }
 
 impl DomainKey {
+    /// Returns the key type as published in the `k=` tag, `rsa` or `ed25519`.
+    pub fn key_type(&self) -> &'static str {
+        self.k.as_str()
+    }
+
     pub(crate) fn verify<'a>(
         &self,
         headers: &mut dyn Iterator<Item = (&'a [u8], &'a [u8])>,
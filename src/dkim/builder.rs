@@ -8,7 +8,12 @@
  * except according to those terms.
  */
 
-use crate::common::crypto::{HashAlgorithm, SigningKey};
+use alloc::string::String;
+
+use crate::common::{
+    clock::Clock,
+    crypto::{AsyncSigningKey, HashAlgorithm, SigningKey},
+};
 
 use super::{Canonicalization, DkimSigner, Done, NeedDomain, NeedHeaders, NeedSelector, Signature};
 
@@ -22,11 +27,29 @@ impl<T: SigningKey> DkimSigner<T> {
                 ..Default::default()
             },
             key,
+            clock: Clock::signing(),
+        }
+    }
+}
+
+impl<T: AsyncSigningKey> DkimSigner<T> {
+    /// Creates a signer for a key that signs asynchronously, such as a key
+    /// held by an HSM. Messages are signed with [`DkimSigner::sign_async`].
+    pub fn from_async_key(key: T) -> DkimSigner<T, NeedDomain> {
+        DkimSigner {
+            _state: Default::default(),
+            template: Signature {
+                v: 1,
+                a: key.algorithm(),
+                ..Default::default()
+            },
+            key,
+            clock: Clock::signing(),
         }
     }
 }
 
-impl<T: SigningKey> DkimSigner<T, NeedDomain> {
+impl<T> DkimSigner<T, NeedDomain> {
     /// Sets the domain to use for signing.
     pub fn domain(mut self, domain: impl Into<String>) -> DkimSigner<T, NeedSelector> {
         self.template.d = domain.into();
@@ -34,11 +57,12 @@ impl<T: SigningKey> DkimSigner<T, NeedDomain> {
             _state: Default::default(),
             key: self.key,
             template: self.template,
+            clock: self.clock,
         }
     }
 }
 
-impl<T: SigningKey> DkimSigner<T, NeedSelector> {
+impl<T> DkimSigner<T, NeedSelector> {
     /// Sets the selector to use for signing.
     pub fn selector(mut self, selector: impl Into<String>) -> DkimSigner<T, NeedHeaders> {
         self.template.s = selector.into();
@@ -46,11 +70,12 @@ impl<T: SigningKey> DkimSigner<T, NeedSelector> {
             _state: Default::default(),
             key: self.key,
             template: self.template,
+            clock: self.clock,
         }
     }
 }
 
-impl<T: SigningKey> DkimSigner<T, NeedHeaders> {
+impl<T> DkimSigner<T, NeedHeaders> {
     /// Sets the headers to sign.
     pub fn headers(
         mut self,
@@ -61,11 +86,19 @@ impl<T: SigningKey> DkimSigner<T, NeedHeaders> {
             _state: Default::default(),
             key: self.key,
             template: self.template,
+            clock: self.clock,
         }
     }
 }
 
-impl<T: SigningKey> DkimSigner<T, Done> {
+impl<T> DkimSigner<T, Done> {
+    /// Sets the clock used to obtain the signature timestamp. Without the
+    /// `std` feature it has to be set, otherwise signatures carry `t=0`.
+    pub fn clock(mut self, clock: impl Into<Clock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
     /// Sets the third party signature.
     pub fn atps(mut self, atps: impl Into<String>) -> Self {
         self.template.atps = Some(atps.into());
@@ -8,6 +8,12 @@
  * except according to those terms.
  */
 
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
 use crate::common::headers::{HeaderStream, Writable, Writer};
 
 use super::{Canonicalization, Signature};
@@ -19,69 +25,102 @@ pub struct CanonicalBody<'a> {
 
 impl Writable for CanonicalBody<'_> {
     fn write(self, hasher: &mut impl Writer) {
-        let mut crlf_seq = 0;
+        let mut canonicalizer = BodyCanonicalizer::new(self.canonicalization, 0);
+        canonicalizer.write(self.body, hasher);
+        canonicalizer.finish(hasher);
+    }
+}
+
+/// Incremental body canonicalization, used when the body is received in chunks.
+#[derive(Debug, Clone)]
+pub struct BodyCanonicalizer {
+    canonicalization: Canonicalization,
+    crlf_seq: usize,
+    last_ch: u8,
+    is_empty: bool,
+    remaining: Option<u64>,
+}
+
+impl BodyCanonicalizer {
+    /// Creates a canonicalizer that reads at most `l` bytes of the raw body,
+    /// or the entire body if `l` is zero.
+    pub fn new(canonicalization: Canonicalization, l: u64) -> Self {
+        BodyCanonicalizer {
+            canonicalization,
+            crlf_seq: 0,
+            last_ch: 0,
+            is_empty: true,
+            remaining: if l > 0 { Some(l) } else { None },
+        }
+    }
+
+    pub fn write(&mut self, chunk: &[u8], hasher: &mut impl Writer) {
+        let chunk = match &mut self.remaining {
+            Some(remaining) => {
+                let len = core::cmp::min(*remaining, chunk.len() as u64);
+                *remaining -= len;
+                &chunk[..len as usize]
+            }
+            None => chunk,
+        };
 
         match self.canonicalization {
             Canonicalization::Relaxed => {
-                let mut last_ch = 0;
-                let mut is_empty = true;
-
-                for &ch in self.body {
+                for &ch in chunk {
                     match ch {
                         b' ' | b'\t' => {
-                            while crlf_seq > 0 {
-                                hasher.write(b"\r\n");
-                                crlf_seq -= 1;
-                            }
-                            is_empty = false;
+                            self.write_crlf(hasher);
+                            self.is_empty = false;
                         }
                         b'\n' => {
-                            crlf_seq += 1;
+                            self.crlf_seq += 1;
                         }
                         b'\r' => {}
                         _ => {
-                            while crlf_seq > 0 {
-                                hasher.write(b"\r\n");
-                                crlf_seq -= 1;
-                            }
+                            self.write_crlf(hasher);
 
-                            if last_ch == b' ' || last_ch == b'\t' {
+                            if self.last_ch == b' ' || self.last_ch == b'\t' {
                                 hasher.write(b" ");
                             }
 
                             hasher.write(&[ch]);
-                            is_empty = false;
+                            self.is_empty = false;
                         }
                     }
 
-                    last_ch = ch;
-                }
-
-                if !is_empty {
-                    hasher.write(b"\r\n");
+                    self.last_ch = ch;
                 }
             }
             Canonicalization::Simple => {
-                for &ch in self.body {
+                for &ch in chunk {
                     match ch {
                         b'\n' => {
-                            crlf_seq += 1;
+                            self.crlf_seq += 1;
                         }
                         b'\r' => {}
                         _ => {
-                            while crlf_seq > 0 {
-                                hasher.write(b"\r\n");
-                                crlf_seq -= 1;
-                            }
+                            self.write_crlf(hasher);
                             hasher.write(&[ch]);
                         }
                     }
                 }
-
-                hasher.write(b"\r\n");
             }
         }
     }
+
+    pub fn finish(self, hasher: &mut impl Writer) {
+        if self.canonicalization == Canonicalization::Simple || !self.is_empty {
+            hasher.write(b"\r\n");
+        }
+    }
+
+    #[inline(always)]
+    fn write_crlf(&mut self, hasher: &mut impl Writer) {
+        while self.crlf_seq > 0 {
+            hasher.write(b"\r\n");
+            self.crlf_seq -= 1;
+        }
+    }
 }
 
 impl Canonicalization {
@@ -144,7 +183,7 @@ impl Canonicalization {
             body: if l == 0 || body.is_empty() {
                 body
             } else {
-                &body[..std::cmp::min(l as usize, body.len())]
+                &body[..core::cmp::min(l as usize, body.len())]
             },
         }
     }
@@ -174,14 +213,14 @@ impl Signature {
             {
                 headers.push((name, value));
                 found_headers[pos] = true;
-                signed_headers.push(std::str::from_utf8(name).unwrap().into());
+                signed_headers.push(core::str::from_utf8(name).unwrap().into());
             }
         }
 
         let body = message.body();
         let body_len = body.len();
         let canonical_headers = self.ch.canonical_headers(headers);
-        let canonical_body = self.ch.canonical_body(body, u64::MAX);
+        let canonical_body = self.cb.canonical_body(body, u64::MAX);
 
         // Add any missing headers
         signed_headers.reverse();
@@ -214,10 +253,14 @@ mod test {
     use super::{CanonicalBody, CanonicalHeaders};
     use crate::{
         common::{
-            crypto::{HashImpl, Sha256},
-            headers::{HeaderIterator, Writable},
+            crypto::{Ed25519Key, HashImpl, Sha256},
+            headers::{HeaderIterator, HeaderWriter, Writable},
         },
-        dkim::Canonicalization,
+        dkim::{
+            verify::{DkimKeys, DkimVerifier},
+            Canonicalization, DkimSigner, Signature,
+        },
+        AuthenticatedMessage, DkimResult,
     };
 
     #[test]
@@ -249,30 +292,30 @@ mod test {
                 ),
                 (
                     concat!("from:John doe <jdoe@domain.com>\r\n", "subject:test\r\n"),
-                    concat!(" body\r\n"),
+                    " body\r\n",
                 ),
                 (
                     concat!(
                         "  From : John\tdoe <jdoe@domain.com>\t\r\n",
                         "SUB JECT:\ttest  \t  \r\n"
                     ),
-                    concat!(" body \t   \r\n"),
+                    " body \t   \r\n",
                 ),
             ),
             (
-                concat!("H: value\t\r\n\r\n",),
-                (concat!("h:value\r\n"), concat!("")),
-                (concat!("H: value\t\r\n"), concat!("\r\n")),
+                "H: value\t\r\n\r\n",
+                ("h:value\r\n", ""),
+                ("H: value\t\r\n", "\r\n"),
             ),
             (
-                concat!("\tx\t: \t\t\tz\r\n\r\nabc",),
-                (concat!("x:z\r\n"), concat!("abc\r\n")),
-                ("\tx\t: \t\t\tz\r\n", concat!("abc\r\n")),
+                "\tx\t: \t\t\tz\r\n\r\nabc",
+                ("x:z\r\n", "abc\r\n"),
+                ("\tx\t: \t\t\tz\r\n", "abc\r\n"),
             ),
             (
-                concat!("Subject: hello\r\n\r\n\r\n",),
-                (concat!("subject:hello\r\n"), ""),
-                ("Subject: hello\r\n", concat!("\r\n")),
+                "Subject: hello\r\n\r\n\r\n",
+                ("subject:hello\r\n", ""),
+                ("Subject: hello\r\n", "\r\n"),
             ),
         ] {
             let mut header_iterator = HeaderIterator::new(message.as_bytes());
@@ -341,4 +384,64 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn dkim_canonicalize_mixed() {
+        let message = concat!(
+            "From: bill@example.com\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject:  TPS   Report\r\n",
+            "\r\n",
+            "I'm going to need \t those TPS reports ASAP.  \r\n",
+            "\r\n",
+        );
+        let mut keys = DkimKeys::new();
+        keys.add_txt(
+            "default",
+            "example.com",
+            b"v=DKIM1; k=ed25519; p=11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=",
+        );
+
+        for (ch, cb) in [
+            (Canonicalization::Relaxed, Canonicalization::Simple),
+            (Canonicalization::Simple, Canonicalization::Relaxed),
+        ] {
+            // The body is canonicalized with the body algorithm
+            let (_, _, _, canonical_body) = Signature {
+                h: vec!["From".into()],
+                ch,
+                cb,
+                ..Default::default()
+            }
+            .canonicalize(HeaderIterator::new(message.as_bytes()));
+            let mut body = Vec::new();
+            canonical_body.write(&mut body);
+            let mut expected = Vec::new();
+            cb.canonical_body(
+                message.split_once("\r\n\r\n").unwrap().1.as_bytes(),
+                u64::MAX,
+            )
+            .write(&mut expected);
+            assert_eq!(body, expected, "c={ch:?}/{cb:?}");
+
+            let signature = DkimSigner::from_key(
+                Ed25519Key::from_private_key(include_bytes!(
+                    "../../resources/keys/ed25519-pkcs8.pem"
+                ))
+                .unwrap(),
+            )
+            .domain("example.com")
+            .selector("default")
+            .headers(["From", "To", "Subject"])
+            .header_canonicalization(ch)
+            .body_canonicalization(cb)
+            .sign(message.as_bytes())
+            .unwrap();
+            let signed_message = signature.to_header() + message;
+            let message = AuthenticatedMessage::parse(signed_message.as_bytes()).unwrap();
+            let results = DkimVerifier::verify_dkim_with_keys(&keys, &message);
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].result(), &DkimResult::Pass, "c={ch:?}/{cb:?}");
+        }
+    }
 }
@@ -8,7 +8,7 @@
  * except according to those terms.
  */
 
-use mail_builder::encoders::base64::base64_encode;
+use crate::common::base64::base64_encode;
 use rsa::{
     pkcs1::{EncodeRsaPrivateKey, EncodeRsaPublicKey},
     RsaPrivateKey, RsaPublicKey,
@@ -68,7 +68,7 @@ impl DkimKeyPair {
     }
 
     pub fn encoded_public_key(&self) -> String {
-        String::from_utf8(base64_encode(&self.public_key).unwrap_or_default()).unwrap_or_default()
+        String::from_utf8(base64_encode(&self.public_key)).unwrap_or_default()
     }
 }
 
@@ -99,7 +99,7 @@ mod test {
         let pk_rsa = RsaKey::<Sha256>::from_der(&rsa_pkcs.private_key).unwrap();
 
         // Create resolver
-        let resolver = Resolver::new_system_conf().unwrap();
+        let resolver = Resolver::new_mock();
         #[cfg(any(test, feature = "test"))]
         {
             resolver.txt_add(
@@ -8,7 +8,12 @@
  * except according to those terms.
  */
 
-use std::fmt::{Display, Formatter};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{Display, Formatter};
 
 use crate::common::headers::{HeaderWriter, Writer};
 
@@ -138,7 +143,7 @@ impl HeaderWriter for Signature {
 }
 
 impl Display for Signature {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         let mut buf = Vec::new();
         self.write(&mut buf, false);
         f.write_str(&String::from_utf8_lossy(&buf))
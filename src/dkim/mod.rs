@@ -8,13 +8,16 @@
  * except according to those terms.
  */
 
+use alloc::{boxed::Box, string::String, vec::Vec};
+
 use crate::{
     arc::Set,
     common::{
-        crypto::{Algorithm, HashAlgorithm, SigningKey},
+        clock::Clock,
+        crypto::{Algorithm, HashAlgorithm},
         verify::VerifySignature,
     },
-    ArcOutput, DkimOutput, DkimResult, Error, Version,
+    ArcOutput, DkimOutput, DkimResult, DnssecStatus, Error, OwnedDkimOutput, Version,
 };
 
 pub mod builder;
@@ -22,22 +25,38 @@ pub mod canonicalize;
 #[cfg(feature = "generate")]
 pub mod generate;
 pub mod headers;
+pub mod multi;
 pub mod parse;
+pub mod policy;
+#[cfg(feature = "generate")]
+pub mod rotate;
+pub mod serialize;
 pub mod sign;
+pub mod trace;
 pub mod verify;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Canonicalization {
     #[default]
     Relaxed,
     Simple,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Default)]
-pub struct DkimSigner<T: SigningKey, State = NeedDomain> {
-    _state: std::marker::PhantomData<State>,
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "std", derive(Default))]
+pub struct DkimSigner<T, State = NeedDomain> {
+    _state: core::marker::PhantomData<State>,
     pub key: T,
     pub template: Signature,
+    pub clock: Clock,
+}
+
+/// Signs a message with several [`DkimSigner`]s, canonicalizing and hashing
+/// the message only once.
+#[derive(Default)]
+pub struct DkimMultiSigner {
+    signers: Vec<Box<dyn multi::DynDkimSigner + Send + Sync>>,
 }
 
 pub struct NeedDomain;
@@ -46,12 +65,15 @@ pub struct NeedHeaders;
 pub struct Done;
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Signature {
     pub v: u32,
     pub a: Algorithm,
     pub d: String,
     pub s: String,
+    #[cfg_attr(feature = "serde", serde(with = "crate::common::serialize::base64"))]
     pub b: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::common::serialize::base64"))]
     pub bh: Vec<u8>,
     pub h: Vec<String>,
     pub z: Vec<String>,
@@ -165,6 +187,7 @@ impl<'x> DkimOutput<'x> {
             signature: None,
             report: None,
             is_atps: false,
+            dnssec: None,
         }
     }
 
@@ -174,6 +197,7 @@ impl<'x> DkimOutput<'x> {
             signature: None,
             report: None,
             is_atps: false,
+            dnssec: None,
         }
     }
 
@@ -183,6 +207,7 @@ impl<'x> DkimOutput<'x> {
             signature: None,
             report: None,
             is_atps: false,
+            dnssec: None,
         }
     }
 
@@ -192,6 +217,17 @@ impl<'x> DkimOutput<'x> {
             signature: None,
             report: None,
             is_atps: false,
+            dnssec: None,
+        }
+    }
+
+    pub(crate) fn policy(err: Error) -> Self {
+        DkimOutput {
+            result: DkimResult::Policy(err),
+            signature: None,
+            report: None,
+            is_atps: false,
+            dnssec: None,
         }
     }
 
@@ -201,6 +237,7 @@ impl<'x> DkimOutput<'x> {
             signature: None,
             report: None,
             is_atps: false,
+            dnssec: None,
         }
     }
 
@@ -217,11 +254,18 @@ impl<'x> DkimOutput<'x> {
         self
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn with_atps(mut self) -> Self {
         self.is_atps = true;
         self
     }
 
+    #[cfg(feature = "std")]
+    pub(crate) fn with_dnssec(mut self, dnssec: Option<DnssecStatus>) -> Self {
+        self.dnssec = dnssec;
+        self
+    }
+
     pub fn result(&self) -> &DkimResult {
         &self.result
     }
@@ -233,6 +277,54 @@ impl<'x> DkimOutput<'x> {
     pub fn failure_report_addr(&self) -> Option<&str> {
         self.report.as_deref()
     }
+
+    /// Returns the DNSSEC status of the key record, `None` if it was not
+    /// validated.
+    pub fn dnssec(&self) -> Option<DnssecStatus> {
+        self.dnssec
+    }
+
+    /// Copies the signature into an [`OwnedDkimOutput`] that no longer borrows
+    /// from the message.
+    pub fn into_owned(self) -> OwnedDkimOutput {
+        OwnedDkimOutput {
+            result: self.result,
+            signature: self.signature.cloned(),
+            report: self.report,
+            is_atps: self.is_atps,
+            dnssec: self.dnssec,
+        }
+    }
+}
+
+impl OwnedDkimOutput {
+    pub fn result(&self) -> &DkimResult {
+        &self.result
+    }
+
+    pub fn signature(&self) -> Option<&Signature> {
+        self.signature.as_ref()
+    }
+
+    pub fn failure_report_addr(&self) -> Option<&str> {
+        self.report.as_deref()
+    }
+
+    pub fn dnssec(&self) -> Option<DnssecStatus> {
+        self.dnssec
+    }
+
+    /// Returns a borrowed [`DkimOutput`], as accepted by the DMARC verifier
+    /// and the Authentication-Results builder.
+    pub fn as_output(&self) -> DkimOutput<'_> {
+        DkimOutput {
+            result: self.result.clone(),
+            signature: self.signature.as_ref(),
+            report: self.report.clone(),
+            is_atps: self.is_atps,
+            dnssec: self.dnssec,
+        }
+    }
 }
 
 impl<'x> ArcOutput<'x> {
@@ -240,7 +332,7 @@ impl<'x> ArcOutput<'x> {
         &self.result
     }
 
-    pub fn sets(&self) -> &[Set] {
+    pub fn sets(&self) -> &[Set<'_>] {
         &self.set
     }
 }
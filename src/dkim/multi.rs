@@ -0,0 +1,336 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
+use core::iter;
+
+use super::{Canonicalization, DkimMultiSigner, DkimSigner, Done, Signature};
+
+use crate::{
+    common::{
+        base64::base64_encode,
+        crypto::{HashAlgorithm, SigningKey},
+        headers::{ChainedHeaderIterator, HeaderIterator, HeaderStream},
+    },
+    Error,
+};
+
+/// Object-safe view of a [`DkimSigner`] that is ready to sign.
+pub(crate) trait DynDkimSigner {
+    fn template(&self) -> &Signature;
+
+    fn now(&self) -> u64;
+
+    fn sign(&self, input: &[u8]) -> crate::Result<Vec<u8>>;
+}
+
+impl<T: SigningKey> DynDkimSigner for DkimSigner<T, Done> {
+    fn template(&self) -> &Signature {
+        &self.template
+    }
+
+    fn now(&self) -> u64 {
+        self.clock.now()
+    }
+
+    fn sign(&self, input: &[u8]) -> crate::Result<Vec<u8>> {
+        self.key.sign(input)
+    }
+}
+
+impl DkimMultiSigner {
+    pub fn new() -> Self {
+        DkimMultiSigner::default()
+    }
+
+    /// Adds a signer. Signers sign in the order they are added, as if each
+    /// signed the message after the DKIM-Signature headers of the previous
+    /// signers were prepended to it.
+    pub fn with_signer<T: SigningKey + Send + Sync + 'static>(
+        mut self,
+        signer: DkimSigner<T, Done>,
+    ) -> Self {
+        self.signers.push(Box::new(signer));
+        self
+    }
+
+    /// Signs a message, returning the signatures in the order their headers
+    /// must appear in the message, which is the reverse of the order the
+    /// signers were added.
+    #[inline(always)]
+    pub fn sign(&self, message: &[u8]) -> crate::Result<Vec<Signature>> {
+        self.sign_stream(HeaderIterator::new(message))
+    }
+
+    /// Signs a chained message, returning the signatures in the order their
+    /// headers must appear in the message.
+    #[inline(always)]
+    pub fn sign_chained<'x>(
+        &self,
+        chunks: impl Iterator<Item = &'x [u8]>,
+    ) -> crate::Result<Vec<Signature>> {
+        self.sign_stream(ChainedHeaderIterator::new(chunks))
+    }
+
+    fn sign_stream<'x>(&self, mut message: impl HeaderStream<'x>) -> crate::Result<Vec<Signature>> {
+        let mut message_headers = Vec::new();
+        while let Some(header) = message.next_header() {
+            message_headers.push(header);
+        }
+        let body = message.body();
+
+        // Body hashes are shared by signers with the same body canonicalization
+        // and hash algorithm, and the canonical form of each message header by
+        // signers with the same header canonicalization. The l= tag always
+        // covers the entire body, so it does not change the hash.
+        let mut body_hashes: Vec<(Canonicalization, HashAlgorithm, Vec<u8>)> = Vec::new();
+        let mut canonical_headers: Vec<(Canonicalization, Vec<Option<Vec<u8>>>)> = Vec::new();
+        let mut signature_headers: Vec<Vec<u8>> = Vec::with_capacity(self.signers.len());
+        let mut signatures = Vec::with_capacity(self.signers.len());
+
+        for signer in &self.signers {
+            let template = signer.template();
+
+            // Headers added by the previous signers are at the top of the message
+            let mut found_headers = vec![false; template.h.len()];
+            let mut signed_headers = Vec::with_capacity(template.h.len());
+            let mut headers = Vec::with_capacity(template.h.len());
+            for (pos, (name, value)) in signature_headers
+                .iter()
+                .rev()
+                .flat_map(|header| HeaderIterator::new(header))
+                .map(|header| (None, header))
+                .chain(
+                    message_headers
+                        .iter()
+                        .copied()
+                        .enumerate()
+                        .map(|(pos, header)| (Some(pos), header)),
+                )
+            {
+                if let Some(h_pos) = template
+                    .h
+                    .iter()
+                    .position(|header| name.eq_ignore_ascii_case(header.as_bytes()))
+                {
+                    headers.push((pos, name, value));
+                    found_headers[h_pos] = true;
+                    signed_headers.push(core::str::from_utf8(name).unwrap().into());
+                }
+            }
+            if signed_headers.is_empty() {
+                return Err(Error::NoHeadersFound);
+            }
+
+            // Add any missing headers
+            signed_headers.reverse();
+            for (header, found) in template.h.iter().zip(found_headers) {
+                if !found {
+                    signed_headers.push(header.to_string());
+                }
+            }
+
+            let ha = HashAlgorithm::from(template.a);
+            let body_hash = match body_hashes
+                .iter()
+                .position(|(cb, h, _)| cb == &template.cb && h == &ha)
+            {
+                Some(pos) => &body_hashes[pos].2,
+                None => {
+                    let hash = ha.hash(template.cb.canonical_body(body, u64::MAX));
+                    body_hashes.push((template.cb, ha, hash.as_ref().to_vec()));
+                    &body_hashes.last().unwrap().2
+                }
+            };
+            let mut signature =
+                template.with_tags(body.len(), signed_headers, body_hash, signer.now());
+
+            // Headers are signed from the bottom of the message up
+            let cache = match canonical_headers
+                .iter()
+                .position(|(ch, _)| ch == &template.ch)
+            {
+                Some(pos) => &mut canonical_headers[pos].1,
+                None => {
+                    canonical_headers.push((template.ch, vec![None; message_headers.len()]));
+                    &mut canonical_headers.last_mut().unwrap().1
+                }
+            };
+            let mut data = Vec::with_capacity(256);
+            for (pos, name, value) in headers.into_iter().rev() {
+                let header = iter::once((name, value));
+                match pos {
+                    Some(pos) => data.extend_from_slice(cache[pos].get_or_insert_with(|| {
+                        let mut canonical_header = Vec::new();
+                        template
+                            .ch
+                            .canonicalize_headers(header, &mut canonical_header);
+                        canonical_header
+                    })),
+                    None => template.ch.canonicalize_headers(header, &mut data),
+                }
+            }
+            signature.write(&mut data, false);
+            signature.b = base64_encode(&signer.sign(&data)?);
+
+            let mut header = Vec::with_capacity(256);
+            signature.write(&mut header, true);
+            signature_headers.push(header);
+            signatures.push(signature);
+        }
+
+        signatures.reverse();
+        Ok(signatures)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use mail_parser::decoders::base64::base64_decode;
+
+    use crate::{
+        common::{
+            crypto::{Ed25519Key, RsaKey, Sha256},
+            headers::HeaderWriter,
+            parse::TxtRecordParser,
+            verify::DomainKey,
+        },
+        dkim::{verify::DkimVerifier, Canonicalization, DkimMultiSigner, DkimSigner},
+        AuthenticatedMessage, DkimResult, Resolver,
+    };
+
+    const RSA_PRIVATE_KEY: &str = include_str!("../../resources/rsa-private.pem");
+
+    const RSA_PUBLIC_KEY: &str = concat!(
+        "v=DKIM1; t=s; p=MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ",
+        "8AMIIBCgKCAQEAv9XYXG3uK95115mB4nJ37nGeNe2CrARm",
+        "1agrbcnSk5oIaEfMZLUR/X8gPzoiNHZcfMZEVR6bAytxUh",
+        "c5EvZIZrjSuEEeny+fFd/cTvcm3cOUUbIaUmSACj0dL2/K",
+        "wW0LyUaza9z9zor7I5XdIl1M53qVd5GI62XBB76FH+Q0bW",
+        "PZNkT4NclzTLspD/MTpNCCPhySM4Kdg5CuDczTH4aNzyS0",
+        "TqgXdtw6A4Sdsp97VXT9fkPW9rso3lrkpsl/9EQ1mR/DWK",
+        "6PBmRfIuSFuqnLKY6v/z2hXHxF7IoojfZLa2kZr9Aed4l9",
+        "WheQOTA19k5r2BmlRw/W9CrgCBo0Sdj+KQIDAQAB",
+    );
+
+    const ED25519_PRIVATE_KEY: &str = "nWGxne/9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A=";
+    const ED25519_PUBLIC_KEY: &str =
+        "v=DKIM1; k=ed25519; p=11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=";
+
+    fn rsa_key() -> RsaKey<Sha256> {
+        #[cfg(feature = "rust-crypto")]
+        let pk = RsaKey::<Sha256>::from_pkcs1_pem(RSA_PRIVATE_KEY).unwrap();
+        #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
+        let pk = RsaKey::<Sha256>::from_rsa_pem(RSA_PRIVATE_KEY).unwrap();
+        pk
+    }
+
+    fn ed25519_key() -> Ed25519Key {
+        let private_key = base64_decode(ED25519_PRIVATE_KEY.as_bytes()).unwrap();
+        #[cfg(feature = "rust-crypto")]
+        let pk = Ed25519Key::from_seed(&private_key).unwrap();
+        #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
+        let pk = Ed25519Key::from_seed_and_public_key(
+            &private_key,
+            &base64_decode(ED25519_PUBLIC_KEY.rsplit_once("p=").unwrap().1.as_bytes()).unwrap(),
+        )
+        .unwrap();
+        pk
+    }
+
+    #[cfg(any(
+        feature = "rust-crypto",
+        all(feature = "ring", feature = "rustls-pemfile")
+    ))]
+    #[tokio::test]
+    async fn dkim_multi_sign() {
+        let message = concat!(
+            "From: bill@example.com\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject: TPS Report\r\n",
+            "\r\n",
+            "I'm going to need those TPS reports  ASAP.\r\n\r\n"
+        );
+
+        let resolver = Resolver::new_mock();
+        for (name, record) in [
+            ("default._domainkey.example.com.", RSA_PUBLIC_KEY),
+            ("ed._domainkey.example.com.", ED25519_PUBLIC_KEY),
+            ("ed._domainkey.example.org.", ED25519_PUBLIC_KEY),
+        ] {
+            resolver.txt_add(
+                name,
+                DomainKey::parse(record.as_bytes()).unwrap(),
+                Instant::now() + Duration::new(3600, 0),
+            );
+        }
+
+        let signatures = DkimMultiSigner::new()
+            .with_signer(
+                DkimSigner::from_key(rsa_key())
+                    .domain("example.com")
+                    .selector("default")
+                    .headers(["From", "To", "Subject"])
+                    .clock(311923920),
+            )
+            .with_signer(
+                DkimSigner::from_key(ed25519_key())
+                    .domain("example.com")
+                    .selector("ed")
+                    .headers(["From", "To", "Subject"]),
+            )
+            .with_signer(
+                DkimSigner::from_key(ed25519_key())
+                    .domain("example.org")
+                    .selector("ed")
+                    .headers(["From", "To", "Subject", "DKIM-Signature"])
+                    .header_canonicalization(Canonicalization::Simple)
+                    .body_canonicalization(Canonicalization::Simple),
+            )
+            .sign(message.as_bytes())
+            .unwrap();
+
+        // Signatures are returned in header order, the first signer last
+        assert_eq!(
+            signatures.iter().map(|s| s.d.as_str()).collect::<Vec<_>>(),
+            ["example.org", "example.com", "example.com"]
+        );
+        assert_eq!(
+            signatures[2],
+            DkimSigner::from_key(rsa_key())
+                .domain("example.com")
+                .selector("default")
+                .headers(["From", "To", "Subject"])
+                .clock(311923920)
+                .sign(message.as_bytes())
+                .unwrap()
+        );
+        assert_eq!(signatures[1].bh, signatures[2].bh);
+        assert_ne!(signatures[0].bh, signatures[1].bh);
+        assert_eq!(
+            signatures[0].h,
+            ["Subject", "To", "From", "DKIM-Signature", "DKIM-Signature"]
+        );
+
+        let signed_message = signatures
+            .iter()
+            .map(|signature| signature.to_header())
+            .collect::<String>()
+            + message;
+        let message = AuthenticatedMessage::parse(signed_message.as_bytes()).unwrap();
+        let results = DkimVerifier::verify_dkim(&resolver, &message).await;
+        assert_eq!(results.len(), 3);
+        for result in results {
+            assert_eq!(result.result(), &DkimResult::Pass);
+        }
+    }
+}
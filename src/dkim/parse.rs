@@ -8,12 +8,11 @@
  * except according to those terms.
  */
 
-use std::slice::Iter;
-
-use mail_parser::decoders::base64::base64_decode_stream;
+use alloc::{string::String, vec::Vec};
+use core::slice::Iter;
 
 use crate::{
-    common::{crypto::VerifyingKeyType, parse::*, verify::DomainKey},
+    common::{base64::base64_decode_stream, crypto::VerifyingKeyType, parse::*, verify::DomainKey},
     dkim::{RR_EXPIRATION, RR_SIGNATURE, RR_UNKNOWN_TAG, RR_VERIFICATION},
     Error,
 };
@@ -241,7 +240,7 @@ impl SignatureParser for Iter<'_, u8> {
 }
 
 impl TxtRecordParser for DomainKey {
-    #[allow(clippy::while_let_on_iterator)]
+    #[allow(clippy::while_let_on_iterator, clippy::collapsible_match)]
     fn parse(header: &[u8]) -> crate::Result<Self> {
         let header_len = header.len();
         let mut header = header.iter();
@@ -298,6 +297,8 @@ impl TxtRecordParser for DomainKey {
             Some(public_key) => Ok(DomainKey {
                 p: key_type.verifying_key(&public_key)?,
                 f: flags,
+                public_key,
+                k: key_type,
             }),
             _ => Err(Error::InvalidRecordType),
         }
@@ -321,7 +322,7 @@ impl TxtRecordParser for DomainKeyReport {
                     record.ra = header.text_qp(Vec::with_capacity(20), true, false);
                 }
                 RP => {
-                    record.rp = std::cmp::min(header.number().unwrap_or(0), 100) as u8;
+                    record.rp = core::cmp::min(header.number().unwrap_or(0), 100) as u8;
                 }
                 RS => {
                     record.rs = header.text_qp(Vec::with_capacity(20), false, false).into();
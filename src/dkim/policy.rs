@@ -0,0 +1,304 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use alloc::vec::Vec;
+
+use crate::{
+    common::{
+        crypto::{keys::rsa_modulus_bits, Algorithm, VerifyingKeyType},
+        verify::DomainKey,
+    },
+    Error, PolicyViolation,
+};
+
+/// Signatures that are acceptable to the verifier. Signatures that violate
+/// the policy are not verified and produce a `DkimResult::Policy` result.
+///
+/// The policy applies to DKIM signatures as well as to ARC message signatures
+/// and seals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkimPolicy {
+    algorithms: Vec<Algorithm>,
+    min_rsa_bits: usize,
+    require_ed25519: bool,
+}
+
+impl DkimPolicy {
+    /// Creates a policy that accepts all supported algorithms and RSA keys of
+    /// any size.
+    pub fn new() -> Self {
+        DkimPolicy {
+            algorithms: alloc::vec![
+                Algorithm::RsaSha1,
+                Algorithm::RsaSha256,
+                Algorithm::Ed25519Sha256
+            ],
+            min_rsa_bits: 0,
+            require_ed25519: false,
+        }
+    }
+
+    /// Creates a policy following RFC 8301, which rejects `rsa-sha1`
+    /// signatures and RSA keys shorter than 1024 bits.
+    pub fn rfc8301() -> Self {
+        DkimPolicy::new()
+            .algorithms([Algorithm::RsaSha256, Algorithm::Ed25519Sha256])
+            .min_rsa_bits(1024)
+    }
+
+    /// Sets the signing algorithms that are accepted.
+    pub fn algorithms(mut self, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        self.algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Sets the minimum size in bits of RSA keys. RFC 8301 requires at least
+    /// 1024 bits and recommends 2048. When set, RSA keys whose size cannot be
+    /// determined are rejected.
+    pub fn min_rsa_bits(mut self, bits: usize) -> Self {
+        self.min_rsa_bits = bits;
+        self
+    }
+
+    /// Requires signatures to use `ed25519-sha256`.
+    pub fn require_ed25519(mut self, require: bool) -> Self {
+        self.require_ed25519 = require;
+        self
+    }
+
+    /// Checks that a signing algorithm and the key that verifies the
+    /// signature are acceptable.
+    pub(crate) fn check(&self, algorithm: Algorithm, record: &DomainKey) -> crate::Result<()> {
+        let violation = if self.require_ed25519 && algorithm != Algorithm::Ed25519Sha256 {
+            PolicyViolation::Ed25519Required
+        } else if !self.algorithms.contains(&algorithm) {
+            PolicyViolation::Algorithm(algorithm)
+        } else if record.k == VerifyingKeyType::Rsa && self.min_rsa_bits > 0 {
+            match rsa_modulus_bits(&record.public_key) {
+                Some(bits) if bits < self.min_rsa_bits => PolicyViolation::RsaKeyLength(bits),
+                Some(_) => return Ok(()),
+                None => PolicyViolation::RsaKeyInvalid,
+            }
+        } else {
+            return Ok(());
+        };
+
+        Err(Error::PolicyViolation(violation))
+    }
+}
+
+impl Default for DkimPolicy {
+    fn default() -> Self {
+        DkimPolicy::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::{
+            crypto::{Algorithm, Ed25519Key, RsaKey, Sha256, VerifyingKey, VerifyingKeyType},
+            headers::HeaderWriter,
+            parse::TxtRecordParser,
+            verify::DomainKey,
+        },
+        dkim::{
+            verify::{DkimKeys, DkimVerifier},
+            DkimSigner,
+        },
+        AuthenticatedMessage, DkimResult, Error, PolicyViolation,
+    };
+
+    use super::DkimPolicy;
+
+    /// Stands in for an RSA key that no backend is able to decode.
+    struct UndecodableKey;
+
+    impl VerifyingKey for UndecodableKey {
+        fn verify<'a>(
+            &self,
+            _: &mut dyn Iterator<Item = (&'a [u8], &'a [u8])>,
+            _: &[u8],
+            _: crate::dkim::Canonicalization,
+            _: Algorithm,
+        ) -> crate::Result<()> {
+            Err(Error::FailedVerification)
+        }
+    }
+
+    const RSA_512_PUBLIC_KEY: &str = concat!(
+        "v=DKIM1; k=rsa; p=MFwwDQYJKoZIhvcNAQEBBQADSwAwSAJBANYxNeJ4JfQVnxPA+",
+        "fthc45DrSlDOjKjk8Ui/Pfbk/31qd9z35gZcfaFBVGv41lBZFlFgHX2NulXoK8is5bWuZkCAwEAAQ==",
+    );
+
+    #[test]
+    fn dkim_policy_check() {
+        let rsa_2048 = DomainKey::parse(
+            RsaKey::<Sha256>::from_private_key(include_bytes!("../../resources/rsa-private.pem"))
+                .unwrap()
+                .public_key_record()
+                .unwrap()
+                .as_bytes(),
+        )
+        .unwrap();
+        let rsa_512 = DomainKey::parse(RSA_512_PUBLIC_KEY.as_bytes()).unwrap();
+        let ed25519 =
+            DomainKey::parse(b"v=DKIM1; k=ed25519; p=11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=")
+                .unwrap();
+        let rsa_invalid = DomainKey {
+            p: Box::new(UndecodableKey),
+            f: 0,
+            public_key: vec![0; 6],
+            k: VerifyingKeyType::Rsa,
+        };
+
+        for (policy, a, record, expected) in [
+            (DkimPolicy::default(), Algorithm::RsaSha1, &rsa_2048, Ok(())),
+            (
+                DkimPolicy::default(),
+                Algorithm::RsaSha256,
+                &rsa_512,
+                Ok(()),
+            ),
+            (
+                DkimPolicy::default(),
+                Algorithm::RsaSha256,
+                &rsa_invalid,
+                Ok(()),
+            ),
+            (
+                DkimPolicy::rfc8301(),
+                Algorithm::RsaSha256,
+                &rsa_512,
+                Err(PolicyViolation::RsaKeyLength(512)),
+            ),
+            (
+                DkimPolicy::rfc8301(),
+                Algorithm::RsaSha256,
+                &rsa_invalid,
+                Err(PolicyViolation::RsaKeyInvalid),
+            ),
+            (
+                DkimPolicy::rfc8301(),
+                Algorithm::RsaSha1,
+                &rsa_2048,
+                Err(PolicyViolation::Algorithm(Algorithm::RsaSha1)),
+            ),
+            (
+                DkimPolicy::rfc8301(),
+                Algorithm::RsaSha256,
+                &rsa_2048,
+                Ok(()),
+            ),
+            (
+                DkimPolicy::rfc8301().min_rsa_bits(4096),
+                Algorithm::RsaSha256,
+                &rsa_2048,
+                Err(PolicyViolation::RsaKeyLength(2048)),
+            ),
+            (
+                DkimPolicy::rfc8301().min_rsa_bits(4096),
+                Algorithm::Ed25519Sha256,
+                &ed25519,
+                Ok(()),
+            ),
+            (
+                DkimPolicy::default().require_ed25519(true),
+                Algorithm::RsaSha256,
+                &rsa_2048,
+                Err(PolicyViolation::Ed25519Required),
+            ),
+            (
+                DkimPolicy::default().require_ed25519(true),
+                Algorithm::Ed25519Sha256,
+                &ed25519,
+                Ok(()),
+            ),
+        ] {
+            assert_eq!(
+                policy.check(a, record),
+                expected.map_err(Error::PolicyViolation),
+                "{policy:?} {a:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn dkim_policy_verify() {
+        let message = concat!(
+            "From: bill@example.com\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject: TPS Report\r\n",
+            "\r\n",
+            "I'm going to need those TPS reports ASAP.\r\n"
+        );
+        let rsa_key =
+            RsaKey::<Sha256>::from_private_key(include_bytes!("../../resources/rsa-private.pem"))
+                .unwrap();
+        let ed25519_key =
+            Ed25519Key::from_private_key(include_bytes!("../../resources/keys/ed25519-pkcs8.pem"))
+                .unwrap();
+        let records = [
+            ("rsa", rsa_key.public_key_record().unwrap()),
+            ("ed", ed25519_key.public_key_record().unwrap()),
+        ];
+
+        let signed_message = [
+            DkimSigner::from_key(ed25519_key)
+                .domain("example.com")
+                .selector("ed")
+                .headers(["From", "To", "Subject"])
+                .sign(message.as_bytes())
+                .unwrap()
+                .to_header(),
+            DkimSigner::from_key(rsa_key)
+                .domain("example.com")
+                .selector("rsa")
+                .headers(["From", "To", "Subject"])
+                .sign(message.as_bytes())
+                .unwrap()
+                .to_header(),
+        ]
+        .concat()
+            + message;
+        let message = AuthenticatedMessage::parse(signed_message.as_bytes()).unwrap();
+
+        for (policy, expected) in [
+            (DkimPolicy::rfc8301(), [DkimResult::Pass, DkimResult::Pass]),
+            (
+                DkimPolicy::rfc8301().min_rsa_bits(3072),
+                [
+                    DkimResult::Pass,
+                    DkimResult::Policy(Error::PolicyViolation(PolicyViolation::RsaKeyLength(2048))),
+                ],
+            ),
+            (
+                DkimPolicy::default().require_ed25519(true),
+                [
+                    DkimResult::Pass,
+                    DkimResult::Policy(Error::PolicyViolation(PolicyViolation::Ed25519Required)),
+                ],
+            ),
+        ] {
+            let mut keys = DkimKeys::new().with_policy(policy);
+            for (selector, record) in &records {
+                keys.add_txt(selector, "example.com", record.as_bytes());
+            }
+            let results = DkimVerifier::verify_dkim_with_keys(&keys, &message);
+            assert_eq!(
+                results
+                    .iter()
+                    .map(|r| r.result().clone())
+                    .collect::<Vec<_>>(),
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,297 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use mail_parser::DateTime;
+
+use crate::{
+    common::{
+        clock::Clock,
+        crypto::{Ed25519Key, RsaKey, Sha256, SigningKey, VerifyingKeyType},
+        verify::DomainKey,
+    },
+    Error, Resolver,
+};
+
+use super::{generate::DkimKeyPair, DkimSigner, NeedHeaders};
+
+/// Smallest RSA key size, in bits, that [`KeyRotation::generate`] accepts.
+/// Verifiers ignore shorter keys (RFC 8301, section 3.2).
+pub const MIN_RSA_BITS: usize = 1024;
+
+/// Type and size of the keys generated by [`KeyRotation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DkimKeyType {
+    Rsa { bits: usize },
+    Ed25519,
+}
+
+/// Key rotation workflow for a signing domain: generates keys published
+/// under date-based selectors, activates them once their records are live
+/// and revokes the selectors they replace.
+#[derive(Debug, Clone)]
+pub struct KeyRotation {
+    domain: String,
+    selector_prefix: String,
+    key_type: DkimKeyType,
+    clock: Clock,
+}
+
+/// Key generated by [`KeyRotation::generate`] that is waiting for its
+/// record to be published.
+pub struct PendingKey {
+    domain: String,
+    selector: String,
+    key_type: DkimKeyType,
+    key_pair: DkimKeyPair,
+}
+
+/// TXT record to publish in the DNS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsTxtRecord {
+    pub name: String,
+    pub value: String,
+}
+
+/// Private key that can be loaded from a key generated by
+/// [`KeyRotation::generate`].
+pub trait RotatedKey: SigningKey + Sized {
+    fn from_key_pair(key_pair: &DkimKeyPair) -> crate::Result<Self>;
+}
+
+impl KeyRotation {
+    /// Creates a rotation for `domain` that generates 2048-bit RSA keys
+    /// under selectors such as `s20240101`.
+    pub fn new(domain: impl Into<String>) -> Self {
+        KeyRotation {
+            domain: domain.into(),
+            selector_prefix: "s".to_string(),
+            key_type: DkimKeyType::Rsa { bits: 2048 },
+            clock: Clock::default(),
+        }
+    }
+
+    /// Sets the text prepended to the date in generated selectors.
+    pub fn selector_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.selector_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the type of the generated keys.
+    pub fn key_type(mut self, key_type: DkimKeyType) -> Self {
+        self.key_type = key_type;
+        self
+    }
+
+    /// Sets the clock used to date the selectors.
+    pub fn clock(mut self, clock: impl Into<Clock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    /// Returns the selector for a key generated today.
+    pub fn selector(&self) -> String {
+        let date = DateTime::from_timestamp(self.clock.now() as i64);
+        format!(
+            "{}{:04}{:02}{:02}",
+            self.selector_prefix, date.year, date.month, date.day
+        )
+    }
+
+    /// Generates a new key under today's selector. RSA keys shorter than
+    /// [`MIN_RSA_BITS`] are rejected.
+    pub fn generate(&self) -> crate::Result<PendingKey> {
+        let key_pair = match self.key_type {
+            DkimKeyType::Rsa { bits } if bits < MIN_RSA_BITS => {
+                return Err(Error::CryptoError(format!(
+                    "RSA keys must have at least {MIN_RSA_BITS} bits, got {bits}"
+                )));
+            }
+            DkimKeyType::Rsa { bits } => DkimKeyPair::generate_rsa(bits)?,
+            DkimKeyType::Ed25519 => DkimKeyPair::generate_ed25519()?,
+        };
+
+        Ok(PendingKey {
+            domain: self.domain.clone(),
+            selector: self.selector(),
+            key_type: self.key_type,
+            key_pair,
+        })
+    }
+
+    /// Returns the record that revokes a retired selector, which has an
+    /// empty `p=` tag as described in RFC 6376, section 3.6.1.
+    pub fn revoke(&self, selector: &str) -> DnsTxtRecord {
+        DnsTxtRecord {
+            name: record_name(selector, &self.domain),
+            value: "v=DKIM1; p=".to_string(),
+        }
+    }
+}
+
+impl PendingKey {
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn selector(&self) -> &str {
+        &self.selector
+    }
+
+    pub fn key_pair(&self) -> &DkimKeyPair {
+        &self.key_pair
+    }
+
+    /// Returns the record publishing the public key.
+    pub fn record(&self) -> crate::Result<DnsTxtRecord> {
+        Ok(DnsTxtRecord {
+            name: record_name(&self.selector, &self.domain),
+            value: self.domain_key()?.to_txt(),
+        })
+    }
+
+    fn domain_key(&self) -> crate::Result<DomainKey> {
+        let k = self.verifying_key_type();
+        let public_key = self.key_pair.public_key();
+
+        Ok(DomainKey {
+            p: k.verifying_key(public_key)?,
+            f: 0,
+            public_key: public_key.to_vec(),
+            k,
+        })
+    }
+
+    fn verifying_key_type(&self) -> VerifyingKeyType {
+        match self.key_type {
+            DkimKeyType::Rsa { .. } => VerifyingKeyType::Rsa,
+            DkimKeyType::Ed25519 => VerifyingKeyType::Ed25519,
+        }
+    }
+
+    /// Returns `true` if the resolver returns the record publishing this key.
+    pub async fn is_published(&self, resolver: &Resolver) -> crate::Result<bool> {
+        match resolver
+            .txt_lookup::<DomainKey>(record_name(&self.selector, &self.domain))
+            .await
+        {
+            Ok(record) => Ok(record.public_key == self.key_pair.public_key()
+                && record.k == self.verifying_key_type()),
+            Err(
+                Error::DnsRecordNotFound(_)
+                | Error::InvalidRecordType
+                | Error::CryptoError(_)
+                | Error::UnsupportedKeyType,
+            ) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Returns a signer for this key once its record is live, or `None` if
+    /// the resolver does not return it yet.
+    pub async fn activate<T: RotatedKey>(
+        &self,
+        resolver: &Resolver,
+    ) -> crate::Result<Option<DkimSigner<T, NeedHeaders>>> {
+        if self.is_published(resolver).await? {
+            Ok(Some(
+                DkimSigner::from_key(T::from_key_pair(&self.key_pair)?)
+                    .domain(&self.domain)
+                    .selector(&self.selector),
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl RotatedKey for RsaKey<Sha256> {
+    fn from_key_pair(key_pair: &DkimKeyPair) -> crate::Result<Self> {
+        RsaKey::<Sha256>::from_der(key_pair.private_key())
+    }
+}
+
+impl RotatedKey for Ed25519Key {
+    fn from_key_pair(key_pair: &DkimKeyPair) -> crate::Result<Self> {
+        Ed25519Key::from_pkcs8_der(key_pair.private_key())
+    }
+}
+
+fn record_name(selector: &str, domain: &str) -> String {
+    format!("{selector}._domainkey.{}.", domain.trim_end_matches('.'))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::{crypto::Ed25519Key, dns::DnsZone, headers::HeaderWriter},
+        dkim::verify::DkimVerifier,
+        AuthenticatedMessage, DkimResult, Resolver,
+    };
+
+    use super::{DkimKeyType, DnsTxtRecord, KeyRotation};
+
+    #[tokio::test]
+    async fn dkim_key_rotation() {
+        let rotation = KeyRotation::new("example.com")
+            .selector_prefix("mail")
+            .key_type(DkimKeyType::Ed25519)
+            .clock(1_727_740_800);
+        assert_eq!(rotation.selector(), "mail20241001");
+        assert!(rotation
+            .clone()
+            .key_type(DkimKeyType::Rsa { bits: 512 })
+            .generate()
+            .is_err());
+
+        let pending = rotation.generate().unwrap();
+        let record = pending.record().unwrap();
+        assert_eq!(record.name, "mail20241001._domainkey.example.com.");
+        assert!(record.value.starts_with("v=DKIM1; k=ed25519; p="));
+
+        // Not live until the record is published
+        let resolver = Resolver::with_backend(DnsZone::new(), 128);
+        assert!(!pending.is_published(&resolver).await.unwrap());
+        assert!(pending
+            .activate::<Ed25519Key>(&resolver)
+            .await
+            .unwrap()
+            .is_none());
+
+        let zone = DnsZone::new();
+        zone.add_txt(record.name.as_str(), record.value.as_str());
+        let resolver = Resolver::with_backend(zone, 128);
+        let signer = pending
+            .activate::<Ed25519Key>(&resolver)
+            .await
+            .unwrap()
+            .unwrap()
+            .headers(["From", "To", "Subject"]);
+
+        let message = concat!(
+            "From: bill@example.com\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject: TPS Report\r\n",
+            "\r\n",
+            "I'm going to need those TPS reports ASAP.\r\n"
+        );
+        let signed_message = signer.sign(message.as_bytes()).unwrap().to_header() + message;
+        let message = AuthenticatedMessage::parse(signed_message.as_bytes()).unwrap();
+        let result = DkimVerifier::verify_dkim(&resolver, &message).await;
+        assert_eq!(result[0].result(), &DkimResult::Pass);
+
+        assert_eq!(
+            rotation.revoke("mail20240701"),
+            DnsTxtRecord {
+                name: "mail20240701._domainkey.example.com.".to_string(),
+                value: "v=DKIM1; p=".to_string(),
+            }
+        );
+    }
+}
@@ -0,0 +1,256 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use alloc::string::{String, ToString};
+use core::fmt::{self, Display, Formatter, Write};
+
+use crate::common::{
+    base64::base64_encode,
+    crypto::{R_HASH_SHA1, R_HASH_SHA256},
+    verify::DomainKey,
+};
+
+use super::{
+    Atps, DomainKeyReport, RR_DNS, RR_EXPIRATION, RR_OTHER, RR_POLICY, RR_SIGNATURE,
+    RR_UNKNOWN_TAG, RR_VERIFICATION, R_FLAG_MATCH_DOMAIN, R_FLAG_TESTING, R_SVC_ALL, R_SVC_EMAIL,
+};
+
+impl DomainKey {
+    /// Serializes the record into the contents of a TXT record.
+    pub fn to_txt(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl DomainKeyReport {
+    /// Serializes the record into the contents of a TXT record.
+    pub fn to_txt(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Atps {
+    /// Serializes the record into the contents of a TXT record.
+    pub fn to_txt(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for DomainKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("v=DKIM1; k=")?;
+        f.write_str(self.key_type())?;
+        write_flags(
+            f,
+            "h",
+            self.f,
+            &[(R_HASH_SHA1, "sha1"), (R_HASH_SHA256, "sha256")],
+        )?;
+        write_flags(f, "s", self.f, &[(R_SVC_EMAIL, "email"), (R_SVC_ALL, "*")])?;
+        write_flags(
+            f,
+            "t",
+            self.f,
+            &[(R_FLAG_TESTING, "y"), (R_FLAG_MATCH_DOMAIN, "s")],
+        )?;
+        f.write_str("; p=")?;
+        f.write_str(&String::from_utf8_lossy(&base64_encode(&self.public_key)))
+    }
+}
+
+impl Display for DomainKeyReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("ra=")?;
+        write_qp(f, &self.ra)?;
+        if self.rp != 100 {
+            write!(f, "; rp={}", self.rp)?;
+        }
+        if self.rr != u8::MAX {
+            f.write_str("; rr=")?;
+            let mut first = true;
+            for (flag, name) in [
+                (RR_DNS, 'd'),
+                (RR_OTHER, 'o'),
+                (RR_POLICY, 'p'),
+                (RR_SIGNATURE, 's'),
+                (RR_UNKNOWN_TAG, 'u'),
+                (RR_VERIFICATION, 'v'),
+                (RR_EXPIRATION, 'x'),
+            ] {
+                if self.rr & flag != 0 {
+                    if !first {
+                        f.write_char(':')?;
+                    }
+                    f.write_char(name)?;
+                    first = false;
+                }
+            }
+        }
+        if let Some(rs) = &self.rs {
+            f.write_str("; rs=")?;
+            write_qp(f, rs)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Atps {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("v=ATPS1")?;
+        if let Some(d) = &self.d {
+            write!(f, "; d={d}")?;
+        }
+        Ok(())
+    }
+}
+
+fn write_flags(f: &mut Formatter<'_>, tag: &str, flags: u64, names: &[(u64, &str)]) -> fmt::Result {
+    let mut first = true;
+    for (flag, name) in names {
+        if flags & flag != 0 {
+            if first {
+                write!(f, "; {tag}=")?;
+                first = false;
+            } else {
+                f.write_char(':')?;
+            }
+            f.write_str(name)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_qp(f: &mut Formatter<'_>, text: &str) -> fmt::Result {
+    for &ch in text.as_bytes() {
+        match ch {
+            0..=0x20 | b';' | b'=' | 0x7f..=u8::MAX => write!(f, "={ch:02X}")?,
+            _ => f.write_char(char::from(ch))?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::PathBuf};
+
+    use mail_parser::decoders::base64::base64_decode;
+    use proptest::prelude::*;
+
+    use crate::{
+        common::{
+            crypto::{Ed25519Key, VerifyingKeyType},
+            parse::TxtRecordParser,
+            verify::DomainKey,
+        },
+        dkim::{Atps, DomainKeyReport},
+        Version,
+    };
+
+    const RSA_PUBLIC_KEY: &str = concat!(
+        "MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ",
+        "8AMIIBCgKCAQEAv9XYXG3uK95115mB4nJ37nGeNe2CrARm",
+        "1agrbcnSk5oIaEfMZLUR/X8gPzoiNHZcfMZEVR6bAytxUh",
+        "c5EvZIZrjSuEEeny+fFd/cTvcm3cOUUbIaUmSACj0dL2/K",
+        "wW0LyUaza9z9zor7I5XdIl1M53qVd5GI62XBB76FH+Q0bW",
+        "PZNkT4NclzTLspD/MTpNCCPhySM4Kdg5CuDczTH4aNzyS0",
+        "TqgXdtw6A4Sdsp97VXT9fkPW9rso3lrkpsl/9EQ1mR/DWK",
+        "6PBmRfIuSFuqnLKY6v/z2hXHxF7IoojfZLa2kZr9Aed4l9",
+        "WheQOTA19k5r2BmlRw/W9CrgCBo0Sdj+KQIDAQAB",
+    );
+    const ED25519_PUBLIC_KEY: &str = "11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo=";
+
+    fn rsa_public_keys() -> Vec<Vec<u8>> {
+        let mut test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_dir.push("resources");
+        test_dir.push("dkim");
+
+        let mut keys = vec![base64_decode(RSA_PUBLIC_KEY.as_bytes()).unwrap()];
+        for file_name in fs::read_dir(&test_dir).unwrap() {
+            let test = fs::read_to_string(file_name.unwrap().path()).unwrap();
+            let (dns_records, _) = test.split_once("\n\n").unwrap();
+            for (_, value) in dns_records.lines().filter_map(|r| r.split_once(' ')) {
+                let record = DomainKey::parse(value.as_bytes()).unwrap();
+                if record.k == VerifyingKeyType::Rsa && !keys.contains(&record.public_key) {
+                    keys.push(record.public_key);
+                }
+            }
+        }
+        keys
+    }
+
+    fn public_key() -> impl Strategy<Value = (VerifyingKeyType, Vec<u8>)> {
+        prop_oneof![
+            proptest::sample::select(rsa_public_keys()).prop_map(|pk| (VerifyingKeyType::Rsa, pk)),
+            any::<[u8; 32]>().prop_map(|seed| (
+                VerifyingKeyType::Ed25519,
+                Ed25519Key::from_seed(&seed).unwrap().public_key()
+            )),
+        ]
+    }
+
+    fn domain_key_report() -> impl Strategy<Value = DomainKeyReport> {
+        (
+            "[a-z0-9.=; _+-]{1,20}",
+            0u8..=100,
+            prop_oneof![Just(u8::MAX), 0u8..0x80],
+            proptest::option::of("[ -~]{0,30}"),
+        )
+            .prop_map(|(ra, rp, rr, rs)| DomainKeyReport { ra, rp, rr, rs })
+    }
+
+    fn atps() -> impl Strategy<Value = Atps> {
+        proptest::option::of("[a-z0-9.-]{0,30}").prop_map(|d| Atps { v: Version::V1, d })
+    }
+
+    proptest! {
+        #[test]
+        fn domain_key_roundtrip((k, public_key) in public_key(), flags in 0u64..0x40) {
+            let record = DomainKey {
+                p: k.verifying_key(&public_key).unwrap(),
+                f: flags,
+                public_key,
+                k,
+            };
+            let parsed = DomainKey::parse(record.to_txt().as_bytes()).unwrap();
+            prop_assert_eq!(parsed.k, record.k);
+            prop_assert_eq!(parsed.f, record.f);
+            prop_assert_eq!(parsed.public_key, record.public_key);
+        }
+
+        #[test]
+        fn domain_key_report_roundtrip(record in domain_key_report()) {
+            prop_assert_eq!(DomainKeyReport::parse(record.to_txt().as_bytes()).unwrap(), record);
+        }
+
+        #[test]
+        fn atps_roundtrip(record in atps()) {
+            prop_assert_eq!(Atps::parse(record.to_txt().as_bytes()).unwrap(), record);
+        }
+    }
+
+    #[test]
+    fn domain_key_serialize() {
+        let record = DomainKey::parse(
+            format!("v=DKIM1; t=y:s; h=sha256; p={ED25519_PUBLIC_KEY}; k=ed25519").as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            record.to_txt(),
+            format!("v=DKIM1; k=ed25519; h=sha256; t=y:s; p={ED25519_PUBLIC_KEY}")
+        );
+        assert_eq!(
+            DomainKeyReport::parse(b"ra=dkim errors; rp=25; rr=s:x; rs=Error report")
+                .unwrap()
+                .to_txt(),
+            "ra=dkimerrors; rp=25; rr=s:x; rs=Errorreport"
+        );
+    }
+}
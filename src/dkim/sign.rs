@@ -8,15 +8,14 @@
  * except according to those terms.
  */
 
-use std::time::SystemTime;
-
-use mail_builder::encoders::base64::base64_encode;
+use alloc::{string::String, vec::Vec};
 
 use super::{canonicalize::CanonicalHeaders, DkimSigner, Done, Signature};
 
 use crate::{
     common::{
-        crypto::SigningKey,
+        base64::base64_encode,
+        crypto::{AsyncSigningKey, HashContext, HashImpl, SigningKey},
         headers::{ChainedHeaderIterator, HeaderIterator, HeaderStream, Writable, Writer},
     },
     Error,
@@ -26,28 +25,16 @@ impl<T: SigningKey> DkimSigner<T, Done> {
     /// Signs a message.
     #[inline(always)]
     pub fn sign(&self, message: &[u8]) -> crate::Result<Signature> {
-        self.sign_stream(
-            HeaderIterator::new(message),
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .map(|d| d.as_secs())
-                .unwrap_or(0),
-        )
+        self.sign_stream(HeaderIterator::new(message), self.clock.now())
     }
 
     #[inline(always)]
     /// Signs a chained message.
     pub fn sign_chained<'x>(
         &self,
-        chunks: impl Iterator<Item=&'x [u8]>,
+        chunks: impl Iterator<Item = &'x [u8]>,
     ) -> crate::Result<Signature> {
-        self.sign_stream(
-            ChainedHeaderIterator::new(chunks),
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .map(|d| d.as_secs())
-                .unwrap_or(0),
-        )
+        self.sign_stream(ChainedHeaderIterator::new(chunks), self.clock.now())
     }
 
     fn sign_stream<'x>(
@@ -55,18 +42,99 @@ impl<T: SigningKey> DkimSigner<T, Done> {
         message: impl HeaderStream<'x>,
         now: u64,
     ) -> crate::Result<Signature> {
+        let (mut signature, canonical_headers) =
+            self.template.prepare::<T::Hasher>(message, now)?;
+
+        // Sign
+        let b = self.key.sign(SignableMessage {
+            headers: canonical_headers,
+            signature: &signature,
+        })?;
+
+        // Encode
+        signature.b = base64_encode(&b);
+
+        Ok(signature)
+    }
+}
+
+impl<T: AsyncSigningKey> DkimSigner<T, Done> {
+    /// Signs a message using a key that signs asynchronously.
+    pub async fn sign_async(&self, message: &[u8]) -> crate::Result<Signature> {
+        self.sign_stream_async(HeaderIterator::new(message), self.clock.now())
+            .await
+    }
+
+    /// Signs a chained message using a key that signs asynchronously.
+    pub async fn sign_chained_async<'x>(
+        &self,
+        chunks: impl Iterator<Item = &'x [u8]>,
+    ) -> crate::Result<Signature> {
+        self.sign_stream_async(ChainedHeaderIterator::new(chunks), self.clock.now())
+            .await
+    }
+
+    async fn sign_stream_async<'x>(
+        &self,
+        message: impl HeaderStream<'x>,
+        now: u64,
+    ) -> crate::Result<Signature> {
+        let (mut signature, canonical_headers) =
+            self.template.prepare::<T::Hasher>(message, now)?;
+
+        // Sign
+        let mut data = Vec::with_capacity(256);
+        SignableMessage {
+            headers: canonical_headers,
+            signature: &signature,
+        }
+        .write(&mut data);
+        let b = self.key.sign(data).await?;
+
+        // Encode
+        signature.b = base64_encode(&b);
+
+        Ok(signature)
+    }
+}
+
+impl Signature {
+    /// Canonicalizes a message and fills in all tags except `b=`.
+    fn prepare<'x, H: HashImpl>(
+        &self,
+        message: impl HeaderStream<'x>,
+        now: u64,
+    ) -> crate::Result<(Signature, CanonicalHeaders<'x>)> {
         // Canonicalize headers and body
         let (body_len, canonical_headers, signed_headers, canonical_body) =
-            self.template.canonicalize(message);
+            self.canonicalize(message);
 
         if signed_headers.is_empty() {
             return Err(Error::NoHeadersFound);
         }
 
-        // Create Signature
-        let mut signature = self.template.clone();
-        let body_hash = self.key.hash(canonical_body);
-        signature.bh = base64_encode(body_hash.as_ref())?;
+        let mut body_hasher = H::hasher();
+        canonical_body.write(&mut body_hasher);
+        let signature = self.with_tags(
+            body_len,
+            signed_headers,
+            body_hasher.complete().as_ref(),
+            now,
+        );
+
+        Ok((signature, canonical_headers))
+    }
+
+    /// Returns a copy of the template with the tags computed at signing time.
+    pub(super) fn with_tags(
+        &self,
+        body_len: usize,
+        signed_headers: Vec<String>,
+        body_hash: &[u8],
+        now: u64,
+    ) -> Signature {
+        let mut signature = self.clone();
+        signature.bh = base64_encode(body_hash);
         signature.t = now;
         signature.x = if signature.x > 0 {
             now + signature.x
@@ -77,23 +145,13 @@ impl<T: SigningKey> DkimSigner<T, Done> {
         if signature.l > 0 {
             signature.l = body_len as u64;
         }
-
-        // Sign
-        let b = self.key.sign(SignableMessage {
-            headers: canonical_headers,
-            signature: &signature,
-        })?;
-
-        // Encode
-        signature.b = base64_encode(&b)?;
-
-        Ok(signature)
+        signature
     }
 }
 
 pub(super) struct SignableMessage<'a> {
-    headers: CanonicalHeaders<'a>,
-    signature: &'a Signature,
+    pub headers: CanonicalHeaders<'a>,
+    pub signature: &'a Signature,
 }
 
 impl<'a> Writable for SignableMessage<'a> {
@@ -112,9 +170,12 @@ pub mod test {
     use hickory_resolver::proto::op::ResponseCode;
     use mail_parser::decoders::base64::base64_decode;
 
+    use crate::dkim::verify::DkimVerifier;
     use crate::{
         common::{
-            crypto::{Ed25519Key, RsaKey, Sha256},
+            crypto::{
+                Algorithm, AsyncSigningKey, Ed25519Key, RsaKey, Sha256, SignFuture, SigningKey,
+            },
             headers::HeaderIterator,
             parse::TxtRecordParser,
             verify::DomainKey,
@@ -122,20 +183,19 @@ pub mod test {
         dkim::{Atps, Canonicalization, DkimSigner, DomainKeyReport, HashAlgorithm, Signature},
         AuthenticatedMessage, DkimOutput, DkimResult, Resolver,
     };
-    use crate::dkim::verify::DkimVerifier;
 
     const RSA_PRIVATE_KEY: &str = include_str!("../../resources/rsa-private.pem");
 
     const RSA_PUBLIC_KEY: &str = concat!(
-    "v=DKIM1; t=s; p=MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ",
-    "8AMIIBCgKCAQEAv9XYXG3uK95115mB4nJ37nGeNe2CrARm",
-    "1agrbcnSk5oIaEfMZLUR/X8gPzoiNHZcfMZEVR6bAytxUh",
-    "c5EvZIZrjSuEEeny+fFd/cTvcm3cOUUbIaUmSACj0dL2/K",
-    "wW0LyUaza9z9zor7I5XdIl1M53qVd5GI62XBB76FH+Q0bW",
-    "PZNkT4NclzTLspD/MTpNCCPhySM4Kdg5CuDczTH4aNzyS0",
-    "TqgXdtw6A4Sdsp97VXT9fkPW9rso3lrkpsl/9EQ1mR/DWK",
-    "6PBmRfIuSFuqnLKY6v/z2hXHxF7IoojfZLa2kZr9Aed4l9",
-    "WheQOTA19k5r2BmlRw/W9CrgCBo0Sdj+KQIDAQAB",
+        "v=DKIM1; t=s; p=MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ",
+        "8AMIIBCgKCAQEAv9XYXG3uK95115mB4nJ37nGeNe2CrARm",
+        "1agrbcnSk5oIaEfMZLUR/X8gPzoiNHZcfMZEVR6bAytxUh",
+        "c5EvZIZrjSuEEeny+fFd/cTvcm3cOUUbIaUmSACj0dL2/K",
+        "wW0LyUaza9z9zor7I5XdIl1M53qVd5GI62XBB76FH+Q0bW",
+        "PZNkT4NclzTLspD/MTpNCCPhySM4Kdg5CuDczTH4aNzyS0",
+        "TqgXdtw6A4Sdsp97VXT9fkPW9rso3lrkpsl/9EQ1mR/DWK",
+        "6PBmRfIuSFuqnLKY6v/z2hXHxF7IoojfZLa2kZr9Aed4l9",
+        "WheQOTA19k5r2BmlRw/W9CrgCBo0Sdj+KQIDAQAB",
     );
 
     const ED25519_PRIVATE_KEY: &str = "nWGxne/9WmC6hEr0kuwsxERJxWl7MmkZcDusAxyuf2A=";
@@ -159,12 +219,12 @@ pub mod test {
             .sign_stream(
                 HeaderIterator::new(
                     concat!(
-                    "From: hello@stalw.art\r\n",
-                    "To: dkim@stalw.art\r\n",
-                    "Subject: Testing  DKIM!\r\n\r\n",
-                    "Here goes the test\r\n\r\n"
+                        "From: hello@stalw.art\r\n",
+                        "To: dkim@stalw.art\r\n",
+                        "Subject: Testing  DKIM!\r\n\r\n",
+                        "Here goes the test\r\n\r\n"
                     )
-                        .as_bytes(),
+                    .as_bytes(),
                 ),
                 311923920,
             )
@@ -172,21 +232,106 @@ pub mod test {
 
         assert_eq!(
             concat!(
-            "dkim-signature:v=1; a=rsa-sha256; s=default; d=stalw.art; ",
-            "c=relaxed/relaxed; h=Subject:To:From; t=311923920; ",
-            "bh=QoiUNYyUV+1tZ/xUPRcE+gST2zAStvJx1OK078Yl m5s=; ",
-            "b=B/p1FPSJ+Jl4A94381+DTZZnNO4c3fVqDnj0M0Vk5JuvnKb5",
-            "dKSwaoIHPO8UUJsroqH z+R0/eWyW1Vlz+uMIZc2j7MVPJcGaY",
-            "Ni85uCQbPd8VpDKWWab6m21ngXYIpagmzKOKYllyOeK3X qwDz",
-            "Bo0T2DdNjGyMUOAWHxrKGU+fbcPHQYxTBCpfOxE/nc/uxxqh+i",
-            "2uXrsxz7PdCEN01LZiYVV yOzcv0ER9A7aDReE2XPVHnFL8jxE",
-            "2BD53HRv3hGkIDcC6wKOKG/lmID+U8tQk5CP0dLmprgjgTv Se",
-            "bu6xNc6SSIgpvwryAAzJEVwmaBqvE8RNk3Vg10lBZEuNsj2Q==;",
+                "dkim-signature:v=1; a=rsa-sha256; s=default; d=stalw.art; ",
+                "c=relaxed/relaxed; h=Subject:To:From; t=311923920; ",
+                "bh=QoiUNYyUV+1tZ/xUPRcE+gST2zAStvJx1OK078Yl m5s=; ",
+                "b=B/p1FPSJ+Jl4A94381+DTZZnNO4c3fVqDnj0M0Vk5JuvnKb5",
+                "dKSwaoIHPO8UUJsroqH z+R0/eWyW1Vlz+uMIZc2j7MVPJcGaY",
+                "Ni85uCQbPd8VpDKWWab6m21ngXYIpagmzKOKYllyOeK3X qwDz",
+                "Bo0T2DdNjGyMUOAWHxrKGU+fbcPHQYxTBCpfOxE/nc/uxxqh+i",
+                "2uXrsxz7PdCEN01LZiYVV yOzcv0ER9A7aDReE2XPVHnFL8jxE",
+                "2BD53HRv3hGkIDcC6wKOKG/lmID+U8tQk5CP0dLmprgjgTv Se",
+                "bu6xNc6SSIgpvwryAAzJEVwmaBqvE8RNk3Vg10lBZEuNsj2Q==;",
             ),
             signature.to_string()
         );
     }
 
+    /// Stand-in for a key held by a remote signing service.
+    pub struct RemoteKey<T>(pub T);
+
+    impl<T: SigningKey + Sync> AsyncSigningKey for RemoteKey<T> {
+        type Hasher = T::Hasher;
+
+        fn sign<'x>(&'x self, input: Vec<u8>) -> SignFuture<'x> {
+            Box::pin(async move {
+                tokio::task::yield_now().await;
+                self.0.sign(input.as_slice())
+            })
+        }
+
+        fn algorithm(&self) -> Algorithm {
+            self.0.algorithm()
+        }
+    }
+
+    #[cfg(any(
+        feature = "rust-crypto",
+        all(feature = "ring", feature = "rustls-pemfile")
+    ))]
+    #[tokio::test]
+    async fn dkim_sign_async() {
+        let message = concat!(
+            "From: hello@stalw.art\r\n",
+            "To: dkim@stalw.art\r\n",
+            "Subject: Testing  DKIM!\r\n\r\n",
+            "Here goes the test\r\n\r\n"
+        );
+
+        #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
+        let pk = RsaKey::<Sha256>::from_rsa_pem(RSA_PRIVATE_KEY).unwrap();
+        #[cfg(feature = "rust-crypto")]
+        let pk = RsaKey::<Sha256>::from_pkcs1_pem(RSA_PRIVATE_KEY).unwrap();
+        let expected = DkimSigner::from_key(pk)
+            .domain("stalw.art")
+            .selector("default")
+            .headers(["From", "To", "Subject"])
+            .clock(311923920)
+            .sign(message.as_bytes())
+            .unwrap();
+
+        #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
+        let pk = RsaKey::<Sha256>::from_rsa_pem(RSA_PRIVATE_KEY).unwrap();
+        #[cfg(feature = "rust-crypto")]
+        let pk = RsaKey::<Sha256>::from_pkcs1_pem(RSA_PRIVATE_KEY).unwrap();
+        let signer = DkimSigner::from_async_key(RemoteKey(pk))
+            .domain("stalw.art")
+            .selector("default")
+            .headers(["From", "To", "Subject"])
+            .clock(311923920);
+
+        assert_eq!(
+            signer.sign_async(message.as_bytes()).await.unwrap(),
+            expected
+        );
+        let (first, rest) = message.split_at(message.find("To:").unwrap());
+        assert_eq!(
+            signer
+                .sign_chained_async([first.as_bytes(), rest.as_bytes()].into_iter())
+                .await
+                .unwrap(),
+            expected
+        );
+
+        // Keys can also be selected at runtime through a trait object
+        #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
+        let pk = RsaKey::<Sha256>::from_rsa_pem(RSA_PRIVATE_KEY).unwrap();
+        #[cfg(feature = "rust-crypto")]
+        let pk = RsaKey::<Sha256>::from_pkcs1_pem(RSA_PRIVATE_KEY).unwrap();
+        let key: Box<dyn AsyncSigningKey<Hasher = Sha256> + Send + Sync> = Box::new(RemoteKey(pk));
+        assert_eq!(
+            DkimSigner::from_async_key(key)
+                .domain("stalw.art")
+                .selector("default")
+                .headers(["From", "To", "Subject"])
+                .clock(311923920)
+                .sign_async(message.as_bytes())
+                .await
+                .unwrap(),
+            expected
+        );
+    }
+
     #[cfg(any(
         feature = "rust-crypto",
         all(feature = "ring", feature = "rustls-pemfile")
@@ -194,47 +339,47 @@ pub mod test {
     #[tokio::test]
     async fn dkim_sign_verify() {
         let message = concat!(
-        "From: bill@example.com\r\n",
-        "To: jdoe@example.com\r\n",
-        "Subject: TPS Report\r\n",
-        "\r\n",
-        "I'm going to need those TPS reports ASAP. ",
-        "So, if you could do that, that'd be great.\r\n"
+            "From: bill@example.com\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject: TPS Report\r\n",
+            "\r\n",
+            "I'm going to need those TPS reports ASAP. ",
+            "So, if you could do that, that'd be great.\r\n"
         );
         let empty_message = concat!(
-        "From: bill@example.com\r\n",
-        "To: jdoe@example.com\r\n",
-        "Subject: Empty TPS Report\r\n",
-        "\r\n",
-        "\r\n"
+            "From: bill@example.com\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject: Empty TPS Report\r\n",
+            "\r\n",
+            "\r\n"
         );
         let message_multiheader = concat!(
-        "X-Duplicate-Header: 4\r\n",
-        "From: bill@example.com\r\n",
-        "X-Duplicate-Header: 3\r\n",
-        "To: jdoe@example.com\r\n",
-        "X-Duplicate-Header: 2\r\n",
-        "Subject: TPS Report\r\n",
-        "X-Duplicate-Header: 1\r\n",
-        "To: jane@example.com\r\n",
-        "\r\n",
-        "I'm going to need those TPS reports ASAP. ",
-        "So, if you could do that, that'd be great.\r\n"
+            "X-Duplicate-Header: 4\r\n",
+            "From: bill@example.com\r\n",
+            "X-Duplicate-Header: 3\r\n",
+            "To: jdoe@example.com\r\n",
+            "X-Duplicate-Header: 2\r\n",
+            "Subject: TPS Report\r\n",
+            "X-Duplicate-Header: 1\r\n",
+            "To: jane@example.com\r\n",
+            "\r\n",
+            "I'm going to need those TPS reports ASAP. ",
+            "So, if you could do that, that'd be great.\r\n"
         );
 
         // Create private keys
         #[cfg(feature = "rust-crypto")]
-        let pk_ed = Ed25519Key::from_bytes(&base64_decode(ED25519_PRIVATE_KEY.as_bytes()).unwrap())
-            .unwrap();
+        let pk_ed =
+            Ed25519Key::from_seed(&base64_decode(ED25519_PRIVATE_KEY.as_bytes()).unwrap()).unwrap();
         #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
         let pk_ed = Ed25519Key::from_seed_and_public_key(
             &base64_decode(ED25519_PRIVATE_KEY.as_bytes()).unwrap(),
             &base64_decode(ED25519_PUBLIC_KEY.rsplit_once("p=").unwrap().1.as_bytes()).unwrap(),
         )
-            .unwrap();
+        .unwrap();
 
         // Create resolver
-        let resolver = Resolver::new_system_conf().unwrap();
+        let resolver = Resolver::new_mock();
         #[cfg(any(test, feature = "test"))]
         {
             resolver.txt_add(
@@ -271,7 +416,7 @@ pub mod test {
             message,
             Ok(()),
         )
-            .await;
+        .await;
 
         dbg!("Test ED25519-SHA256 relaxed/relaxed");
         verify(
@@ -285,7 +430,7 @@ pub mod test {
             message,
             Ok(()),
         )
-            .await;
+        .await;
 
         dbg!("Test RSA-SHA256 relaxed/relaxed with an empty message");
         #[cfg(feature = "rust-crypto")]
@@ -304,7 +449,7 @@ pub mod test {
             empty_message,
             Ok(()),
         )
-            .await;
+        .await;
 
         dbg!("Test RSA-SHA256 simple/simple with an empty message");
         #[cfg(feature = "rust-crypto")]
@@ -325,7 +470,7 @@ pub mod test {
             empty_message,
             Ok(()),
         )
-            .await;
+        .await;
 
         dbg!("Test RSA-SHA256 simple/simple with duplicated headers");
         #[cfg(feature = "rust-crypto")]
@@ -351,7 +496,7 @@ pub mod test {
             message_multiheader,
             Ok(()),
         )
-            .await;
+        .await;
 
         dbg!("Test RSA-SHA256 simple/relaxed with fixed body length (relaxed)");
         #[cfg(feature = "rust-crypto")]
@@ -372,7 +517,7 @@ pub mod test {
             Ok(()),
             false,
         )
-            .await;
+        .await;
 
         dbg!("Test RSA-SHA256 simple/relaxed with fixed body length (strict)");
         #[cfg(feature = "rust-crypto")]
@@ -393,7 +538,7 @@ pub mod test {
             Err(super::Error::SignatureLength),
             true,
         )
-            .await;
+        .await;
 
         dbg!("Test AUID not matching domains");
         #[cfg(feature = "rust-crypto")]
@@ -412,7 +557,7 @@ pub mod test {
             message,
             Err(super::Error::FailedAuidMatch),
         )
-            .await;
+        .await;
 
         dbg!("Test expired signature and reporting");
         #[cfg(feature = "rust-crypto")]
@@ -432,12 +577,41 @@ pub mod test {
             message,
             Err(super::Error::SignatureExpired),
         )
-            .await
-            .pop()
-            .unwrap()
-            .report;
+        .await
+        .pop()
+        .unwrap()
+        .report;
         assert_eq!(r.as_deref(), Some("dkim-failures@example.com"));
 
+        dbg!("Test signature timestamps using a fixed clock");
+        #[cfg(feature = "rust-crypto")]
+        let pk_rsa = RsaKey::<Sha256>::from_pkcs1_pem(RSA_PRIVATE_KEY).unwrap();
+        #[cfg(all(feature = "ring", not(feature = "rust-crypto")))]
+        let pk_rsa = RsaKey::<Sha256>::from_rsa_pem(RSA_PRIVATE_KEY).unwrap();
+        let signature = DkimSigner::from_key(pk_rsa)
+            .domain("example.com")
+            .selector("default")
+            .headers(["From", "To", "Subject"])
+            .expiration(3600)
+            .clock(1_000_000)
+            .sign(message.as_bytes())
+            .unwrap();
+        assert_eq!((signature.t, signature.x), (1_000_000, 1_003_600));
+        verify(
+            &resolver.clone().with_clock(1_003_599),
+            signature.clone(),
+            message,
+            Ok(()),
+        )
+        .await;
+        verify(
+            &resolver.clone().with_clock(1_003_600),
+            signature,
+            message,
+            Err(super::Error::SignatureExpired),
+        )
+        .await;
+
         dbg!("Verify ATPS (failure)");
         #[cfg(feature = "rust-crypto")]
         let pk_rsa = RsaKey::<Sha256>::from_pkcs1_pem(RSA_PRIVATE_KEY).unwrap();
@@ -456,7 +630,7 @@ pub mod test {
             message,
             Err(super::Error::DnsRecordNotFound(ResponseCode::NXDomain)),
         )
-            .await;
+        .await;
 
         dbg!("Verify ATPS (success)");
         #[cfg(feature = "rust-crypto")]
@@ -482,7 +656,7 @@ pub mod test {
             message,
             Ok(()),
         )
-            .await;
+        .await;
 
         dbg!("Verify ATPS (success - no hash)");
         #[cfg(feature = "rust-crypto")]
@@ -507,7 +681,7 @@ pub mod test {
             message,
             Ok(()),
         )
-            .await;
+        .await;
     }
 
     pub async fn verify_with_opts<'x>(
@@ -522,7 +696,7 @@ pub mod test {
         message.extend_from_slice(message_.as_bytes());
 
         let message = AuthenticatedMessage::parse_with_opts(&message, strict).unwrap();
-        let dkim = DkimVerifier::verify_dkim(&resolver, &message).await;
+        let dkim = DkimVerifier::verify_dkim(resolver, &message).await;
 
         match (dkim.last().unwrap().result(), &expect) {
             (DkimResult::Pass, Ok(_)) => (),
@@ -539,6 +713,7 @@ pub mod test {
                 signature: None,
                 report: d.report,
                 is_atps: d.is_atps,
+                dnssec: d.dnssec,
             })
             .collect()
     }
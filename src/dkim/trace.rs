@@ -0,0 +1,293 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use alloc::{borrow::Cow, vec::Vec};
+
+#[cfg(feature = "std")]
+use crate::Resolver;
+use crate::{
+    common::{
+        crypto::{HashAlgorithm, HashContext, HashState},
+        headers::{Writable, Writer},
+    },
+    AuthenticatedMessage, DkimOutput, DkimResult,
+};
+
+use super::{
+    verify::{DkimKeys, DkimVerifier},
+    Signature,
+};
+
+/// Intermediate values computed while verifying a single DKIM-Signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkimVerificationTrace<'x> {
+    signed_headers: Vec<(&'x [u8], Cow<'x, [u8]>)>,
+    canonical_headers: Vec<u8>,
+    canonical_body: Option<Vec<u8>>,
+    computed_body_hash: Vec<u8>,
+    header_hash: Vec<u8>,
+    public_key: Option<Vec<u8>>,
+    output: DkimOutput<'x>,
+}
+
+/// Values recorded by the verifier while checking a DKIM-Signature. Steps
+/// that were not reached leave their values empty.
+#[derive(Debug, Default)]
+pub(crate) struct TraceRecord<'x> {
+    pub(crate) signed_headers: Vec<(&'x [u8], Cow<'x, [u8]>)>,
+    pub(crate) canonical_headers: Vec<u8>,
+    pub(crate) canonical_body: Option<Vec<u8>>,
+    pub(crate) computed_body_hash: Vec<u8>,
+    pub(crate) header_hash: Vec<u8>,
+    pub(crate) public_key: Option<Vec<u8>>,
+}
+
+impl TraceRecord<'_> {
+    /// Hashes the canonical body of a message whose body is retained,
+    /// keeping the canonical body as it is written to the hasher. Returns
+    /// `None` for messages built with `AuthenticatedMessageBuilder`.
+    pub(crate) fn hash_body(
+        &mut self,
+        message: &AuthenticatedMessage<'_>,
+        signature: &Signature,
+    ) -> Option<Vec<u8>> {
+        let body = message.raw_body();
+        if body.len() != message.body_len {
+            return None;
+        }
+
+        let mut writer = BodyRecorder {
+            hasher: HashAlgorithm::from(signature.a).hasher(),
+            body: Vec::with_capacity(body.len()),
+        };
+        signature
+            .cb
+            .canonical_body(body, signature.l)
+            .write(&mut writer);
+        self.canonical_body = Some(writer.body);
+
+        Some(writer.hasher.complete().as_ref().to_vec())
+    }
+}
+
+struct BodyRecorder {
+    hasher: HashState,
+    body: Vec<u8>,
+}
+
+impl Writer for BodyRecorder {
+    fn write(&mut self, buf: &[u8]) {
+        self.hasher.write(buf);
+        self.body.extend_from_slice(buf);
+    }
+}
+
+impl DkimVerifier {
+    /// Verifies the DKIM signatures of a message, returning for each
+    /// DKIM-Signature header the values used during verification.
+    #[cfg(feature = "std")]
+    pub async fn trace_dkim<'x>(
+        resolver: &Resolver,
+        message: &'x AuthenticatedMessage<'x>,
+    ) -> Vec<DkimVerificationTrace<'x>> {
+        let mut records = Vec::with_capacity(message.dkim_headers.len());
+        let outputs = Self::verify_dkim_(resolver, message, Some(&mut records)).await;
+
+        DkimVerificationTrace::collect(records, outputs)
+    }
+
+    /// Same as [`DkimVerifier::trace_dkim`] but using only the keys supplied in
+    /// `keys`, see [`DkimVerifier::verify_dkim_with_keys`].
+    pub fn trace_dkim_with_keys<'x>(
+        keys: &DkimKeys,
+        message: &'x AuthenticatedMessage<'x>,
+    ) -> Vec<DkimVerificationTrace<'x>> {
+        let mut records = Vec::with_capacity(message.dkim_headers.len());
+        let outputs = Self::verify_dkim_with_keys_(keys, message, Some(&mut records));
+
+        DkimVerificationTrace::collect(records, outputs)
+    }
+}
+
+impl<'x> DkimVerificationTrace<'x> {
+    fn collect(records: Vec<TraceRecord<'x>>, outputs: Vec<DkimOutput<'x>>) -> Vec<Self> {
+        records
+            .into_iter()
+            .zip(outputs)
+            .map(|(record, output)| DkimVerificationTrace {
+                signed_headers: record.signed_headers,
+                canonical_headers: record.canonical_headers,
+                canonical_body: record.canonical_body,
+                computed_body_hash: record.computed_body_hash,
+                header_hash: record.header_hash,
+                public_key: record.public_key,
+                output,
+            })
+            .collect()
+    }
+
+    /// Returns the parsed DKIM-Signature, if it could be parsed.
+    pub fn signature(&self) -> Option<&Signature> {
+        self.output.signature()
+    }
+
+    /// Returns the headers selected by the `h=` tag in signing order, followed
+    /// by the DKIM-Signature header with an empty `b=` tag. Empty if the
+    /// signature was rejected before its headers were hashed.
+    pub fn signed_headers(&self) -> &[(&'x [u8], Cow<'x, [u8]>)] {
+        &self.signed_headers
+    }
+
+    /// Returns the canonicalized headers whose hash is signed.
+    pub fn canonical_headers(&self) -> &[u8] {
+        &self.canonical_headers
+    }
+
+    /// Returns the canonicalized body, truncated to the `l=` tag if present.
+    /// Returns `None` if the body hash was not checked or the body is not
+    /// available, as for messages built with `AuthenticatedMessageBuilder`.
+    pub fn canonical_body(&self) -> Option<&[u8]> {
+        self.canonical_body.as_deref()
+    }
+
+    pub fn canonical_body_len(&self) -> Option<usize> {
+        self.canonical_body.as_ref().map(|body| body.len())
+    }
+
+    /// Returns the body hash the verifier compared against the `bh=` tag.
+    pub fn computed_body_hash(&self) -> &[u8] {
+        &self.computed_body_hash
+    }
+
+    /// Returns the body hash declared in the `bh=` tag.
+    pub fn declared_body_hash(&self) -> &[u8] {
+        self.signature().map_or(&[], |s| s.bh.as_slice())
+    }
+
+    /// Returns the hash of the canonicalized headers passed to the verifier.
+    pub fn header_hash(&self) -> &[u8] {
+        &self.header_hash
+    }
+
+    /// Returns the public key bytes from the `p=` tag of the record used to
+    /// verify the signature, if it was retrieved.
+    pub fn public_key(&self) -> Option<&[u8]> {
+        self.public_key.as_deref()
+    }
+
+    pub fn output(&self) -> &DkimOutput<'x> {
+        &self.output
+    }
+
+    pub fn result(&self) -> &DkimResult {
+        self.output.result()
+    }
+
+    pub fn into_output(self) -> DkimOutput<'x> {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        path::PathBuf,
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        common::{crypto::HashAlgorithm, parse::TxtRecordParser, verify::DomainKey},
+        dkim::verify::{DkimKeys, DkimVerifier},
+        AuthenticatedMessage, DkimResult, Resolver,
+    };
+
+    #[tokio::test]
+    async fn dkim_trace() {
+        let mut test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_dir.push("resources");
+        test_dir.push("dkim");
+
+        for file_name in fs::read_dir(&test_dir).unwrap() {
+            let file_name = file_name.unwrap().path();
+            let test = String::from_utf8(fs::read(&file_name).unwrap()).unwrap();
+            let (dns_records, raw_message) = test.split_once("\n\n").unwrap();
+            let raw_message = raw_message.replace('\n', "\r\n");
+            let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
+            let signed_at = message
+                .dkim_headers
+                .last()
+                .and_then(|h| h.header.as_ref().ok())
+                .map(|s| s.t)
+                .unwrap();
+
+            let resolver = Resolver::new_mock().with_clock(signed_at);
+            let mut keys = DkimKeys::new_with_clock(signed_at);
+            for (key, value) in dns_records
+                .split('\n')
+                .filter_map(|r| r.split_once(' ').map(|(a, b)| (a, b.as_bytes())))
+            {
+                resolver.txt_add(
+                    format!("{key}."),
+                    DomainKey::parse(value).unwrap(),
+                    Instant::now() + Duration::new(3200, 0),
+                );
+                let (selector, domain) = key.split_once("._domainkey.").unwrap();
+                keys.add_txt(selector, domain, value);
+            }
+
+            let traces = DkimVerifier::trace_dkim(&resolver, &message).await;
+            assert_eq!(traces, DkimVerifier::trace_dkim_with_keys(&keys, &message));
+            assert_eq!(
+                traces
+                    .iter()
+                    .map(|t| t.output().clone())
+                    .collect::<Vec<_>>(),
+                DkimVerifier::verify_dkim(&resolver, &message).await
+            );
+
+            let trace = traces.last().unwrap();
+            let signature = trace.signature().unwrap();
+            let ha = HashAlgorithm::from(signature.a);
+            assert_eq!(trace.result(), &DkimResult::Pass, "{}", file_name.display());
+            assert_eq!(trace.computed_body_hash(), trace.declared_body_hash());
+            assert_eq!(
+                ha.hash(trace.canonical_body().unwrap()).as_ref(),
+                trace.declared_body_hash()
+            );
+            assert_eq!(
+                ha.hash(trace.canonical_headers()).as_ref(),
+                trace.header_hash()
+            );
+            assert!(trace.signed_headers().len() <= signature.h.len() + 1);
+            assert!(trace.public_key().is_some_and(|pk| !pk.is_empty()));
+            if traces.len() == 1 {
+                assert_eq!(
+                    message.get_canonicalized_header().await.unwrap(),
+                    trace.canonical_headers()
+                );
+            }
+
+            // Messages streamed through the builder have no raw body, the
+            // remaining values are still recorded during verification.
+            let (headers, body) = raw_message.split_at(raw_message.find("\r\n\r\n").unwrap() + 4);
+            let mut builder = AuthenticatedMessage::builder(headers.as_bytes()).unwrap();
+            builder.write_body(body.as_bytes());
+            let built = builder.build();
+            let built_traces = DkimVerifier::trace_dkim_with_keys(&keys, &built);
+            let built_trace = built_traces.last().unwrap();
+            assert_eq!(built_trace.result(), &DkimResult::Pass);
+            assert_eq!(built_trace.canonical_body(), None);
+            assert_eq!(built_trace.computed_body_hash(), trace.computed_body_hash());
+            assert_eq!(built_trace.header_hash(), trace.header_hash());
+            assert_eq!(built_trace.public_key(), trace.public_key());
+        }
+    }
+}
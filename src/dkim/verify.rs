@@ -8,80 +8,224 @@
  * except according to those terms.
  */
 
-use std::time::SystemTime;
+use alloc::{borrow::Cow, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 
+#[cfg(feature = "std")]
 use crate::{
-    common::{
-        base32::Base32Writer,
-        headers::Writer,
-        verify::{DomainKey, VerifySignature},
-    },
-    is_within_pct, AuthenticatedMessage, DkimOutput, DkimResult, Error, Resolver,
+    common::{base32::Base32Writer, headers::Writer, verify::VerifySignature},
+    is_within_pct, DkimResult, Resolver,
+};
+use crate::{
+    common::{clock::Clock, headers::Header, parse::TxtRecordParser, verify::DomainKey},
+    AuthenticatedMessage, DkimOutput, Error, ResponseCode,
 };
 
+use super::{policy::DkimPolicy, trace::TraceRecord, Flag, HashAlgorithm, Signature};
+#[cfg(feature = "std")]
 use super::{
-    Atps, DomainKeyReport, Flag, HashAlgorithm, Signature, RR_DNS, RR_EXPIRATION, RR_OTHER,
-    RR_SIGNATURE, RR_VERIFICATION,
+    Atps, DomainKeyReport, RR_DNS, RR_EXPIRATION, RR_OTHER, RR_SIGNATURE, RR_VERIFICATION,
 };
 
 pub struct DkimVerifier {}
 
+/// Public keys supplied by the caller for offline DKIM verification, indexed
+/// by selector and signing domain.
+#[cfg_attr(feature = "std", derive(Default))]
+pub struct DkimKeys {
+    keys: BTreeMap<(String, String), crate::Result<Arc<DomainKey>>>,
+    clock: Clock,
+    policy: DkimPolicy,
+}
+
+impl DkimKeys {
+    /// Creates an empty key set that checks signature expiration against the
+    /// system clock.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty key set that checks signature expiration against
+    /// `clock`. Without the `std` feature there is no system clock and this
+    /// is the only constructor.
+    pub fn new_with_clock(clock: impl Into<Clock>) -> Self {
+        DkimKeys {
+            keys: BTreeMap::new(),
+            clock: clock.into(),
+            policy: DkimPolicy::default(),
+        }
+    }
+
+    /// Sets the clock used to check signature expiration.
+    pub fn with_clock(mut self, clock: impl Into<Clock>) -> Self {
+        self.clock = clock.into();
+        self
+    }
+
+    /// Sets the policy signatures must satisfy to be verified.
+    pub fn with_policy(mut self, policy: DkimPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Adds the public key published at `selector._domainkey.domain`.
+    pub fn add_key(
+        &mut self,
+        selector: impl AsRef<str>,
+        domain: impl AsRef<str>,
+        key: DomainKey,
+    ) -> &mut Self {
+        self.keys.insert(
+            Self::key(selector.as_ref(), domain.as_ref()),
+            Ok(key.into()),
+        );
+        self
+    }
+
+    /// Adds the raw TXT record published at `selector._domainkey.domain`.
+    /// Records that fail to parse produce a `PermError` for the signatures
+    /// that use them, as they would when fetched from DNS.
+    pub fn add_txt(
+        &mut self,
+        selector: impl AsRef<str>,
+        domain: impl AsRef<str>,
+        record: &[u8],
+    ) -> &mut Self {
+        self.keys.insert(
+            Self::key(selector.as_ref(), domain.as_ref()),
+            DomainKey::parse(record).map(Arc::new),
+        );
+        self
+    }
+
+    pub fn get(&self, selector: &str, domain: &str) -> crate::Result<Arc<DomainKey>> {
+        self.keys
+            .get(&Self::key(selector, domain))
+            .cloned()
+            .unwrap_or(Err(Error::DnsRecordNotFound(ResponseCode::NXDomain)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn key(selector: &str, domain: &str) -> (String, String) {
+        (
+            selector.to_lowercase(),
+            domain.trim_end_matches('.').to_lowercase(),
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: AsRef<str>, D: AsRef<str>> FromIterator<((S, D), DomainKey)> for DkimKeys {
+    fn from_iter<T: IntoIterator<Item = ((S, D), DomainKey)>>(iter: T) -> Self {
+        let mut keys = DkimKeys::new();
+        for ((selector, domain), key) in iter {
+            keys.add_key(selector, domain, key);
+        }
+        keys
+    }
+}
+
 impl DkimVerifier {
-    fn current_timestamp() -> u64 {
-        SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
+    /// Verifies the DKIM signatures of a message using only the public keys in
+    /// `keys`, without performing any DNS queries. Signatures whose key is
+    /// missing fail with `DnsRecordNotFound`. Third-party signatures (ATPS)
+    /// are not authorized and reports are not requested as both require DNS.
+    pub fn verify_dkim_with_keys<'x>(
+        keys: &DkimKeys,
+        message: &'x AuthenticatedMessage<'x>,
+    ) -> Vec<DkimOutput<'x>> {
+        Self::verify_dkim_with_keys_(keys, message, None)
     }
 
-    pub async fn verify_dkim<'x>(resolver: &Resolver, message: &'x AuthenticatedMessage<'x>) -> Vec<DkimOutput<'x>> {
-        let now = Self::current_timestamp();
+    pub(crate) fn verify_dkim_with_keys_<'x>(
+        keys: &DkimKeys,
+        message: &'x AuthenticatedMessage<'x>,
+        mut traces: Option<&mut Vec<TraceRecord<'x>>>,
+    ) -> Vec<DkimOutput<'x>> {
+        let now = keys.clock.now();
+
+        message
+            .dkim_headers
+            .iter()
+            .map(|header| {
+                let mut trace = traces.as_deref_mut().map(|traces| {
+                    traces.push(TraceRecord::default());
+                    traces.last_mut().unwrap()
+                });
+                let signature = match Self::verify_body(message, header, now, trace.as_deref_mut())
+                {
+                    Ok(signature) => signature,
+                    Err(output) => return output,
+                };
+
+                match keys.get(&signature.s, &signature.d) {
+                    Ok(record) => Self::verify_signature(
+                        message,
+                        header,
+                        signature,
+                        &record,
+                        &keys.policy,
+                        trace,
+                    )
+                    .unwrap_or_else(|| DkimOutput::pass().with_signature(signature)),
+                    Err(err) => DkimOutput::dns_error(err).with_signature(signature),
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "std")]
+    pub async fn verify_dkim<'x>(
+        resolver: &Resolver,
+        message: &'x AuthenticatedMessage<'x>,
+    ) -> Vec<DkimOutput<'x>> {
+        Self::verify_dkim_(resolver, message, None).await
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) async fn verify_dkim_<'x>(
+        resolver: &Resolver,
+        message: &'x AuthenticatedMessage<'x>,
+        mut traces: Option<&mut Vec<TraceRecord<'x>>>,
+    ) -> Vec<DkimOutput<'x>> {
+        let now = resolver.clock.now();
 
         let mut output = Vec::with_capacity(message.dkim_headers.len());
         let mut report_requested = false;
 
         // Validate DKIM headers
         for header in &message.dkim_headers {
-            // Validate body hash
-            let signature = match &header.header {
-                Ok(signature) => {
-                    if signature.r {
-                        report_requested = true;
-                    }
-
-                    if signature.x == 0 || (signature.x > signature.t && signature.x > now) {
-                        signature
-                    } else {
-                        output.push(
-                            DkimOutput::neutral(Error::SignatureExpired).with_signature(signature),
-                        );
-                        continue;
-                    }
+            if let Ok(signature) = &header.header {
+                if signature.r {
+                    report_requested = true;
                 }
+            }
+            let mut trace = traces.as_deref_mut().map(|traces| {
+                traces.push(TraceRecord::default());
+                traces.last_mut().unwrap()
+            });
+
+            // Validate expiration and body hash
+            let signature = match Self::verify_body(message, header, now, trace.as_deref_mut()) {
+                Ok(signature) => signature,
                 Err(err) => {
-                    output.push(DkimOutput::neutral(err.clone()));
+                    output.push(err);
                     continue;
                 }
             };
 
-            // Validate body hash
-            let ha = HashAlgorithm::from(signature.a);
-            let bh = &message
-                .body_hashes
-                .iter()
-                .find(|(c, h, l, _)| c == &signature.cb && h == &ha && l == &signature.l)
-                .unwrap()
-                .3;
-
-            if bh != &signature.bh {
-                output.push(
-                    DkimOutput::neutral(Error::FailedBodyHashMatch).with_signature(signature),
-                );
-                continue;
-            }
-
             // Obtain ._domainkey TXT record
-            let record = match resolver.txt_lookup::<DomainKey>(signature.domain_key()).await {
+            let (record, dnssec) = match resolver
+                .txt_lookup_with_dnssec::<DomainKey>(signature.domain_key())
+                .await
+            {
                 Ok(record) => record,
                 Err(err) => {
                     output.push(DkimOutput::dns_error(err).with_signature(signature));
@@ -89,19 +233,16 @@ impl DkimVerifier {
                 }
             };
 
-            // Enforce t=s flag
-            if !signature.validate_auid(&record) {
-                output.push(DkimOutput::fail(Error::FailedAuidMatch).with_signature(signature));
-                continue;
-            }
-
-            // Hash headers
-            let dkim_hdr_value = header.value.strip_signature();
-            let mut headers = message.signed_headers(&signature.h, header.name, &dkim_hdr_value);
-
-            // Verify signature
-            if let Err(err) = record.verify(&mut headers, signature, signature.ch) {
-                output.push(DkimOutput::fail(err).with_signature(signature));
+            // Enforce t=s flag and policy, verify signature
+            if let Some(err) = Self::verify_signature(
+                message,
+                header,
+                signature,
+                &record,
+                &resolver.dkim_policy,
+                trace,
+            ) {
+                output.push(err.with_dnssec(dnssec));
                 continue;
             }
 
@@ -135,13 +276,19 @@ impl DkimVerifier {
                     match resolver.txt_lookup::<Atps>(query_domain).await {
                         Ok(_) => {
                             // ATPS Verification successful
-                            output.push(DkimOutput::pass().with_atps().with_signature(signature));
+                            output.push(
+                                DkimOutput::pass()
+                                    .with_atps()
+                                    .with_signature(signature)
+                                    .with_dnssec(dnssec),
+                            );
                         }
                         Err(err) => {
                             output.push(
                                 DkimOutput::dns_error(err)
                                     .with_atps()
-                                    .with_signature(signature),
+                                    .with_signature(signature)
+                                    .with_dnssec(dnssec),
                             );
                         }
                     }
@@ -150,7 +297,11 @@ impl DkimVerifier {
             }
 
             // Verification successful
-            output.push(DkimOutput::pass().with_signature(signature));
+            output.push(
+                DkimOutput::pass()
+                    .with_signature(signature)
+                    .with_dnssec(dnssec),
+            );
         }
 
         // Handle reports
@@ -185,6 +336,7 @@ impl DkimVerifier {
                 dkim.report = match &dkim.result() {
                     DkimResult::Neutral(err)
                     | DkimResult::Fail(err)
+                    | DkimResult::Policy(err)
                     | DkimResult::PermError(err)
                     | DkimResult::TempError(err) => {
                         let send_report = match err {
@@ -198,7 +350,8 @@ impl DkimVerifier {
                             | Error::UnsupportedAlgorithm
                             | Error::UnsupportedCanonicalization
                             | Error::UnsupportedKeyType
-                            | Error::IncompatibleAlgorithms => (record.rr & RR_SIGNATURE) != 0,
+                            | Error::IncompatibleAlgorithms
+                            | Error::PolicyViolation(_) => (record.rr & RR_SIGNATURE) != 0,
                             Error::SignatureExpired => (record.rr & RR_EXPIRATION) != 0,
                             Error::DnsError(_)
                             | Error::DnsRecordNotFound(_)
@@ -229,6 +382,115 @@ impl DkimVerifier {
 
         output
     }
+
+    fn verify_body<'x>(
+        message: &'x AuthenticatedMessage<'x>,
+        header: &'x Header<'x, crate::Result<Signature>>,
+        now: u64,
+        trace: Option<&mut TraceRecord<'x>>,
+    ) -> Result<&'x Signature, DkimOutput<'x>> {
+        let signature = match &header.header {
+            Ok(signature) => {
+                if signature.x == 0 || (signature.x > signature.t && signature.x > now) {
+                    signature
+                } else {
+                    return Err(
+                        DkimOutput::neutral(Error::SignatureExpired).with_signature(signature)
+                    );
+                }
+            }
+            Err(err) => {
+                return Err(DkimOutput::neutral(err.clone()));
+            }
+        };
+
+        // Validate body hash
+        let ha = HashAlgorithm::from(signature.a);
+        let mut bh = &message
+            .body_hashes
+            .iter()
+            .find(|(c, h, l, _)| c == &signature.cb && h == &ha && l == &signature.l)
+            .unwrap()
+            .3;
+        if let Some(trace) = trace {
+            // When tracing, the canonical body is recorded as it is hashed
+            trace.computed_body_hash = trace
+                .hash_body(message, signature)
+                .unwrap_or_else(|| bh.clone());
+            bh = &trace.computed_body_hash;
+        }
+
+        if bh != &signature.bh {
+            return Err(DkimOutput::neutral(Error::FailedBodyHashMatch).with_signature(signature));
+        }
+
+        Ok(signature)
+    }
+
+    fn verify_signature<'x>(
+        message: &'x AuthenticatedMessage<'x>,
+        header: &'x Header<'x, crate::Result<Signature>>,
+        signature: &'x Signature,
+        record: &DomainKey,
+        policy: &DkimPolicy,
+        mut trace: Option<&mut TraceRecord<'x>>,
+    ) -> Option<DkimOutput<'x>> {
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.public_key = Some(record.public_key.clone());
+        }
+
+        // Enforce t=s flag
+        if !signature.validate_auid(record) {
+            return Some(DkimOutput::fail(Error::FailedAuidMatch).with_signature(signature));
+        }
+
+        // Reject algorithms and keys not allowed by the policy
+        if let Err(err) = policy.check(signature.a, record) {
+            return Some(DkimOutput::policy(err).with_signature(signature));
+        }
+
+        // Hash headers
+        let dkim_hdr_value = header.value.strip_signature();
+        let result = if let Some(trace) = trace {
+            // Keep the headers, ending with the DKIM-Signature without its b=
+            // value, and verify the signature over the recorded copy.
+            trace.signed_headers = message
+                .signed_headers(&signature.h, header.name, b"")
+                .map(|(name, value)| (name, Cow::Borrowed(value)))
+                .collect();
+            if let Some((_, value)) = trace.signed_headers.last_mut() {
+                *value = Cow::Owned(dkim_hdr_value);
+            }
+            signature.ch.canonicalize_headers(
+                trace
+                    .signed_headers
+                    .iter()
+                    .map(|(name, value)| (*name, value.as_ref())),
+                &mut trace.canonical_headers,
+            );
+            trace.header_hash = HashAlgorithm::from(signature.a)
+                .hash(trace.canonical_headers.as_slice())
+                .as_ref()
+                .to_vec();
+
+            record.verify(
+                &mut trace
+                    .signed_headers
+                    .iter()
+                    .map(|(name, value)| (*name, value.as_ref())),
+                signature,
+                signature.ch,
+            )
+        } else {
+            let mut headers = message.signed_headers(&signature.h, header.name, &dkim_hdr_value);
+            record.verify(&mut headers, signature, signature.ch)
+        };
+
+        // Verify signature
+        result
+            .err()
+            .map(|err| DkimOutput::fail(err).with_signature(signature))
+    }
 }
 
 impl<'x> AuthenticatedMessage<'x> {
@@ -267,7 +529,7 @@ impl<'x> AuthenticatedMessage<'x> {
         headers: &'x [String],
         dkim_hdr_name: &'x [u8],
         dkim_hdr_value: &'x [u8],
-    ) -> impl Iterator<Item=(&'x [u8], &'x [u8])> {
+    ) -> impl Iterator<Item = (&'x [u8], &'x [u8])> {
         let mut last_header_pos: Vec<(&[u8], usize)> = Vec::new();
         headers
             .iter()
@@ -383,14 +645,13 @@ mod test {
         time::{Duration, Instant},
     };
 
+    use crate::dkim::verify::DkimVerifier;
     use crate::{
         common::{parse::TxtRecordParser, verify::DomainKey},
-        dkim::verify::Verifier,
-        AuthenticatedMessage, DkimResult, Resolver,
+        dkim::verify::{DkimKeys, Verifier},
+        AuthenticatedMessage, DkimResult, Error, Resolver,
     };
-    use crate::dkim::verify::DkimVerifier;
 
-    #[ignore]
     #[tokio::test]
     async fn dkim_verify() {
         let mut test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -406,16 +667,70 @@ mod test {
 
             let test = String::from_utf8(fs::read(&file_name).unwrap()).unwrap();
             let (dns_records, raw_message) = test.split_once("\n\n").unwrap();
-            let resolver = new_resolver(dns_records);
             let raw_message = raw_message.replace('\n', "\r\n");
             let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
 
+            // Verify as of the signing time, some test signatures have expired
+            let signed_at = message
+                .dkim_headers
+                .last()
+                .and_then(|h| h.header.as_ref().ok())
+                .map(|s| s.t)
+                .unwrap();
+            let resolver = new_resolver(dns_records).with_clock(signed_at);
+
             let dkim = DkimVerifier::verify_dkim(&resolver, &message).await;
 
             assert_eq!(dkim.last().unwrap().result(), &DkimResult::Pass);
         }
     }
 
+    #[tokio::test]
+    async fn dkim_verify_with_keys() {
+        let mut test_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        test_dir.push("resources");
+        test_dir.push("dkim");
+
+        for file_name in fs::read_dir(&test_dir).unwrap() {
+            let file_name = file_name.unwrap().path();
+            let test = String::from_utf8(fs::read(&file_name).unwrap()).unwrap();
+            let (dns_records, raw_message) = test.split_once("\n\n").unwrap();
+            let resolver = new_resolver(dns_records);
+            let raw_message = raw_message.replace('\n', "\r\n");
+            let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
+
+            let mut keys = DkimKeys::new();
+            for (key, value) in dns_records
+                .split('\n')
+                .filter_map(|r| r.split_once(' ').map(|(a, b)| (a, b.as_bytes())))
+            {
+                let (selector, domain) = key.split_once("._domainkey.").unwrap();
+                keys.add_txt(selector, domain, value);
+            }
+
+            // Offline verification must match the DNS based one
+            assert_eq!(
+                DkimVerifier::verify_dkim_with_keys(&keys, &message),
+                DkimVerifier::verify_dkim(&resolver, &message).await,
+                "{}",
+                file_name.display()
+            );
+
+            // Missing keys are reported as not found
+            for output in DkimVerifier::verify_dkim_with_keys(&DkimKeys::new(), &message) {
+                assert!(
+                    matches!(
+                        output.result(),
+                        DkimResult::PermError(Error::DnsRecordNotFound(_)) | DkimResult::Neutral(_)
+                    ),
+                    "{}: {:?}",
+                    file_name.display(),
+                    output.result()
+                );
+            }
+        }
+    }
+
     #[test]
     fn dkim_strip_signature() {
         for (value, stripped_value) in [
@@ -432,7 +747,7 @@ mod test {
     }
 
     fn new_resolver(dns_records: &str) -> Resolver {
-        let resolver = Resolver::new_system_conf().unwrap();
+        let resolver = Resolver::new_mock();
         for (key, value) in dns_records
             .split('\n')
             .filter_map(|r| r.split_once(' ').map(|(a, b)| (a, b.as_bytes())))
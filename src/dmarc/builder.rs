@@ -0,0 +1,238 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::Version;
+
+use super::{
+    validate::{parse_uri, DmarcIssue},
+    Alignment, Dmarc, Format, Policy, Psd, Report,
+};
+
+/// Builds a DMARC record, validating it before it is published.
+#[derive(Debug, Clone)]
+pub struct DmarcBuilder {
+    record: Dmarc,
+    issues: Vec<DmarcIssue>,
+    has_np: bool,
+}
+
+impl DmarcBuilder {
+    /// Creates a builder for a record requesting the `p=` policy. All other
+    /// tags default to the values defined in RFC 7489.
+    pub fn new(policy: Policy) -> Self {
+        DmarcBuilder {
+            record: Dmarc {
+                v: Version::V1,
+                adkim: Alignment::Relaxed,
+                aspf: Alignment::Relaxed,
+                fo: Report::All,
+                np: policy,
+                p: policy,
+                psd: Psd::Default,
+                pct: 100,
+                rf: Format::Afrf as u8,
+                ri: 86400,
+                rua: vec![],
+                ruf: vec![],
+                sp: policy,
+                t: false,
+            },
+            issues: vec![],
+            has_np: false,
+        }
+    }
+
+    /// Sets the policy for subdomains, which also applies to non-existent
+    /// subdomains unless [`DmarcBuilder::non_existent_subdomain_policy`] is
+    /// set.
+    pub fn subdomain_policy(mut self, policy: Policy) -> Self {
+        if !self.has_np {
+            self.record.np = policy;
+        }
+        self.record.sp = policy;
+        self
+    }
+
+    /// Sets the policy for non-existent subdomains.
+    pub fn non_existent_subdomain_policy(mut self, policy: Policy) -> Self {
+        self.record.np = policy;
+        self.has_np = true;
+        self
+    }
+
+    /// Sets whether the domain is a public suffix domain.
+    pub fn psd(mut self, psd: Psd) -> Self {
+        self.record.psd = psd;
+        self
+    }
+
+    /// Sets the DKIM identifier alignment mode.
+    pub fn dkim_alignment(mut self, alignment: Alignment) -> Self {
+        self.record.adkim = alignment;
+        self
+    }
+
+    /// Sets the SPF identifier alignment mode.
+    pub fn spf_alignment(mut self, alignment: Alignment) -> Self {
+        self.record.aspf = alignment;
+        self
+    }
+
+    /// Sets the percentage of messages the policy applies to.
+    pub fn pct(mut self, pct: u8) -> Self {
+        self.record.pct = pct;
+        self
+    }
+
+    /// Sets the failure reporting options.
+    pub fn failure_reporting(mut self, fo: Report) -> Self {
+        self.record.fo = fo;
+        self
+    }
+
+    /// Sets the interval between aggregate reports, in seconds.
+    pub fn report_interval(mut self, ri: u32) -> Self {
+        self.record.ri = ri;
+        self
+    }
+
+    /// Adds an aggregate report URI such as `mailto:dmarc@example.org!10m`.
+    pub fn rua(mut self, uri: impl AsRef<str>) -> Self {
+        match parse_uri(uri.as_ref()) {
+            Ok(uri) => self.record.rua.push(uri),
+            Err(issue) => self.issues.push(issue),
+        }
+        self
+    }
+
+    /// Adds a failure report URI such as `mailto:dmarc@example.org`.
+    pub fn ruf(mut self, uri: impl AsRef<str>) -> Self {
+        match parse_uri(uri.as_ref()) {
+            Ok(uri) => self.record.ruf.push(uri),
+            Err(issue) => self.issues.push(issue),
+        }
+        self
+    }
+
+    /// Sets testing mode.
+    pub fn testing(mut self, testing: bool) -> Self {
+        self.record.t = testing;
+        self
+    }
+
+    /// Validates and returns the record, or the errors found. Warnings
+    /// about risky settings are available through [`Dmarc::validate`].
+    pub fn build(self) -> Result<Dmarc, Vec<DmarcIssue>> {
+        let mut issues = self.issues;
+        issues.extend(self.record.validate());
+        issues.retain(|issue| issue.is_error());
+        if issues.is_empty() {
+            Ok(self.record)
+        } else {
+            Err(issues)
+        }
+    }
+
+    /// Validates the record and serializes it into the contents of a TXT
+    /// record.
+    pub fn build_txt(self) -> Result<String, Vec<DmarcIssue>> {
+        self.build().map(|record| record.to_txt())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::parse::TxtRecordParser,
+        dmarc::{validate::DmarcIssue, Alignment, Dmarc, Policy, Psd, Report, URI},
+    };
+
+    use super::DmarcBuilder;
+
+    #[test]
+    fn dmarc_builder() {
+        let record = DmarcBuilder::new(Policy::Reject)
+            .subdomain_policy(Policy::Quarantine)
+            .dkim_alignment(Alignment::Strict)
+            .pct(50)
+            .failure_reporting(Report::Any)
+            .report_interval(3600)
+            .rua("mailto:dmarc@example.org!10m")
+            .rua("mailto:dmarc%2Breports@example.net")
+            .ruf("MAILTO:Forensic@Example.org")
+            .build()
+            .unwrap();
+        assert_eq!(
+            record.rua,
+            vec![
+                URI::new("dmarc@example.org", 10 * 1024 * 1024),
+                URI::new("dmarc+reports@example.net", 0)
+            ]
+        );
+        assert_eq!(record.np, Policy::Quarantine);
+        assert_eq!(Dmarc::parse(record.to_txt().as_bytes()).unwrap(), record);
+
+        assert_eq!(
+            DmarcBuilder::new(Policy::None)
+                .psd(Psd::Yes)
+                .non_existent_subdomain_policy(Policy::Reject)
+                .rua("mailto:dmarc@example.org")
+                .build_txt()
+                .unwrap(),
+            "v=DMARC1; p=none; np=reject; psd=y; rua=mailto:dmarc@example.org"
+        );
+
+        assert_eq!(
+            DmarcBuilder::new(Policy::Reject)
+                .pct(150)
+                .report_interval(60)
+                .rua("https://example.org/dmarc")
+                .ruf("mailto:dmarc@example.org!5q")
+                .build()
+                .unwrap_err(),
+            vec![
+                DmarcIssue::UnsupportedUriScheme {
+                    uri: "https://example.org/dmarc".into()
+                },
+                DmarcIssue::InvalidSizeLimit {
+                    uri: "mailto:dmarc@example.org!5q".into()
+                },
+                DmarcIssue::PctOutOfRange { pct: 150 },
+            ]
+        );
+
+        // The non-existent subdomain policy is kept regardless of the order
+        for (np, sp) in [
+            (Policy::Reject, Policy::Quarantine),
+            (Policy::Quarantine, Policy::Reject),
+        ] {
+            let np_first = DmarcBuilder::new(Policy::Reject)
+                .non_existent_subdomain_policy(np)
+                .subdomain_policy(sp)
+                .build()
+                .unwrap();
+            let sp_first = DmarcBuilder::new(Policy::Reject)
+                .subdomain_policy(sp)
+                .non_existent_subdomain_policy(np)
+                .build()
+                .unwrap();
+            assert_eq!((np_first.np, np_first.sp), (np, sp));
+            assert_eq!(np_first, sp_first);
+        }
+
+        let record = DmarcBuilder::new(Policy::Reject)
+            .report_interval(60)
+            .build()
+            .unwrap();
+        assert!(record
+            .validate()
+            .contains(&DmarcIssue::ReportIntervalOutOfRange { ri: 60 }));
+    }
+}
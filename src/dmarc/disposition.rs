@@ -0,0 +1,300 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use crate::{
+    random_pct,
+    report::{ActionDisposition, PolicyOverride},
+    DmarcOutput, DmarcResult, Error, Resolver,
+};
+
+use super::{Policy, Psd};
+
+/// Source of the random numbers used to select the messages a DMARC policy
+/// applies to when its `pct=` tag is below 100.
+pub trait PctSampler {
+    /// Returns a value between 0 and 99.
+    fn sample(&mut self) -> u8;
+}
+
+/// Samples messages using the generator shared with the SPF and DKIM
+/// reporting rates.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultSampler;
+
+/// Action to take on a message after applying the DMARC policy of its
+/// RFC5322.From domain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmarcDisposition {
+    disposition: ActionDisposition,
+    policy: Policy,
+    reason: Option<PolicyOverride>,
+}
+
+impl Resolver {
+    /// Applies the DMARC policy verified by [`Resolver::verify_dmarc`],
+    /// selecting the `p=`, `sp=` or `np=` policy for the RFC5322.From domain
+    /// and relaxing it when the record has `t=y` or the message is sampled
+    /// out by `pct=`.
+    ///
+    /// The `np=` policy applies when the From domain, or for records with
+    /// `psd=y` its organizational domain, has no A, AAAA or MX records. DNS
+    /// errors during this check are treated as an existing domain.
+    pub async fn dmarc_disposition(
+        &self,
+        output: &DmarcOutput,
+        sampler: &mut impl PctSampler,
+    ) -> DmarcDisposition {
+        let Some(record) = output.dmarc_record() else {
+            return DmarcDisposition::new(ActionDisposition::None, Policy::Unspecified, None);
+        };
+
+        let is_subdomain = !output.domain().eq_ignore_ascii_case(output.policy_domain());
+        if output.dkim_result() == &DmarcResult::Pass || output.spf_result() == &DmarcResult::Pass {
+            let policy = if is_subdomain { record.sp } else { record.p };
+            return DmarcDisposition::new(ActionDisposition::Pass, policy, None);
+        }
+
+        let policy = if !is_subdomain {
+            record.p
+        } else {
+            let domain = if record.psd == Psd::Yes {
+                organizational_domain(output.domain(), output.policy_domain())
+            } else {
+                output.domain()
+            };
+            if self.domain_exists(domain).await {
+                record.sp
+            } else {
+                record.np
+            }
+        };
+
+        let (applied, reason) = match policy {
+            Policy::Quarantine | Policy::Reject if record.t => {
+                (relax(policy), Some(PolicyOverride::PolicyTestMode))
+            }
+            Policy::Quarantine | Policy::Reject
+                if record.pct < 100 && sampler.sample() >= record.pct =>
+            {
+                (relax(policy), Some(PolicyOverride::SampledOut))
+            }
+            _ => (policy, None),
+        };
+
+        DmarcDisposition::new(
+            match applied {
+                Policy::Quarantine => ActionDisposition::Quarantine,
+                Policy::Reject => ActionDisposition::Reject,
+                Policy::None | Policy::Unspecified => ActionDisposition::None,
+            },
+            policy,
+            reason,
+        )
+    }
+
+    async fn domain_exists(&self, domain: &str) -> bool {
+        let results = [
+            self.mx_lookup(domain).await.map(|r| !r.is_empty()),
+            self.ipv4_lookup(domain).await.map(|r| !r.is_empty()),
+            self.ipv6_lookup(domain).await.map(|r| !r.is_empty()),
+        ];
+        results
+            .into_iter()
+            .any(|result| !matches!(result, Ok(false) | Err(Error::DnsRecordNotFound(_))))
+    }
+}
+
+impl DmarcDisposition {
+    fn new(disposition: ActionDisposition, policy: Policy, reason: Option<PolicyOverride>) -> Self {
+        DmarcDisposition {
+            disposition,
+            policy,
+            reason,
+        }
+    }
+
+    /// Returns the action to take on the message.
+    pub fn disposition(&self) -> ActionDisposition {
+        self.disposition
+    }
+
+    /// Returns the policy requested by the domain owner for the message,
+    /// before any override.
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// Returns the reason the applied disposition is less strict than the
+    /// requested policy.
+    pub fn reason(&self) -> Option<PolicyOverride> {
+        self.reason
+    }
+}
+
+impl PctSampler for DefaultSampler {
+    fn sample(&mut self) -> u8 {
+        random_pct()
+    }
+}
+
+impl<F: FnMut() -> u8> PctSampler for F {
+    fn sample(&mut self) -> u8 {
+        self() % 100
+    }
+}
+
+/// Returns the policy one level less strict than `policy`.
+fn relax(policy: Policy) -> Policy {
+    match policy {
+        Policy::Reject => Policy::Quarantine,
+        Policy::Quarantine => Policy::None,
+        policy => policy,
+    }
+}
+
+/// Returns the domain one label below `public_suffix` in `domain`.
+fn organizational_domain<'x>(domain: &'x str, public_suffix: &str) -> &'x str {
+    let labels = public_suffix.split('.').count() + 1;
+    match domain.rmatch_indices('.').nth(labels - 1) {
+        Some((pos, _)) => &domain[pos + 1..],
+        None => domain,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use crate::{
+        common::dns::DnsZone,
+        dmarc::Policy,
+        report::{ActionDisposition, PolicyOverride},
+        AuthenticatedMessage, DkimOutput, Resolver, SpfOutput, SpfResult,
+    };
+
+    use super::organizational_domain;
+
+    #[tokio::test]
+    async fn dmarc_disposition() {
+        let zone = DnsZone::new();
+        zone.add_txt(
+            "_dmarc.example.org.",
+            "v=DMARC1; p=reject; sp=quarantine; np=reject; pct=40",
+        );
+        zone.add_txt("_dmarc.example.com.", "v=DMARC1; p=reject; t=y");
+        zone.add_txt(
+            "_dmarc.example.",
+            "v=DMARC1; p=none; sp=quarantine; np=reject; psd=y",
+        );
+        zone.add_ipv4("mail.example.org.", Ipv4Addr::new(192, 0, 2, 1));
+        zone.add_mx("bank.example.", 10, "mx.bank.example.");
+        let resolver = Resolver::with_backend(zone, 128);
+
+        for (from, spf, sample, expected_disposition, expected_policy, expected_reason) in [
+            (
+                "example.org",
+                SpfResult::Pass,
+                0,
+                ActionDisposition::Pass,
+                Policy::Reject,
+                None,
+            ),
+            (
+                "example.org",
+                SpfResult::Fail,
+                39,
+                ActionDisposition::Reject,
+                Policy::Reject,
+                None,
+            ),
+            (
+                "example.org",
+                SpfResult::Fail,
+                40,
+                ActionDisposition::Quarantine,
+                Policy::Reject,
+                Some(PolicyOverride::SampledOut),
+            ),
+            (
+                "mail.example.org",
+                SpfResult::Fail,
+                0,
+                ActionDisposition::Quarantine,
+                Policy::Quarantine,
+                None,
+            ),
+            (
+                "nx.example.org",
+                SpfResult::Fail,
+                0,
+                ActionDisposition::Reject,
+                Policy::Reject,
+                None,
+            ),
+            (
+                "example.com",
+                SpfResult::Fail,
+                0,
+                ActionDisposition::Quarantine,
+                Policy::Reject,
+                Some(PolicyOverride::PolicyTestMode),
+            ),
+            (
+                "www.bank.example",
+                SpfResult::Fail,
+                0,
+                ActionDisposition::Quarantine,
+                Policy::Quarantine,
+                None,
+            ),
+            (
+                "www.nx.example",
+                SpfResult::Fail,
+                0,
+                ActionDisposition::Reject,
+                Policy::Reject,
+                None,
+            ),
+            (
+                "example.net",
+                SpfResult::Fail,
+                0,
+                ActionDisposition::None,
+                Policy::Unspecified,
+                None,
+            ),
+        ] {
+            let raw_message = format!("From: hello@{from}\r\n\r\nTest\r\n");
+            let message = AuthenticatedMessage::parse(raw_message.as_bytes()).unwrap();
+            let spf_output = SpfOutput::new(from.to_string()).with_result(spf);
+            let dmarc_output = resolver
+                .verify_dmarc(&message, &[] as &[DkimOutput], from, &spf_output, |d| {
+                    psl::domain_str(d).unwrap_or(d)
+                })
+                .await;
+            let disposition = resolver
+                .dmarc_disposition(&dmarc_output, &mut || sample)
+                .await;
+
+            assert_eq!(disposition.disposition(), expected_disposition, "{from}");
+            assert_eq!(disposition.policy(), expected_policy, "{from}");
+            assert_eq!(disposition.reason(), expected_reason, "{from}");
+        }
+
+        assert_eq!(
+            organizational_domain("a.b.bank.example", "example"),
+            "bank.example"
+        );
+        assert_eq!(
+            organizational_domain("bank.example", "example"),
+            "bank.example"
+        );
+    }
+}
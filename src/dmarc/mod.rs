@@ -12,12 +12,18 @@ use std::{fmt::Display, sync::Arc};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{DmarcOutput, DmarcResult, Error, Version};
+use crate::{DmarcOutput, DmarcResult, DnssecStatus, Error, Version};
 
+pub mod builder;
+pub mod disposition;
+pub mod organizational;
 pub mod parse;
+pub mod serialize;
+pub mod validate;
 pub mod verify;
 
 #[derive(Debug, Hash, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Dmarc {
     pub v: Version,
     pub adkim: Alignment,
@@ -43,12 +49,14 @@ pub struct URI {
 }
 
 #[derive(Debug, Hash, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Alignment {
     Relaxed,
     Strict,
 }
 
 #[derive(Debug, Hash, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Psd {
     Yes,
     No,
@@ -56,6 +64,7 @@ pub enum Psd {
 }
 
 #[derive(Debug, Hash, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Report {
     All,
     Any,
@@ -65,6 +74,7 @@ pub enum Report {
 }
 
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Policy {
     None,
     Quarantine,
@@ -116,10 +126,12 @@ impl Default for DmarcOutput {
     fn default() -> Self {
         Self {
             domain: String::new(),
+            policy_domain: String::new(),
             policy: Policy::None,
             record: None,
             spf_result: DmarcResult::None,
             dkim_result: DmarcResult::None,
+            dnssec: None,
         }
     }
 }
@@ -145,6 +157,11 @@ impl DmarcOutput {
         self
     }
 
+    pub(crate) fn with_dnssec(mut self, dnssec: Option<DnssecStatus>) -> Self {
+        self.dnssec = dnssec;
+        self
+    }
+
     pub fn domain(&self) -> &str {
         &self.domain
     }
@@ -153,6 +170,12 @@ impl DmarcOutput {
         self.domain
     }
 
+    /// Returns the domain at which the DMARC record was found, which is
+    /// either the RFC5322.From domain or one of its parent domains.
+    pub fn policy_domain(&self) -> &str {
+        &self.policy_domain
+    }
+
     pub fn policy(&self) -> Policy {
         self.policy
     }
@@ -173,10 +196,16 @@ impl DmarcOutput {
         self.record.clone()
     }
 
+    /// Returns the DNSSEC status of the DMARC record, `None` if it was not
+    /// validated.
+    pub fn dnssec(&self) -> Option<DnssecStatus> {
+        self.dnssec
+    }
+
     pub fn requested_reports(&self) -> bool {
         self.record
             .as_ref()
-            .map_or(false, |r| !r.rua.is_empty() || !r.ruf.is_empty())
+            .is_some_and(|r| !r.rua.is_empty() || !r.ruf.is_empty())
     }
 
     /// Returns the failure reporting options
@@ -0,0 +1,257 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::path::Path;
+
+use ahash::AHashSet;
+
+use crate::{common::dns::DnsFuture, Error, Resolver};
+
+use super::Psd;
+
+/// Discovers the organizational domain of a domain name, used to check
+/// relaxed DMARC identifier alignment.
+pub trait OrganizationalDomain: Send + Sync {
+    /// Returns the organizational domain of `domain`, or `domain` itself when
+    /// it has none.
+    fn organizational_domain<'x>(
+        &'x self,
+        resolver: &'x Resolver,
+        domain: &'x str,
+    ) -> DnsFuture<'x, String>;
+}
+
+/// Organizational domain discovery based on the Public Suffix List, as
+/// described in RFC 7489, section 3.2.
+#[derive(Debug, Clone, Default)]
+pub struct PublicSuffixList {
+    rules: AHashSet<String>,
+    wildcards: AHashSet<String>,
+    exceptions: AHashSet<String>,
+}
+
+/// Organizational domain discovery based on the DNS tree walk defined by
+/// DMARCbis, which uses the `psd=` tag of the DMARC records published
+/// between the domain and the root instead of the Public Suffix List.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DnsTreeWalk;
+
+impl PublicSuffixList {
+    /// Parses a list in the `public_suffix_list.dat` format. Rules with
+    /// Unicode labels are stored as A-labels, matching the form domains take
+    /// in DNS and in mail headers. Rules that cannot be converted are skipped.
+    pub fn parse(list: &str) -> Self {
+        let mut psl = PublicSuffixList::default();
+        for line in list.lines() {
+            let rule = match line.split_whitespace().next() {
+                Some(rule) if !rule.starts_with("//") => rule,
+                _ => continue,
+            };
+            let (rules, rule) = if let Some(rule) = rule.strip_prefix('!') {
+                (&mut psl.exceptions, rule)
+            } else if let Some(rule) = rule.strip_prefix("*.") {
+                (&mut psl.wildcards, rule)
+            } else {
+                (&mut psl.rules, rule)
+            };
+            if let Ok(rule) = idna::domain_to_ascii(rule) {
+                rules.insert(rule);
+            }
+        }
+        psl
+    }
+
+    /// Loads a `public_suffix_list.dat` file.
+    pub fn from_file(path: impl AsRef<Path>) -> crate::Result<Self> {
+        std::fs::read_to_string(path)
+            .map(|list| PublicSuffixList::parse(&list))
+            .map_err(Error::from)
+    }
+
+    /// Returns the public suffix of `domain`, applying the implicit `*` rule
+    /// when no rule matches.
+    pub fn public_suffix<'x>(&self, domain: &'x str) -> &'x str {
+        let domain = domain.trim_end_matches('.');
+        let lowercase = domain.to_ascii_lowercase();
+        let mut suffix = lowercase.as_str();
+
+        loop {
+            let parent = suffix.split_once('.').map(|(_, parent)| parent);
+            if self.exceptions.contains(suffix) {
+                if let Some(parent) = parent {
+                    return &domain[domain.len() - parent.len()..];
+                }
+            }
+            if self.rules.contains(suffix) || parent.is_some_and(|p| self.wildcards.contains(p)) {
+                return &domain[domain.len() - suffix.len()..];
+            }
+            match parent {
+                Some(parent) => suffix = parent,
+                None => return &domain[domain.len() - suffix.len()..],
+            }
+        }
+    }
+
+    /// Returns the public suffix of `domain` plus one label, or `None` if
+    /// `domain` is itself a public suffix.
+    pub fn registrable_domain<'x>(&self, domain: &'x str) -> Option<&'x str> {
+        let domain = domain.trim_end_matches('.');
+        let suffix = self.public_suffix(domain);
+        let prefix = domain.strip_suffix(suffix)?.strip_suffix('.')?;
+        let label_start = prefix.rfind('.').map_or(0, |pos| pos + 1);
+        Some(&domain[label_start..])
+    }
+}
+
+impl OrganizationalDomain for PublicSuffixList {
+    fn organizational_domain<'x>(
+        &'x self,
+        _: &'x Resolver,
+        domain: &'x str,
+    ) -> DnsFuture<'x, String> {
+        Box::pin(async move {
+            Ok(self
+                .registrable_domain(domain)
+                .unwrap_or(domain)
+                .to_lowercase())
+        })
+    }
+}
+
+impl OrganizationalDomain for DnsTreeWalk {
+    fn organizational_domain<'x>(
+        &'x self,
+        resolver: &'x Resolver,
+        domain: &'x str,
+    ) -> DnsFuture<'x, String> {
+        Box::pin(async move { resolver.organizational_domain_tree_walk(domain).await })
+    }
+}
+
+impl Resolver {
+    /// Walks the DMARC records from `domain` up to its top-level domain. The
+    /// organizational domain is the one with the fewest labels that publishes
+    /// a record, unless a record with `psd=n` is found first, or a record
+    /// with `psd=y` marks the domain one label below it as organizational.
+    async fn organizational_domain_tree_walk(&self, domain: &str) -> crate::Result<String> {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        let mut org_domain = None;
+
+        let found = self
+            .dmarc_tree_walk_with(&domain, |name, dmarc, _| match dmarc.psd {
+                Psd::No => Some(name.to_string()),
+                Psd::Yes if name.len() < domain.len() => {
+                    let prefix = &domain[..domain.len() - name.len() - 1];
+                    let label_start = prefix.rfind('.').map_or(0, |pos| pos + 1);
+                    Some(domain[label_start..].to_string())
+                }
+                Psd::Yes => Some(name.to_string()),
+                Psd::Default => {
+                    org_domain = Some(name.to_string());
+                    None
+                }
+            })
+            .await?;
+
+        Ok(found.or(org_domain).unwrap_or(domain))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use crate::{common::parse::TxtRecordParser, dmarc::Dmarc, Resolver};
+
+    use super::{DnsTreeWalk, OrganizationalDomain, PublicSuffixList};
+
+    const LIST: &str = concat!(
+        "// ===BEGIN ICANN DOMAINS===\n",
+        "com\n",
+        "uk\n",
+        "co.uk\n",
+        "\n",
+        "// jp : https://en.wikipedia.org/wiki/.jp\n",
+        "jp\n",
+        "*.kawasaki.jp\n",
+        "!city.kawasaki.jp\n",
+        "cn\n",
+        "公司.cn\n",
+        "// ===BEGIN PRIVATE DOMAINS===\n",
+        "blogspot.com\n",
+    );
+
+    #[tokio::test]
+    async fn public_suffix_list() {
+        let psl = PublicSuffixList::parse(LIST);
+        let resolver = Resolver::new_mock();
+
+        for (domain, suffix, org_domain) in [
+            ("example.com", "com", "example.com"),
+            ("www.Example.COM.", "COM", "example.com"),
+            ("com", "com", "com"),
+            ("a.b.example.co.uk", "co.uk", "example.co.uk"),
+            ("foo.blogspot.com", "blogspot.com", "foo.blogspot.com"),
+            (
+                "www.foo.kawasaki.jp",
+                "foo.kawasaki.jp",
+                "www.foo.kawasaki.jp",
+            ),
+            ("www.city.kawasaki.jp", "kawasaki.jp", "city.kawasaki.jp"),
+            ("mail.example.unknown", "unknown", "example.unknown"),
+            (
+                "www.example.xn--55qx5d.cn",
+                "xn--55qx5d.cn",
+                "example.xn--55qx5d.cn",
+            ),
+        ] {
+            assert_eq!(psl.public_suffix(domain), suffix, "{domain}");
+            assert_eq!(
+                psl.organizational_domain(&resolver, domain).await.unwrap(),
+                org_domain,
+                "{domain}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn dns_tree_walk() {
+        let resolver = Resolver::new_mock();
+        let valid_until = Instant::now() + Duration::new(3200, 0);
+        for (name, record) in [
+            ("_dmarc.example.org.", "v=DMARC1; p=reject"),
+            ("_dmarc.org.", "v=DMARC1; p=none; psd=y"),
+            ("_dmarc.example.com.", "v=DMARC1; p=reject"),
+            ("_dmarc.mail.example.com.", "v=DMARC1; p=reject; psd=n"),
+            ("_dmarc.example.net.", "v=DMARC1; p=reject"),
+            ("_dmarc.a.b.c.d.example.net.", "v=DMARC1; p=none"),
+        ] {
+            resolver.txt_add(name, Dmarc::parse(record.as_bytes()).unwrap(), valid_until);
+        }
+
+        for (domain, org_domain) in [
+            ("example.org", "example.org"),
+            ("a.b.example.org", "example.org"),
+            ("www.mail.example.com", "mail.example.com"),
+            ("www.example.com", "example.com"),
+            ("x.y.a.b.c.d.example.net", "example.net"),
+            ("nothing.example", "nothing.example"),
+        ] {
+            assert_eq!(
+                DnsTreeWalk
+                    .organizational_domain(&resolver, domain)
+                    .await
+                    .unwrap(),
+                org_domain,
+                "{domain}"
+            );
+        }
+    }
+}
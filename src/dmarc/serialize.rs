@@ -0,0 +1,224 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::fmt::{self, Display, Formatter, Write};
+
+use super::{Alignment, Dmarc, Policy, Psd, Report, URI};
+
+impl Dmarc {
+    /// Serializes the record into the contents of a TXT record.
+    pub fn to_txt(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for Dmarc {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("v=DMARC1")?;
+        if self.p != Policy::Unspecified {
+            write!(f, "; p={}", self.p)?;
+        }
+        if self.sp != self.p && self.sp != Policy::Unspecified {
+            write!(f, "; sp={}", self.sp)?;
+        }
+        if self.np != self.sp && self.np != Policy::Unspecified {
+            write!(f, "; np={}", self.np)?;
+        }
+        match self.psd {
+            Psd::Yes => f.write_str("; psd=y")?,
+            Psd::No => f.write_str("; psd=n")?,
+            Psd::Default => (),
+        }
+        if self.adkim != Alignment::Relaxed {
+            write!(f, "; adkim={}", self.adkim)?;
+        }
+        if self.aspf != Alignment::Relaxed {
+            write!(f, "; aspf={}", self.aspf)?;
+        }
+        if self.pct != 100 {
+            write!(f, "; pct={}", self.pct)?;
+        }
+        if self.fo != Report::All {
+            write!(f, "; fo={}", self.fo)?;
+        }
+        if self.ri != 86400 {
+            write!(f, "; ri={}", self.ri)?;
+        }
+        write_uris(f, "rua", &self.rua)?;
+        write_uris(f, "ruf", &self.ruf)?;
+        if self.t {
+            f.write_str("; t=y")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Alignment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_char(match self {
+            Alignment::Relaxed => 'r',
+            Alignment::Strict => 's',
+        })
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Report::All => "0",
+            Report::Any => "1",
+            Report::Dkim => "d",
+            Report::Spf => "s",
+            Report::DkimSpf => "d:s",
+        })
+    }
+}
+
+impl Display for URI {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("mailto:")?;
+        for &ch in self.uri.as_bytes() {
+            match ch {
+                0..=0x20 | b'%' | b',' | b';' | b'!' | 0x7f..=u8::MAX => write!(f, "%{ch:02X}")?,
+                _ => f.write_char(char::from(ch))?,
+            }
+        }
+        if self.max_size > 0 {
+            let mut size = self.max_size;
+            let mut unit = "";
+            for next_unit in ["k", "m", "g", "t"] {
+                if size % 1024 == 0 {
+                    size /= 1024;
+                    unit = next_unit;
+                } else {
+                    break;
+                }
+            }
+            write!(f, "!{size}{unit}")?;
+        }
+        Ok(())
+    }
+}
+
+fn write_uris(f: &mut Formatter<'_>, tag: &str, uris: &[URI]) -> fmt::Result {
+    for (pos, uri) in uris.iter().enumerate() {
+        if pos == 0 {
+            write!(f, "; {tag}=")?;
+        } else {
+            f.write_char(',')?;
+        }
+        uri.fmt(f)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use crate::{
+        common::parse::TxtRecordParser,
+        dmarc::{Alignment, Dmarc, Format, Policy, Psd, Report, URI},
+        Version,
+    };
+
+    fn policy() -> impl Strategy<Value = Policy> {
+        prop_oneof![
+            Just(Policy::None),
+            Just(Policy::Quarantine),
+            Just(Policy::Reject)
+        ]
+    }
+
+    fn alignment() -> impl Strategy<Value = Alignment> {
+        prop_oneof![Just(Alignment::Relaxed), Just(Alignment::Strict)]
+    }
+
+    fn uris() -> impl Strategy<Value = Vec<URI>> {
+        proptest::collection::vec(
+            (
+                "[a-z0-9._+!%,;-]{1,15}@[a-z0-9.-]{1,15}",
+                prop_oneof![
+                    Just(0usize),
+                    1usize..100_000,
+                    (1usize..1000).prop_map(|size| size * 1024 * 1024),
+                ],
+            )
+                .prop_map(|(uri, max_size)| URI { uri, max_size }),
+            0..3,
+        )
+    }
+
+    fn dmarc() -> impl Strategy<Value = Dmarc> {
+        (
+            (policy(), policy(), policy()),
+            (alignment(), alignment()),
+            prop_oneof![
+                Just(Report::All),
+                Just(Report::Any),
+                Just(Report::Dkim),
+                Just(Report::Spf),
+                Just(Report::DkimSpf)
+            ],
+            prop_oneof![Just(Psd::Yes), Just(Psd::No), Just(Psd::Default)],
+            (0u8..=100, any::<u32>(), any::<bool>()),
+            (uris(), uris()),
+        )
+            .prop_map(
+                |((p, sp, np), (adkim, aspf), fo, psd, (pct, ri, t), (rua, ruf))| Dmarc {
+                    v: Version::V1,
+                    adkim,
+                    aspf,
+                    fo,
+                    np,
+                    p,
+                    psd,
+                    pct,
+                    rf: Format::Afrf as u8,
+                    ri,
+                    rua,
+                    ruf,
+                    sp,
+                    t,
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn dmarc_roundtrip(record in dmarc()) {
+            prop_assert_eq!(Dmarc::parse(record.to_txt().as_bytes()).unwrap(), record);
+        }
+    }
+
+    #[test]
+    fn dmarc_serialize() {
+        for (record, expected) in [
+            (
+                "v=DMARC1; p=none; rua=mailto:dmarc-feedback@example.com",
+                "v=DMARC1; p=none; rua=mailto:dmarc-feedback@example.com",
+            ),
+            (
+                concat!(
+                    "v=DMARC1; p=reject; ri = 3600; aspf=s; adkim =r; ",
+                    "rua=mailto:dmarc-feedback@example.com!10 K , mailto:user%20@example.com ! 2G;",
+                    "ignore_me= true; fo=s:d; sp=quarantine; pct=50; t=y; psd=n",
+                ),
+                concat!(
+                    "v=DMARC1; p=reject; sp=quarantine; psd=n; aspf=s; pct=50; fo=d:s; ri=3600; ",
+                    "rua=mailto:dmarc-feedback@example.com!10k,mailto:user@example.com!2g; t=y"
+                ),
+            ),
+        ] {
+            let dmarc = Dmarc::parse(record.as_bytes()).unwrap();
+            assert_eq!(dmarc.to_txt(), expected, "{record}");
+        }
+    }
+}
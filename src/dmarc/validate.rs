@@ -0,0 +1,291 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::fmt::Display;
+
+use crate::{common::parse::TxtRecordParser, Error};
+
+use super::{Dmarc, Policy, URI};
+
+/// Shortest reporting interval, in seconds, that receivers are expected to
+/// honor (RFC 7489, section 7.2).
+pub const MIN_REPORT_INTERVAL: u32 = 3600;
+
+/// Reporting interval, in seconds, that all receivers must be able to
+/// honor (RFC 7489, section 7.2).
+pub const MAX_REPORT_INTERVAL: u32 = 86400;
+
+/// Problem found while validating a DMARC record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DmarcIssue {
+    /// The record cannot be parsed.
+    Invalid(Error),
+    /// The record has no `p=` tag.
+    MissingPolicy,
+    /// The `pct=` tag is greater than 100.
+    PctOutOfRange { pct: u8 },
+    /// The `ri=` tag is outside [`MIN_REPORT_INTERVAL`] and
+    /// [`MAX_REPORT_INTERVAL`], so receivers may not honor it.
+    ReportIntervalOutOfRange { ri: u32 },
+    /// A `rua=` or `ruf=` URI uses a scheme other than `mailto:`.
+    UnsupportedUriScheme { uri: String },
+    /// A `rua=` or `ruf=` URI does not contain a valid email address.
+    InvalidUri { uri: String },
+    /// A `rua=` or `ruf=` URI has a malformed `!size` suffix.
+    InvalidSizeLimit { uri: String },
+    /// The policy is `none` and `pct=` is below 100, which has no effect.
+    NonePolicyWithPct { pct: u8 },
+    /// The record does not request aggregate reports.
+    MissingRua,
+    /// The record has `t=y`, so receivers apply a policy one level less
+    /// strict than the one requested.
+    TestingMode,
+}
+
+impl Dmarc {
+    /// Validates the semantic constraints of a parsed DMARC record and
+    /// reports risky settings.
+    pub fn validate(&self) -> Vec<DmarcIssue> {
+        let mut issues = Vec::new();
+
+        if self.p == Policy::Unspecified {
+            issues.push(DmarcIssue::MissingPolicy);
+        }
+        if self.pct > 100 {
+            issues.push(DmarcIssue::PctOutOfRange { pct: self.pct });
+        }
+        if !(MIN_REPORT_INTERVAL..=MAX_REPORT_INTERVAL).contains(&self.ri) {
+            issues.push(DmarcIssue::ReportIntervalOutOfRange { ri: self.ri });
+        }
+        for uri in self.rua.iter().chain(self.ruf.iter()) {
+            if !is_valid_address(&uri.uri) {
+                issues.push(DmarcIssue::InvalidUri {
+                    uri: uri.uri.clone(),
+                });
+            }
+        }
+        if self.p == Policy::None && self.pct < 100 {
+            issues.push(DmarcIssue::NonePolicyWithPct { pct: self.pct });
+        }
+        if self.rua.is_empty() {
+            issues.push(DmarcIssue::MissingRua);
+        }
+        if self.t {
+            issues.push(DmarcIssue::TestingMode);
+        }
+
+        issues
+    }
+
+    /// Validates the text of a DMARC record, reporting the `rua=` and `ruf=`
+    /// URIs that [`Dmarc::parse`] silently discards along with the issues
+    /// found by [`Dmarc::validate`].
+    pub fn validate_txt(record: &[u8]) -> Vec<DmarcIssue> {
+        let mut issues = Vec::new();
+        let text = String::from_utf8_lossy(record);
+        for (name, value) in text.split(';').filter_map(|tag| tag.split_once('=')) {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("rua") || name.eq_ignore_ascii_case("ruf") {
+                for uri in value.split(',') {
+                    if let Err(issue) = parse_uri(uri) {
+                        issues.push(issue);
+                    }
+                }
+            }
+        }
+        match Dmarc::parse(record) {
+            Ok(dmarc) => issues.extend(dmarc.validate()),
+            Err(err) => issues.push(DmarcIssue::Invalid(err)),
+        }
+        issues
+    }
+}
+
+impl DmarcIssue {
+    /// Returns `true` if the issue violates RFC 7489, `false` for warnings
+    /// about risky settings.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            DmarcIssue::Invalid(_)
+                | DmarcIssue::MissingPolicy
+                | DmarcIssue::PctOutOfRange { .. }
+                | DmarcIssue::UnsupportedUriScheme { .. }
+                | DmarcIssue::InvalidUri { .. }
+                | DmarcIssue::InvalidSizeLimit { .. }
+        )
+    }
+}
+
+impl Display for DmarcIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DmarcIssue::Invalid(err) => write!(f, "Invalid DMARC record: {err}"),
+            DmarcIssue::MissingPolicy => f.write_str("Missing \"p\" tag"),
+            DmarcIssue::PctOutOfRange { pct } => {
+                write!(f, "Percentage {pct} is greater than 100")
+            }
+            DmarcIssue::ReportIntervalOutOfRange { ri } => write!(
+                f,
+                "Report interval {ri} is outside {MIN_REPORT_INTERVAL} to {MAX_REPORT_INTERVAL} seconds"
+            ),
+            DmarcIssue::UnsupportedUriScheme { uri } => {
+                write!(f, "Unsupported URI scheme in {uri:?}")
+            }
+            DmarcIssue::InvalidUri { uri } => write!(f, "Invalid report URI {uri:?}"),
+            DmarcIssue::InvalidSizeLimit { uri } => write!(f, "Invalid size limit in {uri:?}"),
+            DmarcIssue::NonePolicyWithPct { pct } => {
+                write!(f, "Percentage {pct} has no effect with \"p=none\"")
+            }
+            DmarcIssue::MissingRua => f.write_str("No aggregate report URIs requested"),
+            DmarcIssue::TestingMode => {
+                f.write_str("Testing mode is enabled, a less strict policy is applied")
+            }
+        }
+    }
+}
+
+/// Parses a `rua=` or `ruf=` URI such as `mailto:reports@example.org!10m`.
+pub(crate) fn parse_uri(text: &str) -> Result<URI, DmarcIssue> {
+    let text = text.trim();
+    let compact = text
+        .chars()
+        .filter(|ch| !ch.is_ascii_whitespace())
+        .collect::<String>();
+    let (uri, size) = match compact.split_once('!') {
+        Some((uri, size)) => (uri, Some(size)),
+        None => (compact.as_str(), None),
+    };
+    let address = match uri.split_once(':') {
+        Some((scheme, address)) if scheme.eq_ignore_ascii_case("mailto") => address,
+        _ => {
+            return Err(DmarcIssue::UnsupportedUriScheme { uri: text.into() });
+        }
+    };
+    let address = percent_decode(address)
+        .filter(|address| is_valid_address(address))
+        .ok_or_else(|| DmarcIssue::InvalidUri { uri: text.into() })?;
+    let max_size = match size {
+        Some(size) => {
+            parse_size(size).ok_or_else(|| DmarcIssue::InvalidSizeLimit { uri: text.into() })?
+        }
+        None => 0,
+    };
+
+    Ok(URI {
+        uri: address.to_lowercase(),
+        max_size,
+    })
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut bytes = text.bytes();
+    while let Some(ch) = bytes.next() {
+        if ch == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let ch = u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?;
+            if !ch.is_ascii_whitespace() {
+                decoded.push(ch);
+            }
+        } else {
+            decoded.push(ch);
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+fn parse_size(size: &str) -> Option<usize> {
+    let (digits, unit) = match size.find(|ch: char| !ch.is_ascii_digit()) {
+        Some(pos) => size.split_at(pos),
+        None => (size, ""),
+    };
+    let multiplier: usize = match unit.to_ascii_lowercase().as_str() {
+        "" => 1,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+fn is_valid_address(address: &str) -> bool {
+    address.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && !domain.is_empty()
+            && !domain.contains('@')
+            && !address.contains(|ch: char| ch.is_whitespace() || ch.is_control())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Dmarc, DmarcIssue};
+
+    #[test]
+    fn dmarc_validate() {
+        for (record, expected_issues) in [
+            (
+                "v=DMARC1; p=reject; rua=mailto:dmarc@example.org!10m",
+                vec![],
+            ),
+            (
+                "v=DMARC1; p=none; pct=50",
+                vec![
+                    DmarcIssue::NonePolicyWithPct { pct: 50 },
+                    DmarcIssue::MissingRua,
+                ],
+            ),
+            (
+                "v=DMARC1; rua=mailto:dmarc@example.org; ri=60; t=y",
+                vec![
+                    DmarcIssue::MissingPolicy,
+                    DmarcIssue::ReportIntervalOutOfRange { ri: 60 },
+                    DmarcIssue::TestingMode,
+                ],
+            ),
+            (
+                concat!(
+                    "v=DMARC1; p=quarantine; rua=https://example.org/dmarc,",
+                    "mailto:dmarc@example.org; ruf=mailto:forensic.example.org"
+                ),
+                vec![
+                    DmarcIssue::UnsupportedUriScheme {
+                        uri: "https://example.org/dmarc".into(),
+                    },
+                    DmarcIssue::InvalidUri {
+                        uri: "mailto:forensic.example.org".into(),
+                    },
+                ],
+            ),
+            (
+                "v=DMARC1; p=reject; rua=mailto:dmarc@example.org!10x",
+                vec![
+                    DmarcIssue::InvalidSizeLimit {
+                        uri: "mailto:dmarc@example.org!10x".into(),
+                    },
+                    DmarcIssue::Invalid(crate::Error::ParseError),
+                ],
+            ),
+            (
+                "v=DMARC2; p=reject",
+                vec![DmarcIssue::Invalid(crate::Error::InvalidRecordType)],
+            ),
+        ] {
+            assert_eq!(
+                Dmarc::validate_txt(record.as_bytes()),
+                expected_issues,
+                "{record}"
+            );
+        }
+    }
+}
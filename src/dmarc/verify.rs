@@ -8,14 +8,14 @@
  * except according to those terms.
  */
 
-use std::sync::Arc;
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
 
 use crate::{
-    AuthenticatedMessage, DkimOutput, DkimResult, DmarcOutput, DmarcResult, Error, Resolver,
-    SpfOutput, SpfResult,
+    AuthenticatedMessage, DkimOutput, DkimResult, DmarcOutput, DmarcResult, DnssecStatus, Error,
+    Resolver, SpfOutput, SpfResult,
 };
 
-use super::{Alignment, Dmarc, URI};
+use super::{organizational::OrganizationalDomain, Alignment, Dmarc, URI};
 
 impl Resolver {
     /// Verifies the DMARC policy of an RFC5321.MailFrom domain
@@ -27,25 +27,92 @@ impl Resolver {
         spf_output: &SpfOutput,
         domain_suffix_fn: impl Fn(&str) -> &str,
     ) -> DmarcOutput {
-        // Extract RFC5322.From domain
-        let mut rfc5322_from_domain = "";
-        for from in &message.from {
-            if let Some((_, domain)) = from.rsplit_once('@') {
-                if rfc5322_from_domain.is_empty() {
-                    rfc5322_from_domain = domain;
-                } else if rfc5322_from_domain != domain {
-                    // Multi-valued RFC5322.From header fields with multiple
-                    // domains MUST be exempt from DMARC checking.
-                    return DmarcOutput::default();
+        self.verify_dmarc_(
+            message,
+            dkim_output,
+            rfc5321_mail_from_domain,
+            spf_output,
+            |domain| Cow::Borrowed(domain_suffix_fn(domain)),
+        )
+        .await
+    }
+
+    /// Verifies the DMARC policy of an RFC5321.MailFrom domain, checking
+    /// relaxed alignment against the organizational domains discovered by
+    /// `org_domain`.
+    pub async fn verify_dmarc_with_org_domain(
+        &self,
+        message: &AuthenticatedMessage<'_>,
+        dkim_output: &[DkimOutput<'_>],
+        rfc5321_mail_from_domain: &str,
+        spf_output: &SpfOutput,
+        org_domain: &(impl OrganizationalDomain + ?Sized),
+    ) -> DmarcOutput {
+        let Some(rfc5322_from_domain) = rfc5322_from_domain(message) else {
+            return DmarcOutput::default();
+        };
+
+        // Discover the organizational domains of the identifiers to align
+        let has_spf_pass = spf_output.result == SpfResult::Pass;
+        let mut org_domains = HashMap::new();
+        if has_spf_pass || dkim_output.iter().any(|o| o.result == DkimResult::Pass) {
+            let domains = [rfc5322_from_domain]
+                .into_iter()
+                .chain(Some(rfc5321_mail_from_domain).filter(|_| has_spf_pass))
+                .chain(
+                    dkim_output
+                        .iter()
+                        .filter(|o| o.result == DkimResult::Pass)
+                        .filter_map(|o| o.signature.as_ref().map(|s| s.d.as_str())),
+                );
+            for domain in domains {
+                if !org_domains.contains_key(domain) {
+                    match org_domain.organizational_domain(self, domain).await {
+                        Ok(org_domain) => {
+                            org_domains.insert(domain, org_domain);
+                        }
+                        Err(err) => {
+                            let err = DmarcResult::from(err);
+                            return DmarcOutput::default()
+                                .with_domain(rfc5322_from_domain)
+                                .with_dkim_result(err.clone())
+                                .with_spf_result(err);
+                        }
+                    }
                 }
             }
         }
-        if rfc5322_from_domain.is_empty() {
+
+        self.verify_dmarc_(
+            message,
+            dkim_output,
+            rfc5321_mail_from_domain,
+            spf_output,
+            |domain| {
+                org_domains
+                    .get(domain)
+                    .map_or(Cow::Borrowed(domain), |org_domain| {
+                        Cow::Owned(org_domain.clone())
+                    })
+            },
+        )
+        .await
+    }
+
+    async fn verify_dmarc_(
+        &self,
+        message: &AuthenticatedMessage<'_>,
+        dkim_output: &[DkimOutput<'_>],
+        rfc5321_mail_from_domain: &str,
+        spf_output: &SpfOutput,
+        domain_suffix_fn: impl for<'a> Fn(&'a str) -> Cow<'a, str>,
+    ) -> DmarcOutput {
+        let Some(rfc5322_from_domain) = rfc5322_from_domain(message) else {
             return DmarcOutput::default();
-        }
+        };
 
         // Obtain DMARC policy
-        let dmarc = match self.dmarc_tree_walk(rfc5322_from_domain).await {
+        let (dmarc, policy_domain, dnssec) = match self.dmarc_tree_walk(rfc5322_from_domain).await {
             Ok(Some(dmarc)) => dmarc,
             Ok(None) => return DmarcOutput::default().with_domain(rfc5322_from_domain),
             Err(err) => {
@@ -61,8 +128,10 @@ impl Resolver {
             spf_result: DmarcResult::None,
             dkim_result: DmarcResult::None,
             domain: rfc5322_from_domain.to_string(),
+            policy_domain,
             policy: dmarc.p,
             record: None,
+            dnssec: None,
         };
 
         let has_dkim_pass = dkim_output.iter().any(|o| o.result == DkimResult::Pass);
@@ -111,7 +180,7 @@ impl Resolver {
             }
         }
 
-        output.with_record(dmarc)
+        output.with_record(dmarc).with_dnssec(dnssec)
     }
 
     /// Validates the external report e-mail addresses of a DMARC record
@@ -147,12 +216,29 @@ impl Resolver {
         result.into()
     }
 
-    async fn dmarc_tree_walk(&self, domain: &str) -> crate::Result<Option<Arc<Dmarc>>> {
-        let labels = domain.split('.').collect::<Vec<_>>();
-        let mut x = labels.len();
-        if x == 1 {
+    async fn dmarc_tree_walk(
+        &self,
+        domain: &str,
+    ) -> crate::Result<Option<(Arc<Dmarc>, String, Option<DnssecStatus>)>> {
+        if !domain.contains('.') {
             return Ok(None);
         }
+        self.dmarc_tree_walk_with(domain, |name, dmarc, dnssec| {
+            Some((dmarc, name.to_string(), dnssec))
+        })
+        .await
+    }
+
+    /// Looks up the DMARC records of `domain` and its parent domains, passing
+    /// each record found to `f` along with the domain that published it,
+    /// until `f` returns a value.
+    pub(super) async fn dmarc_tree_walk_with<T>(
+        &self,
+        domain: &str,
+        mut f: impl FnMut(&str, Arc<Dmarc>, Option<DnssecStatus>) -> Option<T>,
+    ) -> crate::Result<Option<T>> {
+        let labels = domain.split('.').collect::<Vec<_>>();
+        let mut x = labels.len();
         while x != 0 {
             // Build query domain
             let mut domain = String::with_capacity(domain.len() + 8);
@@ -164,9 +250,12 @@ impl Resolver {
             domain.push('.');
 
             // Query DMARC
-            match self.txt_lookup::<Dmarc>(domain).await {
-                Ok(dmarc) => {
-                    return Ok(Some(dmarc));
+            match self.txt_lookup_with_dnssec::<Dmarc>(domain.as_str()).await {
+                Ok((dmarc, dnssec)) => {
+                    let name = &domain["_dmarc.".len()..domain.len() - 1];
+                    if let Some(result) = f(name, dmarc, dnssec) {
+                        return Ok(Some(result));
+                    }
                 }
                 Err(Error::DnsRecordNotFound(_)) | Err(Error::InvalidRecordType) => (),
                 Err(err) => return Err(err),
@@ -186,6 +275,23 @@ impl Resolver {
     }
 }
 
+/// Returns the RFC5322.From domain, or `None` if there is no From address.
+/// Multi-valued From header fields with multiple domains are exempt from
+/// DMARC checking and also return `None`.
+fn rfc5322_from_domain<'x>(message: &'x AuthenticatedMessage<'_>) -> Option<&'x str> {
+    let mut rfc5322_from_domain = None;
+    for from in &message.from {
+        if let Some((_, domain)) = from.rsplit_once('@') {
+            match rfc5322_from_domain {
+                None => rfc5322_from_domain = Some(domain),
+                Some(from_domain) if from_domain != domain => return None,
+                Some(_) => (),
+            }
+        }
+    }
+    rfc5322_from_domain
+}
+
 #[cfg(test)]
 #[allow(unused)]
 mod test {
@@ -194,14 +300,17 @@ mod test {
     use crate::{
         common::parse::TxtRecordParser,
         dkim::Signature,
-        dmarc::{Dmarc, Policy, URI},
+        dmarc::{
+            organizational::{DnsTreeWalk, OrganizationalDomain, PublicSuffixList},
+            Dmarc, Policy, URI,
+        },
         AuthenticatedMessage, DkimOutput, DkimResult, DmarcResult, Error, Resolver, SpfOutput,
         SpfResult,
     };
 
     #[tokio::test]
     async fn dmarc_verify() {
-        let resolver = Resolver::new_system_conf().unwrap();
+        let resolver = Resolver::new_mock();
 
         for (
             dmarc_dns,
@@ -345,12 +454,15 @@ mod test {
                 signature: (&signature).into(),
                 report: None,
                 is_atps: false,
+                dnssec: None,
             };
             let spf = SpfOutput {
                 result: spf,
                 domain: rfc5321_mail_from_domain.to_string(),
                 report: None,
                 explanation: None,
+                dnssec: None,
+                limit: None,
             };
             let result = resolver
                 .verify_dmarc(
@@ -367,9 +479,72 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn dmarc_verify_org_domain() {
+        let resolver = Resolver::new_mock();
+        let valid_until = Instant::now() + Duration::new(3200, 0);
+        for (name, record) in [
+            ("_dmarc.example.co.uk.", "v=DMARC1; p=reject"),
+            ("_dmarc.co.uk.", "v=DMARC1; p=none; psd=y"),
+        ] {
+            resolver.txt_add(name, Dmarc::parse(record.as_bytes()).unwrap(), valid_until);
+        }
+        let psl = PublicSuffixList::parse("uk\nco.uk\n");
+        let auth_message =
+            AuthenticatedMessage::parse(b"From: hello@example.co.uk\r\n\r\n").unwrap();
+
+        for org_domain in [&psl as &dyn OrganizationalDomain, &DnsTreeWalk] {
+            for (rfc5321_mail_from_domain, signature_domain, expect_spf, expect_dkim) in [
+                (
+                    "bounces.example.co.uk",
+                    "mail.example.co.uk",
+                    DmarcResult::Pass,
+                    DmarcResult::Pass,
+                ),
+                (
+                    "other.co.uk",
+                    "co.uk",
+                    DmarcResult::Fail(Error::NotAligned),
+                    DmarcResult::Fail(Error::NotAligned),
+                ),
+            ] {
+                let signature = Signature {
+                    d: signature_domain.into(),
+                    ..Default::default()
+                };
+                let dkim = DkimOutput {
+                    result: DkimResult::Pass,
+                    signature: (&signature).into(),
+                    report: None,
+                    is_atps: false,
+                    dnssec: None,
+                };
+                let spf = SpfOutput {
+                    result: SpfResult::Pass,
+                    domain: rfc5321_mail_from_domain.to_string(),
+                    report: None,
+                    explanation: None,
+                    dnssec: None,
+                    limit: None,
+                };
+                let result = resolver
+                    .verify_dmarc_with_org_domain(
+                        &auth_message,
+                        &[dkim],
+                        rfc5321_mail_from_domain,
+                        &spf,
+                        org_domain,
+                    )
+                    .await;
+                assert_eq!(result.spf_result, expect_spf, "{rfc5321_mail_from_domain}");
+                assert_eq!(result.dkim_result, expect_dkim, "{signature_domain}");
+            }
+        }
+    }
+
     #[tokio::test]
     async fn dmarc_verify_report_address() {
-        let resolver = Resolver::new_system_conf().unwrap();
+        let resolver = Resolver::new_mock();
         #[cfg(any(test, feature = "test"))]
         resolver.txt_add(
             "example.org._report._dmarc.external.org.",
@@ -9,49 +9,89 @@
  */
 
 #![doc = include_str!("../README.md")]
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+#[cfg(any(feature = "rsa", feature = "ed25519-dalek"))]
+use alloc::string::ToString;
+use alloc::{string::String, vec::Vec};
+use core::{fmt::Display, ops::Range};
+#[cfg(feature = "std")]
 use std::{
     cell::Cell,
-    fmt::Display,
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     sync::Arc,
     time::SystemTime,
 };
 
-use arc::Set;
-use common::{crypto::HashAlgorithm, headers::Header, lru::LruCache, verify::DomainKey};
-use dkim::{Atps, Canonicalization, DomainKeyReport};
-use dmarc::Dmarc;
-use hickory_resolver::{
-    proto::{error::ProtoError, op::ResponseCode},
-    TokioAsyncResolver,
+use arc::{OwnedSet, Set};
+#[cfg(feature = "std")]
+use common::{clock::Clock, dns::DnsBackend, lru::LruCache, verify::DomainKey};
+use common::{
+    crypto::{Algorithm, HashAlgorithm, HashState},
+    headers::Header,
 };
+use dkim::{canonicalize::BodyCanonicalizer, Canonicalization};
+#[cfg(feature = "std")]
+use dkim::{policy::DkimPolicy, Atps, DomainKeyReport};
+#[cfg(feature = "std")]
+use dmarc::Dmarc;
+#[cfg(feature = "std")]
+use hickory_resolver::proto::error::ProtoError;
+#[cfg(feature = "std")]
 use mta_sts::{MtaSts, TlsRpt};
+#[cfg(feature = "std")]
 use parking_lot::Mutex;
-use spf::{Macro, Spf};
+#[cfg(feature = "std")]
+use spf::{Macro, Spf, SpfLimit};
+
+#[cfg(not(feature = "std"))]
+pub use common::response_code::ResponseCode;
+#[cfg(feature = "std")]
+pub(crate) use hickory_resolver::proto::op::ResponseCode;
 
 pub mod arc;
 pub mod common;
 pub mod dkim;
+#[cfg(feature = "std")]
 pub mod dmarc;
+#[cfg(feature = "std")]
 pub mod mta_sts;
+#[cfg(feature = "std")]
 pub mod report;
+#[cfg(feature = "std")]
 pub mod spf;
 
+#[cfg(feature = "std")]
 pub use flate2;
+#[cfg(feature = "std")]
 pub use hickory_resolver;
+#[cfg(feature = "std")]
 pub use zip;
 
+#[cfg(feature = "std")]
 pub struct Resolver {
-    pub(crate) resolver: TokioAsyncResolver,
-    pub(crate) cache_txt: LruCache<String, Txt>,
+    pub(crate) backend: Arc<dyn DnsBackend>,
+    pub(crate) cache_txt: LruCache<String, (Txt, Option<DnssecStatus>)>,
     pub(crate) cache_mx: LruCache<String, Arc<Vec<MX>>>,
     pub(crate) cache_ipv4: LruCache<String, Arc<Vec<Ipv4Addr>>>,
     pub(crate) cache_ipv6: LruCache<String, Arc<Vec<Ipv6Addr>>>,
     pub(crate) cache_ptr: LruCache<IpAddr, Arc<Vec<String>>>,
+    pub(crate) clock: Clock,
+    pub(crate) dkim_policy: DkimPolicy,
 }
 
+/// Blocking counterpart of [`Resolver`] for callers without an async
+/// runtime, see [`common::blocking`].
+#[cfg(feature = "std")]
+pub struct BlockingResolver {
+    pub(crate) resolver: Resolver,
+    pub(crate) runtime: Option<tokio::runtime::Runtime>,
+}
+
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, Copy, Default)]
 pub enum IpLookupStrategy {
     /// Only query for A (Ipv4) records
@@ -67,6 +107,7 @@ pub enum IpLookupStrategy {
     Ipv4thenIpv6,
 }
 
+#[cfg(feature = "std")]
 #[derive(Clone)]
 pub enum Txt {
     Spf(Arc<Spf>),
@@ -80,6 +121,7 @@ pub enum Txt {
     Error(Error),
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MX {
     pub exchanges: Vec<String>,
@@ -100,8 +142,23 @@ pub struct AuthenticatedMessage<'x> {
     pub received_headers_count: usize,
     pub date_header_present: bool,
     pub message_id_header_present: bool,
+    /// Offsets of the parsed header names and values in `raw_message`.
+    pub(crate) header_offsets: Vec<(Range<usize>, Range<usize>)>,
+    /// Length of the message body, which is not part of `raw_message` when
+    /// the body was streamed through [`AuthenticatedMessageBuilder`].
+    pub(crate) body_len: usize,
+}
+
+/// Incremental [`AuthenticatedMessage`] construction for messages whose body
+/// is read in chunks.
+pub struct AuthenticatedMessageBuilder<'x> {
+    message: AuthenticatedMessage<'x>,
+    body_hashers: Vec<(BodyCanonicalizer, HashState)>,
+    /// Body bytes that followed the headers, hashed on the first write.
+    pending_body: &'x [u8],
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, PartialEq, Eq)]
 // Authentication-Results header
 pub struct AuthenticationResults<'x> {
@@ -109,6 +166,68 @@ pub struct AuthenticationResults<'x> {
     pub(crate) auth_results: String,
 }
 
+/// Authentication-Results header field parsed with
+/// [`AuthenticationResultsHeader::parse`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuthenticationResultsHeader {
+    pub authserv_id: String,
+    pub version: Option<u32>,
+    pub results: Vec<MethodResult>,
+}
+
+/// Result of a single authentication method, e.g. `dkim=pass header.d=example.org`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MethodResult {
+    pub method: AuthMethod,
+    pub version: Option<u32>,
+    pub result: AuthResultCode,
+    pub reason: Option<String>,
+    pub properties: Vec<AuthProperty>,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AuthMethod {
+    Dkim,
+    DkimAtps,
+    Spf,
+    Dmarc,
+    Arc,
+    Iprev,
+    Other(String),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AuthResultCode {
+    None,
+    Pass,
+    Fail,
+    SoftFail,
+    Neutral,
+    Policy,
+    TempError,
+    PermError,
+    Other(String),
+}
+
+/// Property of a method result, such as `header.d=example.org`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AuthProperty {
+    pub ptype: String,
+    pub property: String,
+    pub value: String,
+}
+
+#[cfg(feature = "std")]
 #[derive(Debug, Clone, PartialEq, Eq)]
 // Received-SPF header
 pub struct ReceivedSpf {
@@ -116,30 +235,91 @@ pub struct ReceivedSpf {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DkimResult {
     Pass,
     Neutral(crate::Error),
     Fail(crate::Error),
+    /// The signature was rejected by the verifier's [`dkim::policy::DkimPolicy`].
+    Policy(crate::Error),
     PermError(crate::Error),
     TempError(crate::Error),
     None,
 }
 
+/// DNSSEC validation status of a DNS answer, as defined in RFC 4035 section 4.3.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DnssecStatus {
+    /// The answer was validated up to a trust anchor.
+    Secure,
+    /// The answer comes from a zone that is not signed.
+    Insecure,
+    /// The answer is signed but its signatures failed validation.
+    Bogus,
+    /// Validation could not be completed, for example because of a timeout.
+    Indeterminate,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DkimOutput<'x> {
     result: DkimResult,
     signature: Option<&'x dkim::Signature>,
     report: Option<String>,
     is_atps: bool,
+    dnssec: Option<DnssecStatus>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct ArcOutput<'x> {
     result: DkimResult,
     set: Vec<Set<'x>>,
 }
 
+/// Owned counterpart of [`AuthenticatedMessage`], see
+/// [`AuthenticatedMessage::into_owned`].
+#[derive(Debug, Clone)]
+pub struct OwnedAuthenticatedMessage {
+    buffer: alloc::sync::Arc<[u8]>,
+    raw_message: Range<usize>,
+    headers: Vec<(Range<usize>, Range<usize>)>,
+    from: Vec<String>,
+    body_offset: usize,
+    body_len: usize,
+    body_hashes: Vec<(Canonicalization, HashAlgorithm, u64, Vec<u8>)>,
+    dkim_headers: Vec<(Range<usize>, Range<usize>, crate::Result<dkim::Signature>)>,
+    ams_headers: Vec<(Range<usize>, Range<usize>, crate::Result<arc::Signature>)>,
+    as_headers: Vec<(Range<usize>, Range<usize>, crate::Result<arc::Seal>)>,
+    aar_headers: Vec<(Range<usize>, Range<usize>, crate::Result<arc::Results>)>,
+    received_headers_count: usize,
+    date_header_present: bool,
+    message_id_header_present: bool,
+}
+
+/// Owned counterpart of [`DkimOutput`], see [`DkimOutput::into_owned`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedDkimOutput {
+    result: DkimResult,
+    signature: Option<dkim::Signature>,
+    report: Option<String>,
+    is_atps: bool,
+    dnssec: Option<DnssecStatus>,
+}
+
+/// Owned counterpart of [`ArcOutput`], see [`ArcOutput::into_owned`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedArcOutput {
+    result: DkimResult,
+    set: Vec<OwnedSet>,
+}
+
+#[cfg(feature = "std")]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SpfResult {
     Pass,
     Fail,
@@ -150,24 +330,34 @@ pub enum SpfResult {
     None,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SpfOutput {
     result: SpfResult,
     domain: String,
     report: Option<String>,
     explanation: Option<String>,
+    dnssec: Option<DnssecStatus>,
+    limit: Option<SpfLimit>,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DmarcOutput {
     spf_result: DmarcResult,
     dkim_result: DmarcResult,
     domain: String,
+    policy_domain: String,
     policy: dmarc::Policy,
     record: Option<Arc<Dmarc>>,
+    dnssec: Option<DnssecStatus>,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DmarcResult {
     Pass,
     Fail(crate::Error),
@@ -176,13 +366,17 @@ pub enum DmarcResult {
     None,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IprevOutput {
     pub result: IprevResult,
     pub ptr: Option<Arc<Vec<String>>>,
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IprevResult {
     Pass,
     Fail(crate::Error),
@@ -192,11 +386,13 @@ pub enum IprevResult {
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Version {
     V1,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Error {
     ParseError,
     MissingParameters,
@@ -216,7 +412,13 @@ pub enum Error {
     SignatureExpired,
     SignatureLength,
     DnsError(String),
-    DnsRecordNotFound(ResponseCode),
+    DnsRecordNotFound(
+        #[cfg_attr(
+            feature = "serde",
+            serde(with = "crate::common::serialize::response_code")
+        )]
+        ResponseCode,
+    ),
     ArcChainTooLong,
     ArcInvalidInstance(u32),
     ArcInvalidCV,
@@ -224,14 +426,33 @@ pub enum Error {
     ArcBrokenChain,
     NotAligned,
     InvalidRecordType,
+    PolicyViolation(PolicyViolation),
 }
 
-pub type Result<T> = std::result::Result<T, Error>;
+/// Reason a DKIM signature was rejected by a [`dkim::policy::DkimPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PolicyViolation {
+    /// The signing algorithm is not allowed.
+    Algorithm(Algorithm),
+    /// The RSA key is shorter than the minimum size, in bits.
+    RsaKeyLength(usize),
+    /// The size of the RSA key could not be determined.
+    RsaKeyInvalid,
+    /// The signature does not use `ed25519-sha256`.
+    Ed25519Required,
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
 
+#[cfg(feature = "std")]
 impl std::error::Error for Error {}
 
+#[cfg(not(feature = "std"))]
+impl core::error::Error for Error {}
+
 impl Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Error::ParseError => write!(f, "Parse error"),
             Error::MissingParameters => write!(f, "Missing parameters"),
@@ -270,12 +491,27 @@ impl Display for Error {
             Error::DnsError(err) => write!(f, "DNS resolution error: {err}"),
             Error::DnsRecordNotFound(code) => write!(f, "DNS record not found: {code}"),
             Error::NotAligned => write!(f, "Policy not aligned"),
+            Error::PolicyViolation(violation) => match violation {
+                PolicyViolation::Algorithm(algorithm) => {
+                    write!(f, "Algorithm {algorithm:?} not allowed by policy")
+                }
+                PolicyViolation::RsaKeyLength(bits) => {
+                    write!(f, "RSA key of {bits} bits is too short for policy")
+                }
+                PolicyViolation::RsaKeyInvalid => {
+                    write!(f, "RSA key size could not be determined for policy")
+                }
+                PolicyViolation::Ed25519Required => {
+                    write!(f, "Policy requires Ed25519 signatures")
+                }
+            },
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl Display for SpfResult {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.write_str(match self {
             SpfResult::Pass => "Pass",
             SpfResult::Fail => "Fail",
@@ -288,8 +524,9 @@ impl Display for SpfResult {
     }
 }
 
+#[cfg(feature = "std")]
 impl Display for IprevResult {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             IprevResult::Pass => f.write_str("pass"),
             IprevResult::Fail(err) => write!(f, "fail; {err}"),
@@ -301,11 +538,12 @@ impl Display for IprevResult {
 }
 
 impl Display for DkimResult {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             DkimResult::Pass => f.write_str("pass"),
             DkimResult::Fail(err) => write!(f, "fail; {err}"),
             DkimResult::Neutral(err) => write!(f, "neutral; {err}"),
+            DkimResult::Policy(err) => write!(f, "policy; {err}"),
             DkimResult::TempError(err) => write!(f, "temp error; {err}"),
             DkimResult::PermError(err) => write!(f, "perm error; {err}"),
             DkimResult::None => f.write_str("none"),
@@ -313,8 +551,45 @@ impl Display for DkimResult {
     }
 }
 
+impl DnssecStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DnssecStatus::Secure => "secure",
+            DnssecStatus::Insecure => "insecure",
+            DnssecStatus::Bogus => "bogus",
+            DnssecStatus::Indeterminate => "indeterminate",
+        }
+    }
+
+    /// Returns the least trustworthy of both statuses, used when a result
+    /// depends on several DNS answers.
+    pub fn weakest(self, other: DnssecStatus) -> DnssecStatus {
+        fn rank(status: DnssecStatus) -> u8 {
+            match status {
+                DnssecStatus::Bogus => 0,
+                DnssecStatus::Indeterminate => 1,
+                DnssecStatus::Insecure => 2,
+                DnssecStatus::Secure => 3,
+            }
+        }
+
+        if rank(other) < rank(self) {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+impl Display for DnssecStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "std")]
 impl Display for DmarcResult {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             DmarcResult::Pass => f.write_str("pass"),
             DmarcResult::Fail(err) => write!(f, "fail; {err}"),
@@ -325,12 +600,14 @@ impl Display for DmarcResult {
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Self {
         Error::Io(err.to_string())
     }
 }
 
+#[cfg(feature = "std")]
 impl From<ProtoError> for Error {
     fn from(err: ProtoError) -> Self {
         Error::DnsError(err.to_string())
@@ -351,6 +628,7 @@ impl From<ed25519_dalek::ed25519::Error> for Error {
     }
 }
 
+#[cfg(feature = "std")]
 impl Default for SpfOutput {
     fn default() -> Self {
         Self {
@@ -358,37 +636,47 @@ impl Default for SpfOutput {
             domain: Default::default(),
             report: Default::default(),
             explanation: Default::default(),
+            dnssec: Default::default(),
+            limit: Default::default(),
         }
     }
 }
 
+#[cfg(feature = "std")]
 thread_local!(static COUNTER: Cell<u64>  = const { Cell::new(0) });
 
-/// Generates a random value between 0 and 100.
-/// Returns true if the generated value is within the requested
+/// Generates a random value between 0 and 99.
+#[cfg(feature = "std")]
+pub(crate) fn random_pct() -> u8 {
+    (COUNTER.with(|c| {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+            .wrapping_add(c.replace(c.get() + 1))
+            .wrapping_mul(11400714819323198485u64)
+    }) % 100) as u8
+}
+
+/// Returns true if a random value is within the requested
 /// sampling percentage specified in a SPF, DKIM or DMARC policy.
+#[cfg(feature = "std")]
 pub(crate) fn is_within_pct(pct: u8) -> bool {
-    pct == 100
-        || COUNTER.with(|c| {
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .map(|d| d.as_secs())
-                .unwrap_or(0)
-                .wrapping_add(c.replace(c.get() + 1))
-                .wrapping_mul(11400714819323198485u64)
-        }) % 100
-            < pct as u64
+    pct == 100 || random_pct() < pct
 }
 
+#[cfg(feature = "std")]
 impl Clone for Resolver {
     fn clone(&self) -> Self {
         Self {
-            resolver: self.resolver.clone(),
+            backend: self.backend.clone(),
             cache_txt: Mutex::new(self.cache_txt.lock().clone()),
             cache_mx: Mutex::new(self.cache_mx.lock().clone()),
             cache_ipv4: Mutex::new(self.cache_ipv4.lock().clone()),
             cache_ipv6: Mutex::new(self.cache_ipv6.lock().clone()),
             cache_ptr: Mutex::new(self.cache_ptr.lock().clone()),
+            clock: self.clock,
+            dkim_policy: self.dkim_policy.clone(),
         }
     }
 }
@@ -11,6 +11,7 @@
 use serde::{Deserialize, Serialize};
 
 pub mod parse;
+pub mod serialize;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct MtaSts {
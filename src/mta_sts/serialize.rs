@@ -0,0 +1,117 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::fmt::{self, Display, Formatter, Write};
+
+use super::{MtaSts, ReportUri, TlsRpt};
+
+impl MtaSts {
+    /// Serializes the record into the contents of a TXT record.
+    pub fn to_txt(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl TlsRpt {
+    /// Serializes the record into the contents of a TXT record.
+    pub fn to_txt(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for MtaSts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "v=STSv1; id={}", self.id)
+    }
+}
+
+impl Display for TlsRpt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("v=TLSRPTv1; rua=")?;
+        for (pos, uri) in self.rua.iter().enumerate() {
+            if pos > 0 {
+                f.write_char(',')?;
+            }
+            uri.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for ReportUri {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let uri = match self {
+            ReportUri::Mail(address) => {
+                f.write_str("mailto:")?;
+                address
+            }
+            ReportUri::Http(url) => url,
+        };
+        for &ch in uri.as_bytes() {
+            match ch {
+                0..=0x20 | b',' | b';' | b'=' | 0x7f => write!(f, "={ch:02X}")?,
+                _ => f.write_char(char::from(ch))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use crate::{
+        common::parse::TxtRecordParser,
+        mta_sts::{MtaSts, ReportUri, TlsRpt},
+    };
+
+    fn tls_rpt() -> impl Strategy<Value = TlsRpt> {
+        proptest::collection::vec(
+            prop_oneof![
+                "[a-zA-Z0-9._+=,;-]{1,15}@[a-z0-9.-]{1,15}".prop_map(ReportUri::Mail),
+                "https://[a-z0-9.-]{1,15}/[a-zA-Z0-9/?&=,;_-]{0,20}".prop_map(ReportUri::Http),
+            ],
+            1..4,
+        )
+        .prop_map(|rua| TlsRpt { rua })
+    }
+
+    proptest! {
+        #[test]
+        fn mta_sts_roundtrip(id in "[a-zA-Z0-9]{1,32}") {
+            let record = MtaSts { id };
+            prop_assert_eq!(MtaSts::parse(record.to_txt().as_bytes()).unwrap(), record);
+        }
+
+        #[test]
+        fn tls_rpt_roundtrip(record in tls_rpt()) {
+            prop_assert_eq!(TlsRpt::parse(record.to_txt().as_bytes()).unwrap(), record);
+        }
+    }
+
+    #[test]
+    fn tls_rpt_serialize() {
+        assert_eq!(
+            TlsRpt::parse(
+                b"v=TLSRPTv1; rua=mailto:tls=20rpt@mydomain.com,https://tlsrpt.mydomain.com/v1"
+            )
+            .unwrap()
+            .to_txt(),
+            "v=TLSRPTv1; rua=mailto:tls=20rpt@mydomain.com,https://tlsrpt.mydomain.com/v1"
+        );
+        assert_eq!(
+            MtaSts::parse(b"v=STSv1; id=20160831085700Z;")
+                .unwrap()
+                .to_txt(),
+            "v=STSv1; id=20160831085700Z"
+        );
+    }
+}
@@ -95,6 +95,7 @@ impl<'x> Feedback<'x> {
         }
     }
 
+    #[allow(clippy::collapsible_match)]
     pub fn parse_arf(arf: &'x [u8]) -> Option<Self> {
         let mut f = Feedback {
             incidents: 1,
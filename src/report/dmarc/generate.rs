@@ -351,6 +351,7 @@ impl Display for PolicyOverride {
             PolicyOverride::TrustedForwarder => "trusted_forwarder",
             PolicyOverride::MailingList => "mailing_list",
             PolicyOverride::LocalPolicy => "local_policy",
+            PolicyOverride::PolicyTestMode => "policy_test_mode",
             PolicyOverride::Other => "other",
         })
     }
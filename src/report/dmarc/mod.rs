@@ -208,6 +208,7 @@ impl Record {
                         (DkimResult::Neutral, err.to_string().into())
                     }
                     crate::DkimResult::Fail(err) => (DkimResult::Fail, err.to_string().into()),
+                    crate::DkimResult::Policy(err) => (DkimResult::Policy, err.to_string().into()),
                     crate::DkimResult::PermError(err) => {
                         (DkimResult::PermError, err.to_string().into())
                     }
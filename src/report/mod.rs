@@ -88,6 +88,7 @@ pub enum PolicyOverride {
     TrustedForwarder,
     MailingList,
     LocalPolicy,
+    PolicyTestMode,
     #[default]
     Other,
 }
@@ -296,6 +297,7 @@ impl From<&crate::DkimResult> for AuthFailureType {
         match value {
             crate::DkimResult::Neutral(err)
             | crate::DkimResult::Fail(err)
+            | crate::DkimResult::Policy(err)
             | crate::DkimResult::PermError(err)
             | crate::DkimResult::TempError(err) => match err {
                 crate::Error::FailedBodyHashMatch => AuthFailureType::BodyHash,
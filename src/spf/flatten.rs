@@ -0,0 +1,487 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{
+    future::Future,
+    net::{Ipv4Addr, Ipv6Addr},
+    pin::Pin,
+    sync::Arc,
+};
+
+use crate::{Error, Resolver};
+
+use super::{Directive, Macro, Mechanism, Qualifier, Spf};
+
+/// Maximum depth of nested `include:` and `redirect=` records that are
+/// expanded, matching the DNS lookup limit of RFC 7208.
+const MAX_DEPTH: usize = 10;
+
+/// Maximum number of MX records an `mx` mechanism may return before its
+/// evaluation fails with a `permerror` (RFC 7208, section 4.6.4). Larger
+/// answers are not expanded so that the flattened record fails the same way.
+const MAX_MX_RECORDS: usize = 10;
+
+struct Flattened {
+    directives: Vec<Directive>,
+    redirect: Option<Macro>,
+    exp: Option<Macro>,
+}
+
+type FlattenFuture<'x> = Pin<Box<dyn Future<Output = crate::Result<Flattened>> + Send + 'x>>;
+
+impl Resolver {
+    /// Fetches the SPF record of `domain` and rewrites it so that it needs
+    /// fewer DNS lookups during evaluation.
+    ///
+    /// `a` and `mx` mechanisms without macros are replaced with `ip4:` and
+    /// `ip6:` directives listing the addresses they currently resolve to, and
+    /// `include:` and `redirect=` terms pointing to a literal domain are
+    /// replaced with the contents of the target record whenever this does not
+    /// change the result of the evaluation. Terms that cannot be expanded, such
+    /// as those containing macros, are kept as is.
+    ///
+    /// If the flattened record is longer than `max_length` bytes, trailing
+    /// pass directives are moved to a chain of records named `_spf1.<domain>`,
+    /// `_spf2.<domain>` and so on, each one included by the previous one. The
+    /// first entry returned is the record to publish at `domain` and each entry
+    /// serializes to TXT with [`Spf::to_txt`]. The main record may still exceed
+    /// `max_length` when its terms that cannot be moved do not fit.
+    pub async fn flatten_spf(
+        &self,
+        domain: &str,
+        max_length: usize,
+    ) -> crate::Result<Vec<(String, Spf)>> {
+        let domain = domain.strip_suffix('.').unwrap_or(domain);
+        let spf = self.txt_lookup::<Spf>(domain).await?;
+        let flattened = self
+            .flatten_record(domain.to_string(), spf.clone(), 0)
+            .await?;
+
+        let mut directives: Vec<Directive> = Vec::with_capacity(flattened.directives.len());
+        for directive in flattened.directives {
+            if !directives.contains(&directive) {
+                directives.push(directive);
+            }
+        }
+
+        let spf = Spf {
+            version: spf.version.clone(),
+            directives,
+            exp: flattened.exp,
+            redirect: flattened.redirect,
+            ra: spf.ra.clone(),
+            rp: spf.rp,
+            rr: spf.rr,
+        };
+
+        Ok(split_records(domain, spf, max_length))
+    }
+
+    fn flatten_record(&self, domain: String, spf: Arc<Spf>, depth: usize) -> FlattenFuture<'_> {
+        Box::pin(async move {
+            let mut directives = Vec::with_capacity(spf.directives.len());
+            let mut has_all = false;
+
+            for directive in &spf.directives {
+                match &directive.mechanism {
+                    Mechanism::A {
+                        macro_string,
+                        ip4_mask,
+                        ip6_mask,
+                    }
+                    | Mechanism::Mx {
+                        macro_string,
+                        ip4_mask,
+                        ip6_mask,
+                    } if matches!(macro_string, Macro::Literal(_) | Macro::None) => {
+                        let name = match macro_string {
+                            Macro::Literal(name) => String::from_utf8_lossy(name).into_owned(),
+                            _ => domain.clone(),
+                        };
+                        let hosts = if matches!(directive.mechanism, Mechanism::Mx { .. }) {
+                            self.mx_lookup(name.as_str())
+                                .await
+                                .or_else(not_found)?
+                                .iter()
+                                .flat_map(|mx| mx.exchanges.iter().cloned())
+                                .collect()
+                        } else {
+                            vec![name]
+                        };
+                        if hosts.len() > MAX_MX_RECORDS {
+                            directives.push(directive.clone());
+                            continue;
+                        }
+
+                        for host in hosts {
+                            for addr in self
+                                .ipv4_lookup(host.as_str())
+                                .await
+                                .or_else(not_found)?
+                                .iter()
+                            {
+                                directives.push(Directive::new(
+                                    directive.qualifier.clone(),
+                                    Mechanism::Ip4 {
+                                        addr: Ipv4Addr::from(u32::from(*addr) & ip4_mask),
+                                        mask: *ip4_mask,
+                                    },
+                                ));
+                            }
+                            for addr in self
+                                .ipv6_lookup(host.as_str())
+                                .await
+                                .or_else(not_found)?
+                                .iter()
+                            {
+                                directives.push(Directive::new(
+                                    directive.qualifier.clone(),
+                                    Mechanism::Ip6 {
+                                        addr: Ipv6Addr::from(u128::from(*addr) & ip6_mask),
+                                        mask: *ip6_mask,
+                                    },
+                                ));
+                            }
+                        }
+                    }
+                    Mechanism::Include {
+                        macro_string: Macro::Literal(target),
+                    } if depth < MAX_DEPTH => {
+                        let target = String::from_utf8_lossy(target).into_owned();
+                        let included = self.txt_lookup::<Spf>(target.as_str()).await?;
+                        let included = self.flatten_record(target, included, depth + 1).await?;
+
+                        // Only the pass directives of an included record can match
+                        match pass_ranges(&included) {
+                            Some(ranges) => {
+                                directives.extend(ranges.into_iter().map(|mechanism| {
+                                    Directive::new(directive.qualifier.clone(), mechanism)
+                                }));
+                            }
+                            None => directives.push(directive.clone()),
+                        }
+                    }
+                    Mechanism::All => {
+                        directives.push(directive.clone());
+                        has_all = true;
+                        break;
+                    }
+                    _ => directives.push(directive.clone()),
+                }
+            }
+
+            let mut redirect = None;
+            let mut exp = spf.exp.clone();
+            if !has_all {
+                match &spf.redirect {
+                    Some(Macro::Literal(target)) if depth < MAX_DEPTH => {
+                        let target = String::from_utf8_lossy(target).into_owned();
+                        let record = self.txt_lookup::<Spf>(target.as_str()).await?;
+                        let flattened = self.flatten_record(target, record, depth + 1).await?;
+
+                        // Terms evaluated against the redirect target must stay there,
+                        // and its explanation replaces ours (RFC 7208, section 6.2)
+                        if flattened.redirect.is_none()
+                            && flattened
+                                .directives
+                                .iter()
+                                .all(|d| is_domain_independent(&d.mechanism))
+                            && matches!(flattened.exp, None | Some(Macro::Literal(_)))
+                            && (spf.exp.is_none()
+                                || flattened.exp.is_none()
+                                || spf.exp == flattened.exp)
+                        {
+                            directives.extend(flattened.directives);
+                            exp = flattened.exp.or(exp);
+                        } else {
+                            redirect = spf.redirect.clone();
+                        }
+                    }
+                    other => redirect = other.clone(),
+                }
+            }
+
+            Ok(Flattened {
+                directives,
+                redirect,
+                exp,
+            })
+        })
+    }
+}
+
+fn not_found<T: Default>(err: Error) -> crate::Result<Arc<T>> {
+    match err {
+        Error::DnsRecordNotFound(_) => Ok(Arc::new(T::default())),
+        err => Err(err),
+    }
+}
+
+/// Returns the address ranges of an included record when all its directives
+/// up to `all` are `ip4:` and `ip6:` pass directives.
+fn pass_ranges(flattened: &Flattened) -> Option<Vec<Mechanism>> {
+    let end = flattened
+        .directives
+        .iter()
+        .position(|d| d.mechanism == Mechanism::All)
+        .unwrap_or(flattened.directives.len());
+    let directives = &flattened.directives[..end];
+
+    if flattened.redirect.is_none()
+        && directives.iter().all(|d| {
+            d.qualifier == Qualifier::Pass
+                && matches!(d.mechanism, Mechanism::Ip4 { .. } | Mechanism::Ip6 { .. })
+        })
+    {
+        Some(directives.iter().map(|d| d.mechanism.clone()).collect())
+    } else {
+        None
+    }
+}
+
+fn is_domain_independent(mechanism: &Mechanism) -> bool {
+    match mechanism {
+        Mechanism::All | Mechanism::Ip4 { .. } | Mechanism::Ip6 { .. } => true,
+        Mechanism::Include { macro_string }
+        | Mechanism::Exists { macro_string }
+        | Mechanism::A { macro_string, .. }
+        | Mechanism::Mx { macro_string, .. }
+        | Mechanism::Ptr { macro_string } => matches!(macro_string, Macro::Literal(_)),
+    }
+}
+
+/// Whether a directive keeps its meaning when moved to an included record,
+/// which only matches on pass.
+fn is_movable(directive: &Directive) -> bool {
+    directive.qualifier == Qualifier::Pass
+        && directive.mechanism != Mechanism::All
+        && is_domain_independent(&directive.mechanism)
+}
+
+fn split_records(domain: &str, mut spf: Spf, max_length: usize) -> Vec<(String, Spf)> {
+    if spf.to_string().len() <= max_length {
+        return vec![(domain.to_string(), spf)];
+    }
+
+    let all = if spf.directives.last().map(|d| &d.mechanism) == Some(&Mechanism::All) {
+        spf.directives.pop()
+    } else {
+        None
+    };
+    let start = spf.directives.len()
+        - spf
+            .directives
+            .iter()
+            .rev()
+            .take_while(|d| is_movable(d))
+            .count();
+    let mut movable = spf.directives.split_off(start).into_iter().peekable();
+    let chain_name = |num: usize| format!("_spf{num}.{domain}");
+    let include = |num: usize| {
+        Directive::new(
+            Qualifier::Pass,
+            Mechanism::Include {
+                macro_string: Macro::Literal(chain_name(num).into_bytes()),
+            },
+        )
+    };
+
+    // Keep as many directives as possible in the main record
+    let mut length = spf.to_string().len()
+        + all.as_ref().map_or(0, |d| d.to_string().len() + 1)
+        + include(1).to_string().len()
+        + 1;
+    while let Some(directive) = movable.peek() {
+        let directive_length = directive.to_string().len() + 1;
+        if length + directive_length <= max_length {
+            length += directive_length;
+            spf.directives.push(movable.next().unwrap());
+        } else {
+            break;
+        }
+    }
+    if movable.peek().is_some() {
+        spf.directives.push(include(1));
+    }
+    spf.directives.extend(all);
+
+    let mut records = vec![(domain.to_string(), spf)];
+    let mut num = 1;
+    while movable.peek().is_some() {
+        let mut record = Spf {
+            version: records[0].1.version.clone(),
+            directives: Vec::new(),
+            exp: None,
+            redirect: None,
+            ra: None,
+            rp: 100,
+            rr: u8::MAX,
+        };
+        let mut length = record.to_string().len() + include(num + 1).to_string().len() + 1;
+        let mut remaining = movable
+            .clone()
+            .map(|d| d.to_string().len() + 1)
+            .sum::<usize>();
+
+        while let Some(directive) = movable.peek() {
+            let directive_length = directive.to_string().len() + 1;
+            if record.directives.is_empty()
+                || length + directive_length <= max_length
+                || length + remaining - include(num + 1).to_string().len() - 1 <= max_length
+            {
+                length += directive_length;
+                remaining -= directive_length;
+                record.directives.push(movable.next().unwrap());
+            } else {
+                break;
+            }
+        }
+        if movable.peek().is_some() {
+            record.directives.push(include(num + 1));
+        }
+
+        records.push((chain_name(num), record));
+        num += 1;
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::{dns::DnsZone, parse::TxtRecordParser},
+        spf::Spf,
+        Resolver, SpfResult,
+    };
+
+    #[tokio::test]
+    async fn spf_flatten() {
+        let zone = DnsZone::new();
+        zone.add_txt(
+            "example.org",
+            "v=spf1 -ip4:198.51.100.1 include:_complex.example.net a mx \
+             include:_spf.example.net redirect=_fallback.example.org",
+        )
+        .add_ipv4("example.org", "192.0.2.1".parse().unwrap())
+        .add_ipv6("example.org", "2001:db8::1".parse().unwrap())
+        .add_mx("example.org", 10, "mx1.example.org")
+        .add_mx("example.org", 20, "mx2.example.org")
+        .add_ipv4("mx1.example.org", "192.0.2.10".parse().unwrap())
+        .add_ipv4("mx2.example.org", "192.0.2.1".parse().unwrap())
+        .add_txt(
+            "_spf.example.net",
+            "v=spf1 ip4:203.0.113.0/24 include:_spf2.example.net ~all",
+        )
+        .add_txt("_spf2.example.net", "v=spf1 a:mail.example.net/28 -all")
+        .add_ipv4("mail.example.net", "203.0.114.17".parse().unwrap())
+        .add_txt("_complex.example.net", "v=spf1 ?ip4:10.0.0.1 ip4:10.0.0.2")
+        .add_txt("_fallback.example.org", "v=spf1 ip6:2001:db8:1::/48 ~all");
+        let resolver = Resolver::with_backend(zone, 128);
+
+        let records = resolver.flatten_spf("example.org", 450).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].0, "example.org");
+        assert_eq!(
+            records[0].1.to_txt(),
+            concat!(
+                "v=spf1 -ip4:198.51.100.1 include:_complex.example.net ip4:192.0.2.1 ",
+                "ip6:2001:db8::1 ip4:192.0.2.10 ip4:203.0.113.0/24 ip4:203.0.114.16/28 ",
+                "ip6:2001:db8:1::/48 ~all"
+            )
+        );
+
+        // The flattened record produces the same results as the original one
+        let zone = DnsZone::new();
+        zone.add_txt("example.org", records[0].1.to_txt())
+            .add_txt("_complex.example.net", "v=spf1 ?ip4:10.0.0.1 ip4:10.0.0.2");
+        let flat_resolver = Resolver::with_backend(zone, 128);
+        for (ip, result) in [
+            ("192.0.2.10", SpfResult::Pass),
+            ("2001:db8::1", SpfResult::Pass),
+            ("203.0.114.20", SpfResult::Pass),
+            ("2001:db8:1::1", SpfResult::Pass),
+            ("10.0.0.2", SpfResult::Pass),
+            ("10.0.0.1", SpfResult::SoftFail),
+            ("198.51.100.1", SpfResult::Fail),
+            ("192.0.2.20", SpfResult::SoftFail),
+        ] {
+            for resolver in [&resolver, &flat_resolver] {
+                assert_eq!(
+                    resolver
+                        .check_host(
+                            ip.parse().unwrap(),
+                            "example.org",
+                            "mx.example.org",
+                            "mx.example.org",
+                            "jdoe@example.org",
+                        )
+                        .await
+                        .result(),
+                    result,
+                    "{ip}"
+                );
+            }
+        }
+
+        // Pass directives that do not fit are moved to chained records
+        let records = resolver.flatten_spf("example.org.", 100).await.unwrap();
+        assert_eq!(
+            records
+                .iter()
+                .map(|(name, spf)| (name.as_str(), spf.to_txt()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "example.org",
+                    concat!(
+                        "v=spf1 -ip4:198.51.100.1 include:_complex.example.net ",
+                        "ip4:192.0.2.1 include:_spf1.example.org ~all"
+                    )
+                    .to_string()
+                ),
+                (
+                    "_spf1.example.org",
+                    concat!(
+                        "v=spf1 ip6:2001:db8::1 ip4:192.0.2.10 ip4:203.0.113.0/24 ",
+                        "ip4:203.0.114.16/28 ip6:2001:db8:1::/48"
+                    )
+                    .to_string()
+                ),
+            ]
+        );
+        for (_, spf) in &records {
+            assert!(spf.to_txt().len() <= 100);
+            assert_eq!(&Spf::parse(spf.to_txt().as_bytes()).unwrap(), spf);
+        }
+
+        // Inlined redirects keep their explanation and MX answers over the
+        // limit are left for the evaluation to reject
+        let zone = DnsZone::new();
+        zone.add_txt("example.com", "v=spf1 mx redirect=_spf.example.com")
+            .add_txt(
+                "_spf.example.com",
+                "v=spf1 ip4:192.0.2.0/24 -all exp=explain.example.com",
+            );
+        for num in 0..11 {
+            zone.add_mx("example.com", num, format!("mx{num}.example.com"))
+                .add_ipv4(
+                    format!("mx{num}.example.com"),
+                    format!("198.51.100.{num}").parse().unwrap(),
+                );
+        }
+        let resolver = Resolver::with_backend(zone, 128);
+        let records = resolver.flatten_spf("example.com", 450).await.unwrap();
+        assert_eq!(
+            records[0].1.to_txt(),
+            "v=spf1 mx ip4:192.0.2.0/24 -all exp=explain.example.com"
+        );
+    }
+}
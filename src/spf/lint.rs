@@ -0,0 +1,792 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{collections::HashSet, fmt::Display};
+
+use crate::{
+    common::{parse::TxtRecordParser, resolver::IntoFqdn},
+    Error, Resolver,
+};
+
+use super::{Directive, Macro, Mechanism, Qualifier, Spf, SpfLimits};
+
+/// Records longer than this may not fit in a UDP DNS response.
+pub const MAX_RECORD_LENGTH: usize = 512;
+
+/// Problem found while analyzing an SPF record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpfIssue {
+    /// The record cannot be parsed and evaluates to `permerror`.
+    Invalid(Error),
+    /// More than one SPF record is published, which evaluates to `permerror`.
+    MultipleRecords,
+    /// The record is longer than [`MAX_RECORD_LENGTH`] bytes.
+    RecordTooLong { length: usize },
+    /// Terms are separated by whitespace other than spaces.
+    InvalidWhitespace,
+    /// A term is neither a known mechanism nor a `name=value` modifier.
+    UnknownMechanism { term: String },
+    /// A modifier carries a qualifier, or a mechanism uses `=` as separator.
+    InvalidModifier { term: String },
+    /// A CIDR prefix length exceeds 32 for IPv4 or 128 for IPv6.
+    InvalidCidrLength { term: String },
+    /// The directive at `position` is never evaluated because an `all`
+    /// mechanism precedes it.
+    UnreachableDirective { position: usize },
+    /// The `redirect=` modifier is ignored because the record has an `all`
+    /// mechanism.
+    IgnoredRedirect,
+    /// The record has neither an `all` mechanism nor a `redirect=` modifier,
+    /// so unmatched hosts evaluate to `neutral`.
+    MissingAll,
+    /// The record ends with `+all`, authorizing any host.
+    PassAll,
+    /// The directive at `position` uses the `ptr` mechanism or the `%{p}`
+    /// macro, deprecated by RFC 7208, section 5.5.
+    DeprecatedPtr { position: usize },
+    /// The `ip4` or `ip6` range at `position` is a duplicate of, or is
+    /// contained in, the range at `covered_by`.
+    RedundantRange { position: usize, covered_by: usize },
+    /// Evaluating the record may take more DNS querying terms than allowed.
+    TooManyLookups { lookups: u32 },
+    /// The record of the analyzed domain, or the record referenced by an
+    /// `include:` or `redirect=`, could not be retrieved.
+    RecordNotFound { domain: String },
+    /// The record referenced by an `include:` or `redirect=` references
+    /// itself.
+    IncludeLoop { domain: String },
+}
+
+/// Result of analyzing the SPF record of a domain and the records it
+/// references.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpfLint {
+    issues: Vec<(String, SpfIssue)>,
+    lookups: u32,
+}
+
+impl Spf {
+    /// Analyzes a parsed SPF record without querying DNS.
+    pub fn lint(&self) -> Vec<SpfIssue> {
+        let mut issues = Vec::new();
+        self.lint_directives(&mut issues);
+        let lookups = self.lookup_terms();
+        if lookups > SpfLimits::default().dns_lookups {
+            issues.push(SpfIssue::TooManyLookups { lookups });
+        }
+        issues
+    }
+
+    /// Analyzes the text of an SPF record, reporting syntax that [`Spf::parse`]
+    /// tolerates but RFC 7208 forbids along with the issues found by
+    /// [`Spf::lint`]. The latter are skipped when the syntax already makes the
+    /// record evaluate to `permerror`, as the parsed record may then differ
+    /// from the published one.
+    pub fn lint_txt(record: &[u8]) -> Vec<SpfIssue> {
+        let mut issues = Vec::new();
+        if let Some(spf) = lint_record(record, &mut issues) {
+            let lookups = spf.lookup_terms();
+            if lookups > SpfLimits::default().dns_lookups {
+                issues.push(SpfIssue::TooManyLookups { lookups });
+            }
+        }
+        issues
+    }
+
+    /// Returns the number of mechanisms and modifiers in this record that
+    /// cause DNS queries, without following includes. Directives following
+    /// an `all` mechanism are never evaluated and are not counted.
+    pub fn lookup_terms(&self) -> u32 {
+        let mut lookups = self
+            .reachable_directives()
+            .iter()
+            .filter(|directive| {
+                !matches!(
+                    directive.mechanism,
+                    Mechanism::Ip4 { .. } | Mechanism::Ip6 { .. }
+                )
+            })
+            .count() as u32;
+        if self.redirect.is_some() && !self.has_all() {
+            lookups += 1;
+        }
+        lookups
+    }
+
+    fn has_all(&self) -> bool {
+        self.directives
+            .iter()
+            .any(|directive| directive.mechanism == Mechanism::All)
+    }
+
+    /// Returns the directives evaluated before the first `all` mechanism.
+    fn reachable_directives(&self) -> &[Directive] {
+        let end = self
+            .directives
+            .iter()
+            .position(|directive| directive.mechanism == Mechanism::All)
+            .unwrap_or(self.directives.len());
+        &self.directives[..end]
+    }
+
+    fn lint_directives(&self, issues: &mut Vec<SpfIssue>) {
+        let mut all = None;
+
+        for (position, directive) in self.directives.iter().enumerate() {
+            if all.is_some() {
+                issues.push(SpfIssue::UnreachableDirective { position });
+                continue;
+            }
+
+            match &directive.mechanism {
+                Mechanism::All => {
+                    all = Some(&directive.qualifier);
+                }
+                Mechanism::Ptr { .. } => {
+                    issues.push(SpfIssue::DeprecatedPtr { position });
+                }
+                Mechanism::Ip4 { addr, mask } => {
+                    let addr = u32::from(*addr);
+                    for (prev_position, prev) in self.directives[..position].iter().enumerate() {
+                        if let Mechanism::Ip4 {
+                            addr: prev_addr,
+                            mask: prev_mask,
+                        } = &prev.mechanism
+                        {
+                            let prev_addr = u32::from(*prev_addr);
+                            if let Some(issue) = redundant_range(
+                                (prev_position, prev_addr & prev_mask, *prev_mask),
+                                (position, addr & mask, *mask),
+                                prev.qualifier == directive.qualifier,
+                            ) {
+                                issues.push(issue);
+                            }
+                        }
+                    }
+                }
+                Mechanism::Ip6 { addr, mask } => {
+                    let addr = u128::from(*addr);
+                    for (prev_position, prev) in self.directives[..position].iter().enumerate() {
+                        if let Mechanism::Ip6 {
+                            addr: prev_addr,
+                            mask: prev_mask,
+                        } = &prev.mechanism
+                        {
+                            let prev_addr = u128::from(*prev_addr);
+                            if let Some(issue) = redundant_range(
+                                (prev_position, prev_addr & prev_mask, *prev_mask),
+                                (position, addr & mask, *mask),
+                                prev.qualifier == directive.qualifier,
+                            ) {
+                                issues.push(issue);
+                            }
+                        }
+                    }
+                }
+                mechanism => {
+                    if mechanism.needs_ptr() {
+                        issues.push(SpfIssue::DeprecatedPtr { position });
+                    }
+                }
+            }
+        }
+
+        match all {
+            Some(qualifier) => {
+                if qualifier == &Qualifier::Pass {
+                    issues.push(SpfIssue::PassAll);
+                }
+                if self.redirect.is_some() {
+                    issues.push(SpfIssue::IgnoredRedirect);
+                }
+            }
+            None if self.redirect.is_none() => {
+                issues.push(SpfIssue::MissingAll);
+            }
+            None => (),
+        }
+    }
+}
+
+impl Resolver {
+    /// Analyzes the SPF record published by `domain` and, recursively, the
+    /// records referenced by its `include:` mechanisms and `redirect=`
+    /// modifier. The text of each record is checked as by [`Spf::lint_txt`].
+    /// References built from macros cannot be resolved in advance and are
+    /// counted as a single lookup. The analysis stops once the DNS lookup
+    /// limit is exceeded.
+    pub async fn lint_spf(&self, domain: &str) -> SpfLint {
+        let mut lint = SpfLint {
+            issues: Vec::new(),
+            lookups: 0,
+        };
+        let max_lookups = SpfLimits::default().dns_lookups;
+        let domain = domain.into_fqdn().into_owned();
+        let mut visited = HashSet::new();
+        let mut stack = vec![(domain.clone(), vec![domain.clone()])];
+
+        while let Some((name, path)) = stack.pop() {
+            // Records referenced more than once count their lookups every
+            // time but are only reported once
+            let is_first_visit = visited.insert(name.clone());
+            let referrer = path.len().checked_sub(2).map_or(&name, |pos| &path[pos]);
+            let records = match self.backend.txt_lookup(&name).await {
+                Ok(answer) => answer
+                    .records
+                    .into_iter()
+                    .filter(|record| is_spf_record(record))
+                    .collect::<Vec<_>>(),
+                Err(Error::DnsRecordNotFound(_)) => Vec::new(),
+                Err(err) => {
+                    if is_first_visit {
+                        lint.issues.push((referrer.clone(), SpfIssue::Invalid(err)));
+                    }
+                    continue;
+                }
+            };
+
+            let mut issues = Vec::new();
+            let spf = match records.as_slice() {
+                [record] => lint_record(record, &mut issues),
+                [] => {
+                    if is_first_visit {
+                        lint.issues.push((
+                            referrer.clone(),
+                            SpfIssue::RecordNotFound {
+                                domain: name.clone(),
+                            },
+                        ));
+                    }
+                    continue;
+                }
+                _ => {
+                    issues.push(SpfIssue::MultipleRecords);
+                    None
+                }
+            };
+
+            if let Some(spf) = spf {
+                lint.lookups += spf.lookup_terms();
+
+                // Follow the references that do not depend on macros
+                let has_all = spf.has_all();
+                for target in spf
+                    .reachable_directives()
+                    .iter()
+                    .filter_map(|directive| match &directive.mechanism {
+                        Mechanism::Include { macro_string } => Some(macro_string),
+                        _ => None,
+                    })
+                    .chain(spf.redirect.as_ref().filter(|_| !has_all))
+                    .rev()
+                {
+                    if let Macro::Literal(target) = target {
+                        let target = String::from_utf8_lossy(target)
+                            .as_ref()
+                            .into_fqdn()
+                            .into_owned();
+                        if path.contains(&target) {
+                            issues.push(SpfIssue::IncludeLoop { domain: target });
+                        } else {
+                            let mut path = path.clone();
+                            path.push(target.clone());
+                            stack.push((target, path));
+                        }
+                    }
+                }
+            }
+
+            if is_first_visit {
+                lint.issues
+                    .extend(issues.into_iter().map(|issue| (name.clone(), issue)));
+            }
+
+            if lint.lookups > max_lookups {
+                break;
+            }
+        }
+
+        if lint.lookups > max_lookups {
+            lint.issues.push((
+                domain,
+                SpfIssue::TooManyLookups {
+                    lookups: lint.lookups,
+                },
+            ));
+        }
+
+        lint
+    }
+}
+
+impl SpfLint {
+    /// Returns the issues found along with the domain publishing the
+    /// offending record.
+    pub fn issues(&self) -> &[(String, SpfIssue)] {
+        &self.issues
+    }
+
+    /// Returns the worst-case number of DNS querying terms across the
+    /// record and all the records it references, counted until the limit is
+    /// exceeded.
+    pub fn lookups(&self) -> u32 {
+        self.lookups
+    }
+
+    /// Returns `true` if any of the issues makes the policy evaluate to
+    /// `permerror`.
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|(_, issue)| issue.is_error())
+    }
+}
+
+impl SpfIssue {
+    /// Returns `true` if the issue makes the policy evaluate to `permerror`
+    /// for some or all hosts, `false` for warnings.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            SpfIssue::Invalid(_)
+                | SpfIssue::MultipleRecords
+                | SpfIssue::InvalidWhitespace
+                | SpfIssue::UnknownMechanism { .. }
+                | SpfIssue::InvalidModifier { .. }
+                | SpfIssue::InvalidCidrLength { .. }
+                | SpfIssue::TooManyLookups { .. }
+                | SpfIssue::RecordNotFound { .. }
+                | SpfIssue::IncludeLoop { .. }
+        )
+    }
+}
+
+impl Display for SpfIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpfIssue::Invalid(err) => write!(f, "Invalid SPF record: {err}"),
+            SpfIssue::MultipleRecords => f.write_str("Multiple SPF records published"),
+            SpfIssue::RecordTooLong { length } => write!(
+                f,
+                "Record is {length} bytes long, more than {MAX_RECORD_LENGTH} bytes"
+            ),
+            SpfIssue::InvalidWhitespace => f.write_str("Terms must be separated by spaces"),
+            SpfIssue::UnknownMechanism { term } => write!(f, "Unknown mechanism {term:?}"),
+            SpfIssue::InvalidModifier { term } => write!(f, "Invalid modifier {term:?}"),
+            SpfIssue::InvalidCidrLength { term } => {
+                write!(f, "Invalid CIDR prefix length in {term:?}")
+            }
+            SpfIssue::UnreachableDirective { position } => {
+                write!(
+                    f,
+                    "Directive {position} follows \"all\" and is never evaluated"
+                )
+            }
+            SpfIssue::IgnoredRedirect => {
+                f.write_str("The redirect modifier is ignored because \"all\" is present")
+            }
+            SpfIssue::MissingAll => {
+                f.write_str("No \"all\" mechanism or redirect modifier, defaulting to neutral")
+            }
+            SpfIssue::PassAll => f.write_str("\"+all\" authorizes any host"),
+            SpfIssue::DeprecatedPtr { position } => {
+                write!(
+                    f,
+                    "Directive {position} uses the deprecated \"ptr\" mechanism or macro"
+                )
+            }
+            SpfIssue::RedundantRange {
+                position,
+                covered_by,
+            } => write!(
+                f,
+                "Address range in directive {position} is covered by directive {covered_by}"
+            ),
+            SpfIssue::TooManyLookups { lookups } => write!(
+                f,
+                "Evaluation may take {lookups} DNS lookups, more than {}",
+                SpfLimits::default().dns_lookups
+            ),
+            SpfIssue::RecordNotFound { domain } => write!(f, "No SPF record found at {domain}"),
+            SpfIssue::IncludeLoop { domain } => write!(f, "Include loop through {domain}"),
+        }
+    }
+}
+
+fn is_spf_record(record: &[u8]) -> bool {
+    record
+        .get(..6)
+        .is_some_and(|v| v.eq_ignore_ascii_case(b"v=spf1"))
+        && record.get(6).map_or(true, |ch| ch.is_ascii_whitespace())
+}
+
+/// Checks the text of a record, returning the parsed record unless it does
+/// not parse or its syntax already makes it evaluate to `permerror`.
+fn lint_record(record: &[u8], issues: &mut Vec<SpfIssue>) -> Option<Spf> {
+    lint_terms(record, issues);
+    match Spf::parse(record) {
+        Ok(spf) if !issues.iter().any(SpfIssue::is_error) => {
+            spf.lint_directives(issues);
+            Some(spf)
+        }
+        Ok(_) => None,
+        Err(err) => {
+            issues.push(SpfIssue::Invalid(err));
+            None
+        }
+    }
+}
+
+/// Checks the terms of a record for syntax that the parser accepts.
+fn lint_terms(record: &[u8], issues: &mut Vec<SpfIssue>) {
+    if record.len() > MAX_RECORD_LENGTH {
+        issues.push(SpfIssue::RecordTooLong {
+            length: record.len(),
+        });
+    }
+    if !is_spf_record(record) {
+        return;
+    }
+    if record
+        .iter()
+        .any(|ch| ch.is_ascii_whitespace() && *ch != b' ')
+    {
+        issues.push(SpfIssue::InvalidWhitespace);
+    }
+
+    let record = String::from_utf8_lossy(record);
+    for term in record.split_ascii_whitespace().skip(1) {
+        let (qualified, rest) = match term.strip_prefix(['+', '-', '~', '?']) {
+            Some(rest) => (true, rest),
+            None => (false, term),
+        };
+        let (name, separator, value) = match rest.find([':', '=', '/']) {
+            Some(pos) => (&rest[..pos], rest.as_bytes()[pos], &rest[pos + 1..]),
+            None => (rest, b' ', ""),
+        };
+        let issue = match name.to_ascii_lowercase().as_str() {
+            "all" | "include" | "a" | "mx" | "ptr" | "ip4" | "ip6" | "exists" => {
+                if separator == b'=' {
+                    Some(SpfIssue::InvalidModifier { term: term.into() })
+                } else if !valid_cidr_lengths(name, separator, value) {
+                    Some(SpfIssue::InvalidCidrLength { term: term.into() })
+                } else {
+                    None
+                }
+            }
+            _ if separator == b'=' => {
+                if qualified {
+                    Some(SpfIssue::InvalidModifier { term: term.into() })
+                } else {
+                    None
+                }
+            }
+            _ => Some(SpfIssue::UnknownMechanism { term: term.into() }),
+        };
+        issues.extend(issue);
+    }
+}
+
+fn valid_cidr_lengths(name: &str, separator: u8, value: &str) -> bool {
+    let cidr = if separator == b'/' {
+        value
+    } else if let Some((_, cidr)) = value.split_once('/') {
+        cidr
+    } else {
+        return true;
+    };
+    let is_valid = |length: &str, max: u8| length.parse::<u8>().is_ok_and(|l| l <= max);
+
+    if name.eq_ignore_ascii_case("ip4") {
+        is_valid(cidr, 32)
+    } else if name.eq_ignore_ascii_case("ip6") {
+        is_valid(cidr, 128)
+    } else if let Some((ip4, ip6)) = cidr.split_once("//") {
+        (ip4.is_empty() || is_valid(ip4, 32)) && is_valid(ip6, 128)
+    } else if let Some(ip6) = cidr.strip_prefix('/') {
+        is_valid(ip6, 128)
+    } else {
+        is_valid(cidr, 32)
+    }
+}
+
+fn redundant_range<T>(
+    (prev_position, prev_net, prev_mask): (usize, T, T),
+    (position, net, mask): (usize, T, T),
+    same_qualifier: bool,
+) -> Option<SpfIssue>
+where
+    T: Copy + PartialEq + std::ops::BitAnd<Output = T>,
+{
+    if prev_mask & mask == prev_mask && net & prev_mask == prev_net {
+        // The previous range contains this one, which never matches
+        Some(SpfIssue::RedundantRange {
+            position,
+            covered_by: prev_position,
+        })
+    } else if same_qualifier && prev_mask & mask == mask && prev_net & mask == net {
+        // This range contains the previous one with the same outcome
+        Some(SpfIssue::RedundantRange {
+            position: prev_position,
+            covered_by: position,
+        })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{common::dns::DnsZone, Resolver};
+
+    use super::{Spf, SpfIssue};
+
+    #[test]
+    fn spf_lint() {
+        for (record, expected_issues) in [
+            ("v=spf1 ip4:192.0.2.0/24 mx -all", vec![]),
+            (
+                "v=spf1 ip4:192.0.2.0/24 ip4:192.0.2.10 ip6:2001:db8::/32 ip6:2001:db8::/32 ~all",
+                vec![
+                    SpfIssue::RedundantRange {
+                        position: 1,
+                        covered_by: 0,
+                    },
+                    SpfIssue::RedundantRange {
+                        position: 3,
+                        covered_by: 2,
+                    },
+                ],
+            ),
+            (
+                "v=spf1 ip4:192.0.2.10 ip4:192.0.2.0/24 ip4:198.51.100.0/24 -all",
+                vec![SpfIssue::RedundantRange {
+                    position: 0,
+                    covered_by: 1,
+                }],
+            ),
+            ("v=spf1 -ip4:192.0.2.10 ip4:192.0.2.0/24 -all", vec![]),
+            (
+                "v=spf1 +all a redirect=_spf.example.org",
+                vec![
+                    SpfIssue::UnreachableDirective { position: 1 },
+                    SpfIssue::PassAll,
+                    SpfIssue::IgnoredRedirect,
+                ],
+            ),
+            (
+                "v=spf1 ptr a:%{p}.example.org",
+                vec![
+                    SpfIssue::DeprecatedPtr { position: 0 },
+                    SpfIssue::DeprecatedPtr { position: 1 },
+                    SpfIssue::MissingAll,
+                ],
+            ),
+            (
+                "v=spf1\tip4:192.0.2.0/33 a=example.org -foo=bar -all",
+                vec![
+                    SpfIssue::InvalidWhitespace,
+                    SpfIssue::InvalidCidrLength {
+                        term: "ip4:192.0.2.0/33".into(),
+                    },
+                    SpfIssue::InvalidModifier {
+                        term: "a=example.org".into(),
+                    },
+                    SpfIssue::InvalidModifier {
+                        term: "-foo=bar".into(),
+                    },
+                ],
+            ),
+            (
+                "v=spf1 custom -all",
+                vec![SpfIssue::UnknownMechanism {
+                    term: "custom".into(),
+                }],
+            ),
+            (
+                "v=spf1 a/24//129 mx:example.org//64 -all",
+                vec![SpfIssue::InvalidCidrLength {
+                    term: "a/24//129".into(),
+                }],
+            ),
+            (
+                "v=spf1 a:1.example.org a:2.example.org a:3.example.org a:4.example.org \
+                a:5.example.org a:6.example.org mx:7.example.org mx:8.example.org \
+                exists:9.example.org include:10.example.org include:11.example.org -all",
+                vec![SpfIssue::TooManyLookups { lookups: 11 }],
+            ),
+        ] {
+            assert_eq!(
+                Spf::lint_txt(record.as_bytes()),
+                expected_issues,
+                "{record}"
+            );
+        }
+
+        let long_record = format!(
+            "v=spf1 {}-all",
+            (1..=40)
+                .map(|num| format!("ip4:192.0.2.{num} "))
+                .collect::<String>()
+        );
+        assert_eq!(
+            Spf::lint_txt(long_record.as_bytes()),
+            [SpfIssue::RecordTooLong {
+                length: long_record.len()
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn spf_lint_resolver() {
+        let zone = DnsZone::new();
+        zone.add_txt(
+            "example.org",
+            "v=spf1 include:_spf1.example.org include:_spf2.example.org a mx -all",
+        )
+        .add_txt("example.org", "google-site-verification=abc")
+        .add_txt(
+            "_spf1.example.org",
+            "v=spf1 a:a.example.org mx:mx.example.org include:_spf3.example.org ~all",
+        )
+        .add_txt(
+            "_spf2.example.org",
+            "v=spf1 ptr exists:%{i}.bl.example.org redirect=_spf3.example.org",
+        )
+        .add_txt(
+            "_spf3.example.org",
+            "v=spf1 a:b.example.org a:c.example.org include:_missing.example.org",
+        )
+        .add_txt("loop.example.org", "v=spf1 include:loop.example.org -all")
+        .add_txt(
+            "twice.example.org",
+            "v=spf1 include:_spf4.example.org include:_spf4.example.org -all",
+        )
+        .add_txt("_spf4.example.org", "v=spf1 a")
+        .add_txt("multiple.example.org", "v=spf1 -all")
+        .add_txt("multiple.example.org", "v=spf1 +all")
+        .add_txt(
+            "syntax.example.org",
+            "v=spf1 custom include:_spf4.example.org -all",
+        )
+        .add_txt(
+            "after.example.org",
+            "v=spf1 a -all include:_spf4.example.org",
+        );
+        let long_record = format!(
+            "v=spf1 {}-all",
+            (1..=40)
+                .map(|num| format!("ip4:192.0.2.{num} "))
+                .collect::<String>()
+        );
+        zone.add_txt("long.example.org", long_record.as_str());
+        let resolver = Resolver::with_backend(zone, 128);
+
+        // example.org: 4, _spf1: 3, _spf3: 3, _spf2: 3, stopping before
+        // the second visit to _spf3
+        let lint = resolver.lint_spf("example.org").await;
+        assert_eq!(lint.lookups(), 13);
+        assert!(lint.has_errors());
+        assert_eq!(
+            lint.issues(),
+            [
+                ("_spf3.example.org.".to_string(), SpfIssue::MissingAll),
+                (
+                    "_spf3.example.org.".to_string(),
+                    SpfIssue::RecordNotFound {
+                        domain: "_missing.example.org.".to_string(),
+                    }
+                ),
+                (
+                    "_spf2.example.org.".to_string(),
+                    SpfIssue::DeprecatedPtr { position: 0 }
+                ),
+                (
+                    "example.org.".to_string(),
+                    SpfIssue::TooManyLookups { lookups: 13 }
+                ),
+            ]
+        );
+
+        // Lookups are counted for each reference, issues reported once
+        let lint = resolver.lint_spf("twice.example.org").await;
+        assert_eq!(lint.lookups(), 4);
+        assert_eq!(
+            lint.issues(),
+            [("_spf4.example.org.".to_string(), SpfIssue::MissingAll)]
+        );
+
+        let lint = resolver.lint_spf("loop.example.org").await;
+        assert_eq!(
+            lint.issues(),
+            [(
+                "loop.example.org.".to_string(),
+                SpfIssue::IncludeLoop {
+                    domain: "loop.example.org.".to_string()
+                }
+            )]
+        );
+
+        let lint = resolver.lint_spf("multiple.example.org").await;
+        assert!(lint.has_errors());
+        assert_eq!(
+            lint.issues(),
+            [(
+                "multiple.example.org.".to_string(),
+                SpfIssue::MultipleRecords
+            )]
+        );
+
+        // The text of fetched records is checked, records with syntax
+        // errors are not followed
+        let lint = resolver.lint_spf("syntax.example.org").await;
+        assert_eq!(lint.lookups(), 0);
+        assert_eq!(
+            lint.issues(),
+            [(
+                "syntax.example.org.".to_string(),
+                SpfIssue::UnknownMechanism {
+                    term: "custom".into()
+                }
+            )]
+        );
+        let lint = resolver.lint_spf("long.example.org").await;
+        assert!(!lint.has_errors());
+        assert_eq!(
+            lint.issues(),
+            [(
+                "long.example.org.".to_string(),
+                SpfIssue::RecordTooLong {
+                    length: long_record.len()
+                }
+            )]
+        );
+
+        // Directives after "all" are neither counted nor followed
+        let lint = resolver.lint_spf("after.example.org").await;
+        assert_eq!(lint.lookups(), 1);
+        assert_eq!(
+            lint.issues(),
+            [(
+                "after.example.org.".to_string(),
+                SpfIssue::UnreachableDirective { position: 2 }
+            )]
+        );
+
+        let lint = resolver.lint_spf("none.example.org").await;
+        assert_eq!(
+            lint.issues(),
+            [(
+                "none.example.org.".to_string(),
+                SpfIssue::RecordNotFound {
+                    domain: "none.example.org.".to_string()
+                }
+            )]
+        );
+    }
+}
@@ -8,7 +8,9 @@
  * except according to those terms.
  */
 
-use std::{borrow::Cow, net::IpAddr, time::SystemTime};
+use std::{borrow::Cow, net::IpAddr};
+
+use crate::common::clock::Clock;
 
 use super::{Macro, Variable, Variables};
 
@@ -81,16 +83,14 @@ impl Macro {
 impl<'x> Variables<'x> {
     pub fn new() -> Self {
         let mut vars = Variables::default();
-        vars.vars[Variable::CurrentTime as usize] = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0)
-            .to_string()
-            .into_bytes()
-            .into();
+        vars.set_current_time(Clock::System.now());
         vars
     }
 
+    pub fn set_current_time(&mut self, value: u64) {
+        self.vars[Variable::CurrentTime as usize] = value.to_string().into_bytes().into();
+    }
+
     pub fn set_ip(&mut self, value: &IpAddr) {
         let (v, i, c) = match value {
             IpAddr::V4(ip) => (
@@ -281,6 +281,7 @@ mod test {
         vars.set_ip(&"2001:db8::cb01".parse::<IpAddr>().unwrap());
         vars.set_validated_domain("mx.example.org".as_bytes());
         vars.set_domain("email.example.com".as_bytes());
+        vars.set_current_time(1234567890);
 
         for (macro_string, expansion) in [
             (
@@ -291,6 +292,7 @@ mod test {
                 ),
             ),
             ("%{c}", "2001:db8::cb01"),
+            ("%{t}", "1234567890"),
             (
                 "%{c} is not one of %{d}'s designated mail servers.",
                 "2001:db8::cb01 is not one of email.example.com's designated mail servers.",
@@ -8,16 +8,22 @@
  * except according to those terms.
  */
 
+pub mod flatten;
+pub mod lint;
 pub mod macros;
 pub mod parse;
+pub mod serialize;
+pub mod trace;
 pub mod verify;
 
 use std::{
     borrow::Cow,
+    fmt::Display,
     net::{Ipv4Addr, Ipv6Addr},
+    time::Duration,
 };
 
-use crate::{is_within_pct, SpfOutput, SpfResult, Version};
+use crate::{is_within_pct, DnssecStatus, SpfOutput, SpfResult, Version};
 
 /*
       "+" pass
@@ -141,6 +147,37 @@ pub struct Spf {
     pub rr: u8,
 }
 
+/// Processing limits applied while evaluating an SPF policy. The defaults
+/// are the limits defined in RFC 7208, section 4.6.4.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SpfLimits {
+    /// Maximum number of mechanisms and modifiers that cause DNS queries
+    /// (`include`, `a`, `mx`, `ptr`, `exists` and `redirect`).
+    pub dns_lookups: u32,
+    /// Maximum number of DNS queries that return no records.
+    pub void_lookups: u32,
+    /// Maximum number of MX records whose addresses are looked up when
+    /// evaluating an `mx` mechanism.
+    pub mx_addresses: u32,
+    /// Maximum number of PTR names whose addresses are looked up when
+    /// evaluating a `ptr` mechanism. Further names are ignored instead of
+    /// producing a `permerror`, as mandated by RFC 7208.
+    pub ptr_addresses: u32,
+    /// Maximum duration of the whole evaluation.
+    pub timeout: Duration,
+}
+
+/// Processing limit that was exceeded while evaluating an SPF policy.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpfLimit {
+    DnsLookups,
+    VoidLookups,
+    MxAddresses,
+    PtrAddresses,
+    Timeout,
+}
+
 pub(crate) const RR_TEMP_PERM_ERROR: u8 = 0x01;
 pub(crate) const RR_FAIL: u8 = 0x02;
 pub(crate) const RR_SOFTFAIL: u8 = 0x04;
@@ -170,6 +207,74 @@ impl Mechanism {
     }
 }
 
+impl Default for SpfLimits {
+    fn default() -> Self {
+        SpfLimits {
+            dns_lookups: 10,
+            void_lookups: 2,
+            mx_addresses: 10,
+            ptr_addresses: 10,
+            timeout: Duration::from_secs(20),
+        }
+    }
+}
+
+impl SpfLimits {
+    /// Limits that never trigger, for trusted sources such as internal relays.
+    pub fn unlimited() -> Self {
+        SpfLimits {
+            dns_lookups: u32::MAX,
+            void_lookups: u32::MAX,
+            mx_addresses: u32::MAX,
+            ptr_addresses: u32::MAX,
+            timeout: Duration::MAX,
+        }
+    }
+
+    pub fn with_dns_lookups(mut self, dns_lookups: u32) -> Self {
+        self.dns_lookups = dns_lookups;
+        self
+    }
+
+    pub fn with_void_lookups(mut self, void_lookups: u32) -> Self {
+        self.void_lookups = void_lookups;
+        self
+    }
+
+    pub fn with_mx_addresses(mut self, mx_addresses: u32) -> Self {
+        self.mx_addresses = mx_addresses;
+        self
+    }
+
+    pub fn with_ptr_addresses(mut self, ptr_addresses: u32) -> Self {
+        self.ptr_addresses = ptr_addresses;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl SpfLimit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpfLimit::DnsLookups => "dns-lookups",
+            SpfLimit::VoidLookups => "void-lookups",
+            SpfLimit::MxAddresses => "mx-addresses",
+            SpfLimit::PtrAddresses => "ptr-addresses",
+            SpfLimit::Timeout => "timeout",
+        }
+    }
+}
+
+impl Display for SpfLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 impl TryFrom<&str> for SpfResult {
     type Error = ();
 
@@ -209,6 +314,19 @@ impl SpfOutput {
             report: None,
             explanation: None,
             domain,
+            dnssec: None,
+            limit: None,
+        }
+    }
+
+    /// Records the DNSSEC status of an SPF record used during evaluation,
+    /// keeping the weakest status seen so far.
+    pub(crate) fn add_dnssec(&mut self, dnssec: Option<DnssecStatus>) {
+        if let Some(dnssec) = dnssec {
+            self.dnssec = Some(match self.dnssec {
+                Some(current) => current.weakest(dnssec),
+                None => dnssec,
+            });
         }
     }
 
@@ -217,6 +335,7 @@ impl SpfOutput {
         self
     }
 
+    #[allow(clippy::collapsible_match)]
     pub(crate) fn with_report(mut self, spf: &Spf) -> Self {
         match &spf.ra {
             Some(ra) if is_within_pct(spf.rp) => {
@@ -242,6 +361,11 @@ impl SpfOutput {
         self
     }
 
+    pub(crate) fn with_limit(mut self, limit: Option<SpfLimit>) -> Self {
+        self.limit = limit;
+        self
+    }
+
     pub fn result(&self) -> SpfResult {
         self.result
     }
@@ -257,4 +381,15 @@ impl SpfOutput {
     pub fn report_address(&self) -> Option<&str> {
         self.report.as_deref()
     }
+
+    /// Returns the weakest DNSSEC status of the SPF records consulted during
+    /// evaluation, `None` if they were not validated.
+    pub fn dnssec(&self) -> Option<DnssecStatus> {
+        self.dnssec
+    }
+
+    /// Returns the processing limit that caused a `permerror` result, if any.
+    pub fn exceeded_limit(&self) -> Option<SpfLimit> {
+        self.limit
+    }
 }
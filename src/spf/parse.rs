@@ -28,7 +28,7 @@ impl TxtRecordParser for Spf {
         let mut record = bytes.iter();
         if !matches!(record.key(), Some(k) if k == V)
             || !record.match_bytes(b"spf1")
-            || record.next().map_or(false, |v| !v.is_ascii_whitespace())
+            || record.next().is_some_and(|v| !v.is_ascii_whitespace())
         {
             return Err(Error::InvalidRecordType);
         }
@@ -392,7 +392,8 @@ impl SPFParser for Iter<'_, u8> {
                     break;
                 }
                 _ => {
-                    if last_is_pct {
+                    if last_is_pct || (!ch.is_ascii() && !is_exp) {
+                        // RFC 7208 macro literals are visible ASCII characters
                         return Err(Error::ParseError);
                     } else if !ch.is_ascii_whitespace() || is_exp {
                         literal.push(ch);
@@ -1426,7 +1427,7 @@ mod test {
                 },
             ),
             (
-                concat!("v=spf1 mx:example.org -all ra=postmaster rp=15 rr=e:f:s:n"),
+                "v=spf1 mx:example.org -all ra=postmaster rp=15 rr=e:f:s:n",
                 Spf {
                     version: Version::V1,
                     ra: b"postmaster".to_vec().into(),
@@ -1448,7 +1449,7 @@ mod test {
                 },
             ),
             (
-                concat!("v=spf1 ip6:fe80:0000:0000::0000:0000:0000:1 -all"),
+                "v=spf1 ip6:fe80:0000:0000::0000:0000:0000:1 -all",
                 Spf {
                     version: Version::V1,
                     ra: None,
@@ -0,0 +1,418 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::fmt::{self, Display, Formatter, Write};
+
+use super::{
+    Directive, Macro, Mechanism, Qualifier, Spf, Variable, RR_FAIL, RR_NEUTRAL_NONE, RR_SOFTFAIL,
+    RR_TEMP_PERM_ERROR,
+};
+
+impl Spf {
+    /// Serializes the record into the contents of a TXT record.
+    pub fn to_txt(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Display for Spf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("v=spf1")?;
+        for directive in &self.directives {
+            write!(f, " {directive}")?;
+        }
+        if let Some(redirect) = &self.redirect {
+            write!(f, " redirect={redirect}")?;
+        }
+        if let Some(exp) = &self.exp {
+            write!(f, " exp={exp}")?;
+        }
+        if let Some(ra) = &self.ra {
+            write!(f, " ra={}", String::from_utf8_lossy(ra))?;
+        }
+        if self.rp != 100 {
+            write!(f, " rp={}", self.rp)?;
+        }
+        if self.rr != u8::MAX {
+            f.write_str(" rr=")?;
+            let mut first = true;
+            for (flag, ch) in [
+                (RR_TEMP_PERM_ERROR, 'e'),
+                (RR_FAIL, 'f'),
+                (RR_SOFTFAIL, 's'),
+                (RR_NEUTRAL_NONE, 'n'),
+            ] {
+                if self.rr & flag != 0 {
+                    if !first {
+                        f.write_char(':')?;
+                    }
+                    f.write_char(ch)?;
+                    first = false;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Directive {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.qualifier, self.mechanism)
+    }
+}
+
+impl Display for Qualifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Qualifier::Pass => "",
+            Qualifier::Fail => "-",
+            Qualifier::SoftFail => "~",
+            Qualifier::Neutral => "?",
+        })
+    }
+}
+
+impl Display for Mechanism {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Mechanism::All => f.write_str("all"),
+            Mechanism::Include { macro_string } => write!(f, "include:{macro_string}"),
+            Mechanism::A {
+                macro_string,
+                ip4_mask,
+                ip6_mask,
+            } => {
+                f.write_char('a')?;
+                fmt_domain_spec(f, macro_string, *ip4_mask, *ip6_mask)
+            }
+            Mechanism::Mx {
+                macro_string,
+                ip4_mask,
+                ip6_mask,
+            } => {
+                f.write_str("mx")?;
+                fmt_domain_spec(f, macro_string, *ip4_mask, *ip6_mask)
+            }
+            Mechanism::Ptr { macro_string } => {
+                f.write_str("ptr")?;
+                if macro_string != &Macro::None {
+                    write!(f, ":{macro_string}")?;
+                }
+                Ok(())
+            }
+            Mechanism::Ip4 { addr, mask } => {
+                write!(f, "ip4:{addr}")?;
+                if *mask != u32::MAX {
+                    write!(f, "/{}", mask.leading_ones())?;
+                }
+                Ok(())
+            }
+            Mechanism::Ip6 { addr, mask } => {
+                write!(f, "ip6:{addr}")?;
+                if *mask != u128::MAX {
+                    write!(f, "/{}", mask.leading_ones())?;
+                }
+                Ok(())
+            }
+            Mechanism::Exists { macro_string } => write!(f, "exists:{macro_string}"),
+        }
+    }
+}
+
+fn fmt_domain_spec(
+    f: &mut Formatter<'_>,
+    macro_string: &Macro,
+    ip4_mask: u32,
+    ip6_mask: u128,
+) -> fmt::Result {
+    if macro_string != &Macro::None {
+        write!(f, ":{macro_string}")?;
+    }
+    if ip4_mask != u32::MAX {
+        write!(f, "/{}", ip4_mask.leading_ones())?;
+    }
+    if ip6_mask != u128::MAX {
+        write!(f, "//{}", ip6_mask.leading_ones())?;
+    }
+    Ok(())
+}
+
+impl Display for Macro {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Macro::Literal(literal) => {
+                for ch in String::from_utf8_lossy(literal).chars() {
+                    match ch {
+                        '%' => f.write_str("%%")?,
+                        ' ' => f.write_str("%_")?,
+                        _ => f.write_char(ch)?,
+                    }
+                }
+                Ok(())
+            }
+            Macro::Variable {
+                letter,
+                num_parts,
+                reverse,
+                escape,
+                delimiters,
+            } => {
+                f.write_str("%{")?;
+                let letter = letter.as_char();
+                f.write_char(if *escape {
+                    letter.to_ascii_uppercase()
+                } else {
+                    letter
+                })?;
+                if *num_parts > 0 {
+                    write!(f, "{num_parts}")?;
+                }
+                if *reverse {
+                    f.write_char('r')?;
+                }
+                if *delimiters != 1u64 << (b'.' - b'+') {
+                    for ch in [b'.', b'-', b'+', b',', b'/', b'_', b'='] {
+                        if delimiters & (1u64 << (ch - b'+')) != 0 {
+                            f.write_char(char::from(ch))?;
+                        }
+                    }
+                }
+                f.write_char('}')
+            }
+            Macro::List(list) => {
+                for item in list {
+                    item.fmt(f)?;
+                }
+                Ok(())
+            }
+            Macro::None => Ok(()),
+        }
+    }
+}
+
+impl Variable {
+    /// Returns the macro letter of the variable, such as `d` for the domain.
+    pub fn as_char(&self) -> char {
+        match self {
+            Variable::Sender => 's',
+            Variable::SenderLocalPart => 'l',
+            Variable::SenderDomainPart => 'o',
+            Variable::Domain => 'd',
+            Variable::Ip => 'i',
+            Variable::ValidatedDomain => 'p',
+            Variable::IpVersion => 'v',
+            Variable::HeloDomain => 'h',
+            Variable::SmtpIp => 'c',
+            Variable::HostDomain => 'r',
+            Variable::CurrentTime => 't',
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use proptest::prelude::*;
+
+    use crate::{
+        common::parse::TxtRecordParser,
+        spf::{
+            Directive, Macro, Mechanism, Qualifier, Spf, Variable, RR_FAIL, RR_NEUTRAL_NONE,
+            RR_SOFTFAIL, RR_TEMP_PERM_ERROR,
+        },
+        Version,
+    };
+
+    fn domain() -> impl Strategy<Value = Macro> {
+        prop_oneof![
+            "[a-z0-9_-]{1,10}(\\.[a-z0-9-]{1,10}){1,2}"
+                .prop_map(|d| Macro::Literal(d.into_bytes())),
+            macro_string(),
+        ]
+    }
+
+    fn literal() -> impl Strategy<Value = Macro> {
+        "[a-z0-9._% -]{1,10}".prop_map(|l| Macro::Literal(l.into_bytes()))
+    }
+
+    fn variable() -> impl Strategy<Value = Macro> {
+        (
+            proptest::sample::select(vec![
+                Variable::Sender,
+                Variable::SenderLocalPart,
+                Variable::SenderDomainPart,
+                Variable::Domain,
+                Variable::Ip,
+                Variable::ValidatedDomain,
+                Variable::IpVersion,
+                Variable::HeloDomain,
+            ]),
+            0u32..=128,
+            any::<bool>(),
+            any::<bool>(),
+            proptest::sample::subsequence(vec![b'.', b'-', b'+', b',', b'/', b'_', b'='], 1..=7),
+        )
+            .prop_map(
+                |(letter, num_parts, reverse, escape, delimiters)| Macro::Variable {
+                    letter,
+                    num_parts,
+                    reverse,
+                    escape,
+                    delimiters: delimiters
+                        .into_iter()
+                        .fold(0, |mask, ch| mask | 1u64 << (ch - b'+')),
+                },
+            )
+    }
+
+    fn macro_string() -> impl Strategy<Value = Macro> {
+        (
+            proptest::option::of(literal()),
+            proptest::collection::vec((variable(), proptest::option::of(literal())), 1..4),
+        )
+            .prop_map(|(prefix, items)| {
+                let mut list = prefix.into_iter().collect::<Vec<_>>();
+                for (variable, literal) in items {
+                    list.push(variable);
+                    list.extend(literal);
+                }
+                if list.len() == 1 {
+                    list.pop().unwrap()
+                } else {
+                    Macro::List(list)
+                }
+            })
+    }
+
+    fn domain_spec() -> impl Strategy<Value = (Macro, u32, u128)> {
+        (
+            prop_oneof![Just(Macro::None), domain()],
+            prop_oneof![
+                Just(u32::MAX),
+                (1u32..=32).prop_map(|l| u32::MAX << (32 - l))
+            ],
+            prop_oneof![
+                Just(u128::MAX),
+                (1u32..=128).prop_map(|l| u128::MAX << (128 - l))
+            ],
+        )
+    }
+
+    fn directive() -> impl Strategy<Value = Directive> {
+        (
+            prop_oneof![
+                Just(Qualifier::Pass),
+                Just(Qualifier::Fail),
+                Just(Qualifier::SoftFail),
+                Just(Qualifier::Neutral)
+            ],
+            prop_oneof![
+                Just(Mechanism::All),
+                domain().prop_map(|macro_string| Mechanism::Include { macro_string }),
+                domain().prop_map(|macro_string| Mechanism::Exists { macro_string }),
+                prop_oneof![Just(Macro::None), domain()]
+                    .prop_map(|macro_string| Mechanism::Ptr { macro_string }),
+                domain_spec().prop_map(|(macro_string, ip4_mask, ip6_mask)| Mechanism::A {
+                    macro_string,
+                    ip4_mask,
+                    ip6_mask
+                }),
+                domain_spec().prop_map(|(macro_string, ip4_mask, ip6_mask)| Mechanism::Mx {
+                    macro_string,
+                    ip4_mask,
+                    ip6_mask
+                }),
+                (any::<u32>(), 1u32..=32).prop_map(|(addr, l)| Mechanism::Ip4 {
+                    addr: Ipv4Addr::from(addr),
+                    mask: u32::MAX << (32 - l)
+                }),
+                (any::<u128>(), 1u32..=128).prop_map(|(addr, l)| Mechanism::Ip6 {
+                    addr: Ipv6Addr::from(addr),
+                    mask: u128::MAX << (128 - l)
+                }),
+            ],
+        )
+            .prop_map(|(qualifier, mechanism)| Directive {
+                qualifier,
+                mechanism,
+            })
+    }
+
+    fn spf() -> impl Strategy<Value = Spf> {
+        (
+            proptest::collection::vec(directive(), 0..8),
+            proptest::option::of(domain()),
+            proptest::option::of(domain()),
+            proptest::option::of("[!-~]{1,20}"),
+            0u8..=100,
+            prop_oneof![
+                Just(u8::MAX),
+                proptest::sample::subsequence(
+                    vec![RR_TEMP_PERM_ERROR, RR_FAIL, RR_SOFTFAIL, RR_NEUTRAL_NONE],
+                    0..=4
+                )
+                .prop_map(|flags| flags.into_iter().fold(0, |rr, flag| rr | flag)),
+            ],
+        )
+            .prop_map(|(directives, redirect, exp, ra, rp, rr)| Spf {
+                version: Version::V1,
+                directives,
+                exp,
+                redirect,
+                ra: ra.map(String::into_bytes),
+                rp,
+                rr,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn spf_roundtrip(record in spf()) {
+            prop_assert_eq!(Spf::parse(record.to_txt().as_bytes()).unwrap(), record);
+        }
+    }
+
+    #[test]
+    fn spf_serialize() {
+        for (record, expected) in [
+            ("v=spf1 +mx a:colo.example.com/28 -all", "v=spf1 mx a:colo.example.com/28 -all"),
+            (
+                "v=spf1 a/24//64 mx:example.org//126 ?ptr ~ptr:example.net ip4:192.0.2.0/24 ip6:2001:db8::/32 -all",
+                "v=spf1 a/24//64 mx:example.org//126 ?ptr ~ptr:example.net ip4:192.0.2.0/24 ip6:2001:db8::/32 -all",
+            ),
+            (
+                "v=spf1 exists:%{ir}.%{l1r+-}._spf.%{d} include:%{D2}.example.com redirect=_spf.example.com",
+                "v=spf1 exists:%{ir}.%{l1r-+}._spf.%{d} include:%{D2}.example.com redirect=_spf.example.com",
+            ),
+            (
+                "v=spf1 -all exp=explain._spf.%{d} ra=postmaster rp=15 rr=e:f:s:n",
+                "v=spf1 -all exp=explain._spf.%{d} ra=postmaster rp=15 rr=e:f:s:n",
+            ),
+            (
+                "v=spf1 rr=f:n ip4:10.0.0.1 ip6:::1 redirect=%%%_%{d}",
+                "v=spf1 ip4:10.0.0.1 ip6:::1 redirect=%%%_%{d} rr=f:n",
+            ),
+        ] {
+            let spf = Spf::parse(record.as_bytes()).unwrap();
+            let txt = spf.to_txt();
+            assert_eq!(txt, expected, "{record}");
+            assert_eq!(Spf::parse(txt.as_bytes()).unwrap(), spf, "{record}");
+        }
+
+        // Non-ASCII literals are rejected by the parser
+        assert!(Spf::parse(b"v=spf1 include:caf\xc3\xa9.example.com -all").is_err());
+        assert!(Spf::parse("v=spf1 a:bücher.example -all".as_bytes()).is_err());
+        assert_eq!(
+            Macro::Literal("bücher.example".into()).to_string(),
+            "bücher.example"
+        );
+    }
+}
@@ -0,0 +1,296 @@
+/*
+ * Copyright (c) 2020-2023, Stalwart Labs Ltd.
+ *
+ * Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+ * https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+ * <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+ * option. This file may not be copied, modified, or distributed
+ * except according to those terms.
+ */
+
+use std::{net::IpAddr, sync::Arc};
+
+use crate::{Error, Resolver, SpfOutput, SpfResult};
+
+use super::{verify::Evaluation, Directive, Macro, Mechanism, Spf, SpfLimit, SpfLimits};
+
+/// Steps taken while evaluating the SPF policy of a domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpfTrace {
+    events: Vec<SpfEvent>,
+    lookups: u32,
+    output: SpfOutput,
+}
+
+/// A single step of an SPF evaluation, in the order it took place. Domain
+/// names are reported without a trailing dot, DNS answers as returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpfEvent {
+    /// The SPF record of `domain` was fetched, either at the start of the
+    /// evaluation or following an `include:` mechanism or a `redirect=` modifier.
+    Record {
+        domain: String,
+        record: Result<Arc<Spf>, Error>,
+    },
+    /// The directive at `position` of the record published by `domain` is
+    /// about to be evaluated.
+    Directive {
+        domain: String,
+        position: usize,
+        directive: Directive,
+    },
+    /// The directive at `position` of the record published by `domain`
+    /// matched, producing `result`. An `include:` matches after the directive
+    /// of the included record that passed.
+    Match {
+        domain: String,
+        position: usize,
+        directive: Directive,
+        result: SpfResult,
+    },
+    /// No directive of the record published by `domain` matched and its
+    /// `redirect=` modifier points to `target`.
+    Redirect { domain: String, target: String },
+    /// A macro string was expanded into a domain name.
+    Macro {
+        macro_string: Macro,
+        expanded: String,
+    },
+    /// A DNS query was issued for `name`. PTR queries are named after the IP
+    /// address and the answer to the `exp=` TXT query is the expanded
+    /// explanation.
+    DnsQuery {
+        query: SpfQuery,
+        name: String,
+        answer: Result<Vec<String>, Error>,
+    },
+    /// The `exists:` mechanism checked whether `name` has an address record.
+    Exists {
+        name: String,
+        result: Result<bool, Error>,
+    },
+    /// A processing limit was exceeded. Evaluation stops with a `permerror`
+    /// except for [`SpfLimit::PtrAddresses`], where further names are ignored.
+    LimitExceeded(SpfLimit),
+}
+
+/// Type of a DNS query issued during an SPF evaluation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpfQuery {
+    A,
+    Aaaa,
+    Mx,
+    Ptr,
+    Txt,
+}
+
+impl Resolver {
+    /// Same as [`Resolver::check_host`] but also returns every step taken
+    /// while evaluating the policy.
+    pub async fn trace_spf(
+        &self,
+        ip: IpAddr,
+        domain: &str,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+    ) -> SpfTrace {
+        self.trace_spf_with_limits(
+            ip,
+            domain,
+            helo_domain,
+            host_domain,
+            sender,
+            SpfLimits::default(),
+        )
+        .await
+    }
+
+    /// Same as [`Resolver::trace_spf`] but enforcing `limits`, see
+    /// [`Resolver::check_host_with_limits`].
+    pub async fn trace_spf_with_limits(
+        &self,
+        ip: IpAddr,
+        domain: &str,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+        limits: SpfLimits,
+    ) -> SpfTrace {
+        let mut eval = Evaluation::new(limits, true);
+        let output = self
+            .evaluate_spf(ip, domain, helo_domain, host_domain, sender, &mut eval)
+            .await;
+        SpfTrace {
+            lookups: eval.lookups(),
+            events: eval.into_events(),
+            output,
+        }
+    }
+}
+
+impl SpfTrace {
+    pub fn events(&self) -> &[SpfEvent] {
+        &self.events
+    }
+
+    /// Returns the number of DNS querying terms counted against
+    /// [`SpfLimits::dns_lookups`].
+    pub fn lookups(&self) -> u32 {
+        self.lookups
+    }
+
+    /// Returns the records fetched during the evaluation, starting with the
+    /// one published by the domain being checked.
+    pub fn records(&self) -> impl Iterator<Item = (&str, &Arc<Spf>)> {
+        self.events.iter().filter_map(|event| match event {
+            SpfEvent::Record {
+                domain,
+                record: Ok(record),
+            } => Some((domain.as_str(), record)),
+            _ => None,
+        })
+    }
+
+    /// Returns the directive that decided the result along with the domain
+    /// publishing it, or `None` if the result was not produced by a directive.
+    /// When the result comes from an `include:`, this is the directive of the
+    /// included record that passed.
+    pub fn matched(&self) -> Option<(&str, &Directive)> {
+        let mut matches = self.events.iter().rev().filter_map(|event| match event {
+            SpfEvent::Match {
+                domain, directive, ..
+            } => Some((domain.as_str(), directive)),
+            _ => None,
+        });
+        let mut matched = matches.next()?;
+        // The included directive that passed is recorded just before the include
+        while matches!(matched.1.mechanism, Mechanism::Include { .. }) {
+            match matches.next() {
+                Some(inner) => matched = inner,
+                None => break,
+            }
+        }
+        Some(matched)
+    }
+
+    pub fn output(&self) -> &SpfOutput {
+        &self.output
+    }
+
+    pub fn result(&self) -> SpfResult {
+        self.output.result()
+    }
+
+    pub fn into_output(self) -> SpfOutput {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        common::dns::DnsZone,
+        spf::{Mechanism, Qualifier},
+        Resolver, SpfResult,
+    };
+
+    use super::{SpfEvent, SpfQuery};
+
+    #[tokio::test]
+    async fn spf_trace() {
+        let zone = DnsZone::new();
+        zone.add_txt(
+            "example.org",
+            "v=spf1 ip4:192.0.2.10 include:_spf.%{d} redirect=_fallback.example.org",
+        )
+        .add_txt("_spf.example.org", "v=spf1 a:mail.%{o} -all")
+        .add_txt("_fallback.example.org", "v=spf1 mx ~all")
+        .add_mx("_fallback.example.org", 10, "mx.example.org")
+        .add_ipv4("mail.example.org", "192.0.2.20".parse().unwrap())
+        .add_ipv4("mx.example.org", "192.0.2.30".parse().unwrap());
+        let resolver = Resolver::with_backend(zone, 128);
+
+        // Pass through the included record
+        let trace = resolver
+            .trace_spf(
+                "192.0.2.20".parse().unwrap(),
+                "example.org",
+                "mx.example.net",
+                "mx.example.org",
+                "jdoe@example.org",
+            )
+            .await;
+        assert_eq!(trace.result(), SpfResult::Pass);
+        assert_eq!(trace.lookups(), 2);
+        assert_eq!(
+            trace.records().map(|(d, _)| d).collect::<Vec<_>>(),
+            ["example.org", "_spf.example.org"]
+        );
+        let (domain, directive) = trace.matched().unwrap();
+        assert_eq!(domain, "_spf.example.org");
+        assert!(matches!(directive.mechanism, Mechanism::A { .. }));
+        assert!(trace.events().iter().any(|e| matches!(e,
+            SpfEvent::Macro { expanded, .. } if expanded == "mail.example.org")));
+        assert!(trace.events().iter().any(|e| matches!(e,
+            SpfEvent::DnsQuery { query: SpfQuery::A, name, answer: Ok(answer) }
+                if name == "mail.example.org" && answer == &["192.0.2.20"])));
+        assert!(matches!(
+            trace.events().last().unwrap(),
+            SpfEvent::Match { domain, position: 1, result: SpfResult::Pass, .. }
+                if domain == "example.org"
+        ));
+        assert_eq!(
+            trace.output(),
+            &resolver
+                .check_host(
+                    "192.0.2.20".parse().unwrap(),
+                    "example.org",
+                    "mx.example.net",
+                    "mx.example.org",
+                    "jdoe@example.org",
+                )
+                .await
+        );
+
+        // The include fails, the redirect softfails
+        let trace = resolver
+            .trace_spf(
+                "192.0.2.99".parse().unwrap(),
+                "example.org",
+                "mx.example.net",
+                "mx.example.org",
+                "jdoe@example.org",
+            )
+            .await;
+        assert_eq!(trace.result(), SpfResult::SoftFail);
+        assert_eq!(trace.lookups(), 4);
+        assert!(trace.events().iter().any(|e| matches!(e,
+            SpfEvent::Redirect { domain, target }
+                if domain == "example.org" && target == "_fallback.example.org")));
+        assert!(trace.events().iter().any(|e| matches!(e,
+            SpfEvent::DnsQuery { query: SpfQuery::Mx, answer: Ok(answer), .. }
+                if answer == &["mx.example.org."])));
+        let (domain, directive) = trace.matched().unwrap();
+        assert_eq!(domain, "_fallback.example.org");
+        assert_eq!(directive.qualifier, Qualifier::SoftFail);
+        assert_eq!(directive.mechanism, Mechanism::All);
+
+        // Missing record
+        let trace = resolver
+            .trace_spf(
+                "192.0.2.20".parse().unwrap(),
+                "example.net",
+                "mx.example.net",
+                "mx.example.org",
+                "jdoe@example.net",
+            )
+            .await;
+        assert_eq!(trace.result(), SpfResult::None);
+        assert!(matches!(
+            trace.events(),
+            [SpfEvent::Record { record: Err(_), .. }]
+        ));
+        assert!(trace.matched().is_none());
+    }
+}
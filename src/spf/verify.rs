@@ -9,13 +9,18 @@
  */
 
 use std::{
+    borrow::Cow,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
     time::Instant,
 };
 
 use crate::{Error, Resolver, SpfOutput, SpfResult};
 
-use super::{Macro, Mechanism, Qualifier, Spf, Variables};
+use super::{
+    trace::{SpfEvent, SpfQuery},
+    Macro, Mechanism, Qualifier, Spf, SpfLimit, SpfLimits, Variables,
+};
 
 #[allow(clippy::iter_skip_zero)]
 impl Resolver {
@@ -25,14 +30,27 @@ impl Resolver {
         ip: IpAddr,
         helo_domain: &str,
         host_domain: &str,
+    ) -> SpfOutput {
+        self.verify_spf_helo_with_limits(ip, helo_domain, host_domain, SpfLimits::default())
+            .await
+    }
+
+    /// Verifies the SPF EHLO identity using custom processing limits
+    pub async fn verify_spf_helo_with_limits(
+        &self,
+        ip: IpAddr,
+        helo_domain: &str,
+        host_domain: &str,
+        limits: SpfLimits,
     ) -> SpfOutput {
         if helo_domain.has_valid_labels() {
-            self.check_host(
+            self.check_host_with_limits(
                 ip,
                 helo_domain,
                 helo_domain,
                 host_domain,
                 &format!("postmaster@{helo_domain}"),
+                limits,
             )
             .await
         } else {
@@ -48,12 +66,32 @@ impl Resolver {
         host_domain: &str,
         sender: &str,
     ) -> SpfOutput {
-        self.check_host(
+        self.verify_spf_sender_with_limits(
+            ip,
+            helo_domain,
+            host_domain,
+            sender,
+            SpfLimits::default(),
+        )
+        .await
+    }
+
+    /// Verifies the SPF MAIL FROM identity using custom processing limits
+    pub async fn verify_spf_sender_with_limits(
+        &self,
+        ip: IpAddr,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+        limits: SpfLimits,
+    ) -> SpfOutput {
+        self.check_host_with_limits(
             ip,
             sender.rsplit_once('@').map_or(helo_domain, |(_, d)| d),
             helo_domain,
             host_domain,
             sender,
+            limits,
         )
         .await
     }
@@ -65,34 +103,100 @@ impl Resolver {
         helo_domain: &str,
         host_domain: &str,
         mail_from: &str,
+    ) -> SpfOutput {
+        self.verify_spf_with_limits(
+            ip,
+            helo_domain,
+            host_domain,
+            mail_from,
+            SpfLimits::default(),
+        )
+        .await
+    }
+
+    /// Verifies both the SPF EHLO and MAIL FROM identities using custom
+    /// processing limits
+    pub async fn verify_spf_with_limits(
+        &self,
+        ip: IpAddr,
+        helo_domain: &str,
+        host_domain: &str,
+        mail_from: &str,
+        limits: SpfLimits,
     ) -> SpfOutput {
         // Verify HELO identity
-        let output = self.verify_spf_helo(ip, helo_domain, host_domain).await;
+        let output = self
+            .verify_spf_helo_with_limits(ip, helo_domain, host_domain, limits)
+            .await;
         if matches!(output.result(), SpfResult::Pass) {
             // Verify MAIL FROM identity
-            self.verify_spf_sender(ip, helo_domain, host_domain, mail_from)
+            self.verify_spf_sender_with_limits(ip, helo_domain, host_domain, mail_from, limits)
                 .await
         } else {
             output
         }
     }
 
+    pub async fn check_host(
+        &self,
+        ip: IpAddr,
+        domain: &str,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+    ) -> SpfOutput {
+        self.check_host_with_limits(
+            ip,
+            domain,
+            helo_domain,
+            host_domain,
+            sender,
+            SpfLimits::default(),
+        )
+        .await
+    }
+
+    /// Same as [`Resolver::check_host`] but enforcing `limits` instead of the
+    /// RFC 7208 defaults. Exceeding a limit produces a `permerror` result
+    /// naming the limit, see [`SpfOutput::exceeded_limit`].
+    pub async fn check_host_with_limits(
+        &self,
+        ip: IpAddr,
+        domain: &str,
+        helo_domain: &str,
+        host_domain: &str,
+        sender: &str,
+        limits: SpfLimits,
+    ) -> SpfOutput {
+        self.evaluate_spf(
+            ip,
+            domain,
+            helo_domain,
+            host_domain,
+            sender,
+            &mut Evaluation::new(limits, false),
+        )
+        .await
+    }
+
     #[allow(clippy::while_let_on_iterator)]
     #[allow(clippy::iter_skip_zero)]
-    pub async fn check_host(
+    pub(crate) async fn evaluate_spf(
         &self,
         ip: IpAddr,
         domain: &str,
         helo_domain: &str,
         host_domain: &str,
         sender: &str,
+        eval: &mut Evaluation,
     ) -> SpfOutput {
-        let output = SpfOutput::new(domain.to_string());
+        let mut output = SpfOutput::new(domain.to_string());
         if domain.is_empty() || domain.len() > 255 || !domain.has_valid_labels() {
             return output.with_result(SpfResult::None);
         }
         let mut vars = Variables::new();
         let mut has_p_var = false;
+        vars.set_current_time(self.clock.now());
         vars.set_ip(&ip);
         if !sender.is_empty() {
             vars.set_sender(sender.as_bytes());
@@ -103,10 +207,16 @@ impl Resolver {
         vars.set_host_domain(host_domain.as_bytes());
         vars.set_helo_domain(helo_domain.as_bytes());
 
-        let mut lookup_limit = LookupLimit::new();
-        let mut spf_record = match self.txt_lookup::<Spf>(domain).await {
-            Ok(spf_record) => spf_record,
-            Err(err) => return output.with_result(err.into()),
+        let mut spf_record = match self.txt_lookup_with_dnssec::<Spf>(domain).await {
+            Ok((spf_record, dnssec)) => {
+                eval.record_spf(domain, Ok(&spf_record));
+                output.add_dnssec(dnssec);
+                spf_record
+            }
+            Err(err) => {
+                eval.record_spf(domain, Err(&err));
+                return output.with_result(err.into());
+            }
         };
 
         let mut domain = domain.to_string();
@@ -118,14 +228,12 @@ impl Resolver {
         loop {
             while let Some((pos, directive)) = directives.next() {
                 if !has_p_var && directive.mechanism.needs_ptr() {
-                    if !lookup_limit.can_lookup() {
-                        return output
-                            .with_result(SpfResult::PermError)
-                            .with_report(&spf_record);
+                    if !eval.can_lookup() {
+                        return eval.limit_error(output).with_report(&spf_record);
                     }
-                    if let Some(ptr) = self
-                        .ptr_lookup(ip)
-                        .await
+                    let ptrs = self.ptr_lookup(ip).await;
+                    eval.record_query(SpfQuery::Ptr, &ip.to_string(), &ptrs);
+                    if let Some(ptr) = ptrs
                         .ok()
                         .and_then(|ptrs| ptrs.first().map(|ptr| ptr.as_bytes().to_vec()))
                     {
@@ -133,6 +241,11 @@ impl Resolver {
                     }
                     has_p_var = true;
                 }
+                eval.record(|| SpfEvent::Directive {
+                    domain: trace_name(&domain),
+                    position: pos,
+                    directive: directive.clone(),
+                });
 
                 let matches = match &directive.mechanism {
                     Mechanism::All => true,
@@ -143,22 +256,22 @@ impl Resolver {
                         ip4_mask,
                         ip6_mask,
                     } => {
-                        if !lookup_limit.can_lookup() {
-                            return output
-                                .with_result(SpfResult::PermError)
-                                .with_report(&spf_record);
+                        if !eval.can_lookup() {
+                            return eval.limit_error(output).with_report(&spf_record);
                         }
+                        let target_name = eval.expand(macro_string, &vars, &domain);
                         match self
-                            .ip_matches(
-                                macro_string.eval(&vars, &domain, true).as_ref(),
-                                ip,
-                                *ip4_mask,
-                                *ip6_mask,
-                            )
+                            .ip_matches(target_name.as_ref(), ip, *ip4_mask, *ip6_mask, eval)
                             .await
                         {
                             Ok(true) => true,
-                            Ok(false) | Err(Error::DnsRecordNotFound(_)) => false,
+                            Ok(false) => false,
+                            Err(Error::DnsRecordNotFound(_)) => {
+                                if !eval.void_lookup() {
+                                    return eval.limit_error(output).with_report(&spf_record);
+                                }
+                                false
+                            }
                             Err(_) => {
                                 return output
                                     .with_result(SpfResult::TempError)
@@ -171,30 +284,38 @@ impl Resolver {
                         ip4_mask,
                         ip6_mask,
                     } => {
-                        if !lookup_limit.can_lookup() {
-                            return output
-                                .with_result(SpfResult::PermError)
-                                .with_report(&spf_record);
+                        if !eval.can_lookup() {
+                            return eval.limit_error(output).with_report(&spf_record);
                         }
 
                         let mut matches = false;
-                        match self
-                            .mx_lookup(macro_string.eval(&vars, &domain, true).as_ref())
-                            .await
-                        {
+                        let target_name = eval.expand(macro_string, &vars, &domain);
+                        let records = self.mx_lookup(target_name.as_ref()).await;
+                        eval.record(|| SpfEvent::DnsQuery {
+                            query: SpfQuery::Mx,
+                            name: trace_name(&target_name),
+                            answer: records.as_ref().map_err(Clone::clone).map(|records| {
+                                records
+                                    .iter()
+                                    .flat_map(|mx| mx.exchanges.iter().cloned())
+                                    .collect()
+                            }),
+                        });
+                        match records {
                             Ok(records) => {
                                 for (mx_num, exchange) in records
                                     .iter()
                                     .flat_map(|mx| mx.exchanges.iter())
                                     .enumerate()
                                 {
-                                    if mx_num > 9 {
-                                        return output
-                                            .with_result(SpfResult::PermError)
-                                            .with_report(&spf_record);
+                                    if mx_num >= eval.limits.mx_addresses as usize {
+                                        eval.exceed(SpfLimit::MxAddresses);
+                                        return eval.limit_error(output).with_report(&spf_record);
                                     }
 
-                                    match self.ip_matches(exchange, ip, *ip4_mask, *ip6_mask).await
+                                    match self
+                                        .ip_matches(exchange, ip, *ip4_mask, *ip6_mask, eval)
+                                        .await
                                     {
                                         Ok(true) => {
                                             matches = true;
@@ -209,7 +330,11 @@ impl Resolver {
                                     }
                                 }
                             }
-                            Err(Error::DnsRecordNotFound(_)) => (),
+                            Err(Error::DnsRecordNotFound(_)) => {
+                                if !eval.void_lookup() {
+                                    return eval.limit_error(output).with_report(&spf_record);
+                                }
+                            }
                             Err(_) => {
                                 return output
                                     .with_result(SpfResult::TempError)
@@ -219,15 +344,21 @@ impl Resolver {
                         matches
                     }
                     Mechanism::Include { macro_string } => {
-                        if !lookup_limit.can_lookup() {
-                            return output
-                                .with_result(SpfResult::PermError)
-                                .with_report(&spf_record);
+                        if !eval.can_lookup() {
+                            return eval.limit_error(output).with_report(&spf_record);
                         }
 
-                        let target_name = macro_string.eval(&vars, &domain, true);
-                        match self.txt_lookup::<Spf>(target_name.as_ref()).await {
-                            Ok(included_spf) => {
+                        let target_name = eval.expand(macro_string, &vars, &domain);
+                        let included_spf = self
+                            .txt_lookup_with_dnssec::<Spf>(target_name.as_ref())
+                            .await;
+                        eval.record_spf(
+                            target_name.as_ref(),
+                            included_spf.as_ref().map(|(spf, _)| spf),
+                        );
+                        match included_spf {
+                            Ok((included_spf, dnssec)) => {
+                                output.add_dnssec(dnssec);
                                 let new_domain = target_name.to_string();
                                 include_stack.push((
                                     std::mem::replace(&mut spf_record, included_spf),
@@ -256,21 +387,27 @@ impl Resolver {
                         }
                     }
                     Mechanism::Ptr { macro_string } => {
-                        if !lookup_limit.can_lookup() {
-                            return output
-                                .with_result(SpfResult::PermError)
-                                .with_report(&spf_record);
+                        if !eval.can_lookup() {
+                            return eval.limit_error(output).with_report(&spf_record);
                         }
 
-                        let target_addr = macro_string.eval(&vars, &domain, true).to_lowercase();
+                        let target_addr = eval.expand(macro_string, &vars, &domain).to_lowercase();
                         let target_sub_addr = format!(".{target_addr}");
                         let mut matches = false;
 
-                        if let Ok(records) = self.ptr_lookup(ip).await {
-                            for record in records.iter() {
-                                if lookup_limit.can_lookup() {
+                        let records = self.ptr_lookup(ip).await;
+                        eval.record_query(SpfQuery::Ptr, &ip.to_string(), &records);
+                        match records {
+                            Ok(records) => {
+                                // Names past the limit are ignored rather than
+                                // producing a permerror (RFC 7208, section 4.6.4)
+                                let max_names = eval.limits.ptr_addresses as usize;
+                                if records.len() > max_names {
+                                    eval.record(|| SpfEvent::LimitExceeded(SpfLimit::PtrAddresses));
+                                }
+                                for record in records.iter().take(max_names) {
                                     if let Ok(true) =
-                                        self.ip_matches(record, ip, u32::MAX, u128::MAX).await
+                                        self.ip_matches(record, ip, u32::MAX, u128::MAX, eval).await
                                     {
                                         matches = record == &target_addr
                                             || record
@@ -283,20 +420,30 @@ impl Resolver {
                                     }
                                 }
                             }
+                            Err(Error::DnsRecordNotFound(_)) => {
+                                if !eval.void_lookup() {
+                                    return eval.limit_error(output).with_report(&spf_record);
+                                }
+                            }
+                            Err(_) => (),
                         }
                         matches
                     }
                     Mechanism::Exists { macro_string } => {
-                        if !lookup_limit.can_lookup() {
-                            return output
-                                .with_result(SpfResult::PermError)
-                                .with_report(&spf_record);
+                        if !eval.can_lookup() {
+                            return eval.limit_error(output).with_report(&spf_record);
                         }
 
-                        if let Ok(result) = self
-                            .exists(macro_string.eval(&vars, &domain, true).as_ref())
-                            .await
-                        {
+                        let target_name = eval.expand(macro_string, &vars, &domain);
+                        let result = self.exists(target_name.as_ref()).await;
+                        eval.record(|| SpfEvent::Exists {
+                            name: trace_name(&target_name),
+                            result: result.clone(),
+                        });
+                        if let Ok(result) = result {
+                            if !result && !eval.void_lookup() {
+                                return eval.limit_error(output).with_report(&spf_record);
+                            }
                             result
                         } else {
                             return output
@@ -307,7 +454,14 @@ impl Resolver {
                 };
 
                 if matches {
-                    result = Some((&directive.qualifier).into());
+                    let directive_result = (&directive.qualifier).into();
+                    eval.record(|| SpfEvent::Match {
+                        domain: trace_name(&domain),
+                        position: pos,
+                        directive: directive.clone(),
+                        result: directive_result,
+                    });
+                    result = Some(directive_result);
                     break;
                 }
             }
@@ -318,7 +472,14 @@ impl Resolver {
                 let (_, directive) = directives.next().unwrap();
 
                 if matches!(result, Some(SpfResult::Pass)) {
-                    result = Some((&directive.qualifier).into());
+                    let directive_result = (&directive.qualifier).into();
+                    eval.record(|| SpfEvent::Match {
+                        domain: trace_name(&prev_domain),
+                        position: prev_pos,
+                        directive: directive.clone(),
+                        result: directive_result,
+                    });
+                    result = Some(directive_result);
                     break;
                 } else {
                     vars.set_domain(prev_domain.as_bytes().to_vec());
@@ -328,15 +489,25 @@ impl Resolver {
             } else {
                 // Follow redirect
                 if let (Some(macro_string), None) = (&spf_record.redirect, &result) {
-                    if !lookup_limit.can_lookup() {
-                        return output
-                            .with_result(SpfResult::PermError)
-                            .with_report(&spf_record);
+                    if !eval.can_lookup() {
+                        return eval.limit_error(output).with_report(&spf_record);
                     }
 
-                    let target_name = macro_string.eval(&vars, &domain, true);
-                    match self.txt_lookup::<Spf>(target_name.as_ref()).await {
-                        Ok(redirect_spf) => {
+                    let target_name = eval.expand(macro_string, &vars, &domain);
+                    eval.record(|| SpfEvent::Redirect {
+                        domain: trace_name(&domain),
+                        target: trace_name(&target_name),
+                    });
+                    let redirect_spf = self
+                        .txt_lookup_with_dnssec::<Spf>(target_name.as_ref())
+                        .await;
+                    eval.record_spf(
+                        target_name.as_ref(),
+                        redirect_spf.as_ref().map(|(spf, _)| spf),
+                    );
+                    match redirect_spf {
+                        Ok((redirect_spf, dnssec)) => {
+                            output.add_dnssec(dnssec);
                             let new_domain = target_name.to_string();
                             spf_record = redirect_spf;
                             directives = spf_record.directives.iter().enumerate().skip(0);
@@ -366,14 +537,21 @@ impl Resolver {
         }
 
         // Evaluate explain
-        if let (Some(macro_string), Some(SpfResult::Fail { .. })) = (&spf_record.exp, &result) {
-            if let Ok(macro_string) = self
-                .txt_lookup::<Macro>(macro_string.eval(&vars, &domain, true).to_string())
+        if let (Some(macro_string), Some(SpfResult::Fail)) = (&spf_record.exp, &result) {
+            let target_name = eval.expand(macro_string, &vars, &domain);
+            let explanation = self
+                .txt_lookup::<Macro>(target_name.to_string())
                 .await
-            {
+                .map(|macro_string| macro_string.eval(&vars, &domain, false).to_string());
+            eval.record(|| SpfEvent::DnsQuery {
+                query: SpfQuery::Txt,
+                name: trace_name(&target_name),
+                answer: explanation.clone().map(|explanation| vec![explanation]),
+            });
+            if let Ok(explanation) = explanation {
                 return output
                     .with_result(SpfResult::Fail)
-                    .with_explanation(macro_string.eval(&vars, &domain, false).to_string())
+                    .with_explanation(explanation)
                     .with_report(&spf_record);
             }
         }
@@ -389,18 +567,23 @@ impl Resolver {
         ip: IpAddr,
         ip4_mask: u32,
         ip6_mask: u128,
+        eval: &mut Evaluation,
     ) -> crate::Result<bool> {
         Ok(match ip {
-            IpAddr::V4(ip) => self
-                .ipv4_lookup(target_name)
-                .await?
-                .iter()
-                .any(|addr| ip.matches_ipv4_mask(addr, ip4_mask)),
-            IpAddr::V6(ip) => self
-                .ipv6_lookup(target_name)
-                .await?
-                .iter()
-                .any(|addr| ip.matches_ipv6_mask(addr, ip6_mask)),
+            IpAddr::V4(ip) => {
+                let addrs = self.ipv4_lookup(target_name).await;
+                eval.record_query(SpfQuery::A, target_name, &addrs);
+                addrs?
+                    .iter()
+                    .any(|addr| ip.matches_ipv4_mask(addr, ip4_mask))
+            }
+            IpAddr::V6(ip) => {
+                let addrs = self.ipv6_lookup(target_name).await;
+                eval.record_query(SpfQuery::Aaaa, target_name, &addrs);
+                addrs?
+                    .iter()
+                    .any(|addr| ip.matches_ipv6_mask(addr, ip6_mask))
+            }
         })
     }
 }
@@ -475,28 +658,118 @@ impl From<Error> for SpfResult {
     }
 }
 
-struct LookupLimit {
-    num_lookups: u32,
+/// State of a single SPF evaluation: the limits consumed so far and, when
+/// tracing, the events recorded.
+pub(crate) struct Evaluation {
+    limits: SpfLimits,
+    lookups: u32,
+    void_lookups: u32,
     timer: Instant,
+    exceeded: Option<SpfLimit>,
+    events: Option<Vec<SpfEvent>>,
 }
 
-impl LookupLimit {
-    pub fn new() -> Self {
-        LookupLimit {
-            num_lookups: 1,
+impl Evaluation {
+    pub(crate) fn new(limits: SpfLimits, trace: bool) -> Self {
+        Evaluation {
+            limits,
+            lookups: 0,
+            void_lookups: 0,
             timer: Instant::now(),
+            exceeded: None,
+            events: trace.then(Vec::new),
         }
     }
 
+    pub(crate) fn lookups(&self) -> u32 {
+        self.lookups
+    }
+
+    pub(crate) fn into_events(self) -> Vec<SpfEvent> {
+        self.events.unwrap_or_default()
+    }
+
     #[inline(always)]
     fn can_lookup(&mut self) -> bool {
-        if self.num_lookups <= 10 && self.timer.elapsed().as_secs() < 20 {
-            self.num_lookups += 1;
-            true
+        if self.timer.elapsed() >= self.limits.timeout {
+            self.exceed(SpfLimit::Timeout)
+        } else if self.lookups >= self.limits.dns_lookups {
+            self.exceed(SpfLimit::DnsLookups)
         } else {
-            false
+            self.lookups += 1;
+            true
+        }
+    }
+
+    /// Counts a query that returned no records.
+    fn void_lookup(&mut self) -> bool {
+        self.void_lookups += 1;
+        self.void_lookups <= self.limits.void_lookups || self.exceed(SpfLimit::VoidLookups)
+    }
+
+    fn exceed(&mut self, limit: SpfLimit) -> bool {
+        self.exceeded = Some(limit);
+        self.record(|| SpfEvent::LimitExceeded(limit));
+        false
+    }
+
+    fn limit_error(&self, output: SpfOutput) -> SpfOutput {
+        output
+            .with_result(SpfResult::PermError)
+            .with_limit(self.exceeded)
+    }
+
+    #[inline(always)]
+    fn record(&mut self, event: impl FnOnce() -> SpfEvent) {
+        if let Some(events) = &mut self.events {
+            events.push(event());
         }
     }
+
+    fn record_spf(&mut self, domain: &str, record: Result<&Arc<Spf>, &Error>) {
+        self.record(|| SpfEvent::Record {
+            domain: trace_name(domain),
+            record: record.cloned().map_err(Clone::clone),
+        });
+    }
+
+    fn record_query<T: ToString>(
+        &mut self,
+        query: SpfQuery,
+        name: &str,
+        answer: &crate::Result<Arc<Vec<T>>>,
+    ) {
+        self.record(|| SpfEvent::DnsQuery {
+            query,
+            name: trace_name(name),
+            answer: answer
+                .as_ref()
+                .map(|records| records.iter().map(ToString::to_string).collect())
+                .map_err(Clone::clone),
+        });
+    }
+
+    fn expand<'x>(
+        &mut self,
+        macro_string: &'x Macro,
+        vars: &'x Variables<'x>,
+        domain: &'x str,
+    ) -> Cow<'x, str> {
+        let expanded = macro_string.eval(vars, domain, true);
+        if matches!(macro_string, Macro::Variable { .. } | Macro::List(_)) {
+            self.record(|| SpfEvent::Macro {
+                macro_string: macro_string.clone(),
+                expanded: trace_name(&expanded),
+            });
+        }
+        expanded
+    }
+}
+
+/// Domain names are recorded in trace events without the trailing dot that
+/// macro expansion adds, so that each domain is always named the same way.
+fn trace_name(name: &str) -> String {
+    name.strip_suffix('.').unwrap_or(name).to_string()
 }
 
 pub trait HasValidLabels {
@@ -541,8 +814,8 @@ mod test {
     };
 
     use crate::{
-        common::parse::TxtRecordParser,
-        spf::{Macro, Spf},
+        common::{dns::DnsZone, parse::TxtRecordParser},
+        spf::{Macro, Spf, SpfLimit, SpfLimits},
         Resolver, SpfResult, MX,
     };
 
@@ -559,7 +832,7 @@ mod test {
             let test_suite = String::from_utf8(fs::read(&file_name).unwrap()).unwrap();
 
             for test in test_suite.split("---\n") {
-                let resolver = Resolver::new_system_conf().unwrap();
+                let resolver = Resolver::new_mock();
                 let mut test_name = "";
                 let mut last_test_name = "";
                 let mut helo = "";
@@ -680,4 +953,86 @@ mod test {
             }
         }
     }
+
+    #[tokio::test]
+    async fn spf_limits() {
+        let zone = DnsZone::new();
+        zone.add_txt(
+            "lookups.example.org",
+            "v=spf1 a:a1.example.org a:a2.example.org a:a3.example.org ~all",
+        )
+        .add_txt(
+            "void.example.org",
+            "v=spf1 a:v1.example.org a:v2.example.org a:v3.example.org ~all",
+        )
+        .add_txt("mx.example.org", "v=spf1 mx -all");
+        for num in 1..=3 {
+            zone.add_ipv4(format!("a{num}.example.org"), Ipv4Addr::new(192, 0, 2, num))
+                .add_mx("mx.example.org", 10, format!("mx{num}.example.org"))
+                .add_ipv4(
+                    format!("mx{num}.example.org"),
+                    Ipv4Addr::new(192, 0, 2, 10 + num),
+                );
+        }
+        let resolver = Resolver::with_backend(zone, 128);
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 13));
+
+        for (domain, limits, result, limit) in [
+            (
+                "lookups.example.org",
+                SpfLimits::default(),
+                SpfResult::SoftFail,
+                None,
+            ),
+            (
+                "lookups.example.org",
+                SpfLimits::default().with_dns_lookups(2),
+                SpfResult::PermError,
+                Some(SpfLimit::DnsLookups),
+            ),
+            (
+                "void.example.org",
+                SpfLimits::default(),
+                SpfResult::PermError,
+                Some(SpfLimit::VoidLookups),
+            ),
+            (
+                "void.example.org",
+                SpfLimits::unlimited(),
+                SpfResult::SoftFail,
+                None,
+            ),
+            (
+                "mx.example.org",
+                SpfLimits::default(),
+                SpfResult::Pass,
+                None,
+            ),
+            (
+                "mx.example.org",
+                SpfLimits::default().with_mx_addresses(2),
+                SpfResult::PermError,
+                Some(SpfLimit::MxAddresses),
+            ),
+            (
+                "mx.example.org",
+                SpfLimits::default().with_timeout(Duration::ZERO),
+                SpfResult::PermError,
+                Some(SpfLimit::Timeout),
+            ),
+        ] {
+            let output = resolver
+                .check_host_with_limits(
+                    ip,
+                    domain,
+                    "mx.example.net",
+                    "localdomain.org",
+                    &format!("sender@{domain}"),
+                    limits,
+                )
+                .await;
+            assert_eq!(output.result(), result, "{domain} {limits:?}");
+            assert_eq!(output.exceeded_limit(), limit, "{domain} {limits:?}");
+        }
+    }
 }